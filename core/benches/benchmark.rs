@@ -124,6 +124,75 @@ fn limbo_bench(criterion: &mut Criterion) {
             });
         },
     );
+
+    // 110k rows, so that the constant path is applied many times per statement.
+    let mut stmt = conn
+        .prepare("SELECT json_extract('{\"a\":[1,2,3]}', '$.a[1]') FROM users, products")
+        .unwrap();
+    group.bench_function(
+        "Execute prepared statement: json_extract with a constant path over 110k rows",
+        |b| {
+            let io = io.clone();
+            b.iter(|| {
+                let mut rows = stmt.query().unwrap();
+                loop {
+                    match rows.next_row().unwrap() {
+                        limbo_core::StepResult::Row(row) => {
+                            assert_eq!(row.get::<i64>(0).unwrap(), 2);
+                        }
+                        limbo_core::StepResult::IO => {
+                            io.run_once().unwrap();
+                        }
+                        limbo_core::StepResult::Done => {
+                            break;
+                        }
+                        limbo_core::StepResult::Interrupt | limbo_core::StepResult::Busy => {
+                            unreachable!();
+                        }
+                    }
+                }
+                stmt.reset();
+            });
+        },
+    );
+
+    // A document of many short strings without escapes, parsed again for every row.
+    let strings = (0..200)
+        .map(|i| format!("\"name{}\"", i))
+        .collect::<Vec<_>>()
+        .join(",");
+    let mut stmt = conn
+        .prepare(format!(
+            "SELECT json_array_length('[{}]') FROM users LIMIT 1000",
+            strings
+        ))
+        .unwrap();
+    group.bench_function(
+        "Execute prepared statement: json_array_length of a string-heavy array over 1000 rows",
+        |b| {
+            let io = io.clone();
+            b.iter(|| {
+                let mut rows = stmt.query().unwrap();
+                loop {
+                    match rows.next_row().unwrap() {
+                        limbo_core::StepResult::Row(row) => {
+                            assert_eq!(row.get::<i64>(0).unwrap(), 200);
+                        }
+                        limbo_core::StepResult::IO => {
+                            io.run_once().unwrap();
+                        }
+                        limbo_core::StepResult::Done => {
+                            break;
+                        }
+                        limbo_core::StepResult::Interrupt | limbo_core::StepResult::Busy => {
+                            unreachable!();
+                        }
+                    }
+                }
+                stmt.reset();
+            });
+        },
+    );
 }
 
 fn rusqlite_bench(criterion: &mut Criterion) {
@@ -0,0 +1,219 @@
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use limbo_core::{Database, PlatformIO, Row, Statement, Value, IO};
+use pprof::criterion::{Output, PProfProfiler};
+use std::sync::Arc;
+
+// Compare the string scanning with and without the `simd` feature:
+//   cargo bench --bench json_benchmark
+//   cargo bench --bench json_benchmark --features simd
+
+/// About a megabyte of JSON made mostly of strings without escapes, already minified, so that
+/// json() returns it unchanged.
+fn string_heavy_json() -> String {
+    let items = (0..10_000)
+        .map(|i| {
+            format!(
+                r#"{{"id":"item-{0}","title":"The quick brown fox jumps over the lazy dog {0}","tags":["alpha","bravo","charlie"]}}"#,
+                i
+            )
+        })
+        .collect::<Vec<_>>();
+    format!("[{}]", items.join(","))
+}
+
+/// An object with 100 members, each an object of a few members, like a wide row stored as JSON.
+fn wide_object_json() -> String {
+    let members = (0..100)
+        .map(|i| {
+            format!(
+                r#""key{0}":{{"id":{0},"name":"member {0}","scores":[{0},{0}.5,null]}}"#,
+                i
+            )
+        })
+        .collect::<Vec<_>>();
+    format!("{{{}}}", members.join(","))
+}
+
+/// About 10 MB of JSON: an array of small objects, with a space after every separator so that
+/// json() can't return it as it is and has to parse it.
+fn large_array_json() -> String {
+    let items = (0..100_000)
+        .map(|i| {
+            format!(
+                r#"{{"id": {0}, "name": "user {0}", "email": "user{0}@example.com", "scores": [{0}, {0}.5, null]}}"#,
+                i
+            )
+        })
+        .collect::<Vec<_>>();
+    format!("[{}]", items.join(", "))
+}
+
+/// The maximum resident set size of the process so far, in kilobytes.
+fn max_rss_kb() -> i64 {
+    let mut usage = std::mem::MaybeUninit::<libc::rusage>::uninit();
+    assert_eq!(
+        unsafe { libc::getrusage(libc::RUSAGE_SELF, usage.as_mut_ptr()) },
+        0
+    );
+    unsafe { usage.assume_init() }.ru_maxrss
+}
+
+fn query_one<T>(stmt: &mut Statement, io: &Arc<PlatformIO>, get: impl Fn(&Row) -> T) -> T {
+    let result = loop {
+        match stmt.step().unwrap() {
+            limbo_core::StepResult::Row(row) => break get(&row),
+            limbo_core::StepResult::IO => {
+                io.run_once().unwrap();
+            }
+            limbo_core::StepResult::Done
+            | limbo_core::StepResult::Interrupt
+            | limbo_core::StepResult::Busy => {
+                unreachable!();
+            }
+        }
+    };
+    stmt.reset();
+    result
+}
+
+fn bench(criterion: &mut Criterion) {
+    let json = string_heavy_json();
+    assert!(json.len() >= 1 << 20);
+
+    let mut group = criterion.benchmark_group("json");
+    group.throughput(Throughput::Bytes(json.len() as u64));
+    #[allow(clippy::arc_with_non_send_sync)]
+    let io = Arc::new(PlatformIO::new().unwrap());
+    let db = Database::open_file(io.clone(), "../testing/testing.db").unwrap();
+    let conn = db.connect();
+
+    let mut stmt = conn.prepare(format!("SELECT json('{}')", json)).unwrap();
+    group.bench_function("json() of 1 MiB of string-heavy JSON", |b| {
+        b.iter(|| {
+            // The output must be byte-identical to the input, whichever way strings are scanned.
+            let output = query_one(&mut stmt, &io, |row| row.get::<String>(0).unwrap());
+            assert!(output == json);
+        });
+    });
+
+    let mut stmt = conn
+        .prepare(format!("SELECT json_valid('{}', 1)", json))
+        .unwrap();
+    group.bench_function("json_valid(_, 1) of 1 MiB of string-heavy JSON", |b| {
+        b.iter(|| {
+            assert_eq!(
+                query_one(&mut stmt, &io, |row| row.get::<i64>(0).unwrap()),
+                1
+            );
+        });
+    });
+}
+
+fn bench_extract(criterion: &mut Criterion) {
+    // More documents than the parse cache holds, so that every row has to be parsed again.
+    let documents = (0..8)
+        .map(|i| wide_object_json().replace("\"key0\"", &format!("\"row\":{},\"key0\"", i)))
+        .collect::<Vec<_>>();
+
+    let mut group = criterion.benchmark_group("json_extract");
+    group.throughput(Throughput::Bytes(documents[0].len() as u64));
+    #[allow(clippy::arc_with_non_send_sync)]
+    let io = Arc::new(PlatformIO::new().unwrap());
+    let db = Database::open_file(io.clone(), "../testing/testing.db").unwrap();
+    let conn = db.connect();
+
+    let mut stmt = conn.prepare("SELECT json_extract(?, '$.key50')").unwrap();
+    let mut documents = documents.iter().cycle();
+    group.bench_function("$.key of an object with 100 members", |b| {
+        b.iter(|| {
+            stmt.bind_at(
+                1.try_into().unwrap(),
+                Value::Text(documents.next().unwrap()),
+            );
+            let output = query_one(&mut stmt, &io, |row| row.get::<String>(0).unwrap());
+            assert_eq!(
+                output,
+                r#"{"id":50,"name":"member 50","scores":[50,50.5,null]}"#
+            );
+        });
+    });
+}
+
+fn bench_group_array(criterion: &mut Criterion) {
+    let mut group = criterion.benchmark_group("json_group_array");
+    group.sample_size(10);
+    #[allow(clippy::arc_with_non_send_sync)]
+    let io = Arc::new(PlatformIO::new().unwrap());
+    let db = Database::open_file(io.clone(), "../testing/testing.db").unwrap();
+    let conn = db.connect();
+
+    // 10,000 users times 100 gives a million small rows. The elements are written out as they
+    // are stepped, so the peak memory is about the size of the resulting text (~3 MB) rather
+    // than a `Val` per row; compare the maximum resident set size with that of count(a.age).
+    let mut stmt = conn
+        .prepare("SELECT json_group_array(a.age) FROM users a, users b WHERE b.id <= 100")
+        .unwrap();
+    group.throughput(Throughput::Elements(1_000_000));
+    group.bench_function("1M integers", |b| {
+        b.iter(|| {
+            let output = query_one(&mut stmt, &io, |row| row.get::<String>(0).unwrap());
+            assert!(output.starts_with('[') && output.ends_with(']'));
+        });
+    });
+}
+
+fn bench_validate(criterion: &mut Criterion) {
+    let json = large_array_json();
+    assert!(json.len() >= 10_000_000);
+
+    let mut group = criterion.benchmark_group("json_valid");
+    group.throughput(Throughput::Bytes(json.len() as u64));
+    group.sample_size(10);
+    #[allow(clippy::arc_with_non_send_sync)]
+    let io = Arc::new(PlatformIO::new().unwrap());
+    let db = Database::open_file(io.clone(), "../testing/testing.db").unwrap();
+    let conn = db.connect();
+
+    let mut valid = conn
+        .prepare(format!("SELECT json_valid('{}')", json))
+        .unwrap();
+    let mut parsed = conn.prepare(format!("SELECT json('{}')", json)).unwrap();
+
+    // json_valid() scans the text without building any values, while json() parses it into a
+    // tree first, like json_valid() used to. The maximum resident set size only ever grows, so
+    // json_valid() is measured first, after a first run has allocated what any run needs.
+    query_one(&mut valid, &io, |row| row.get::<i64>(0).unwrap());
+    let baseline = max_rss_kb();
+    query_one(&mut valid, &io, |row| row.get::<i64>(0).unwrap());
+    let after_valid = max_rss_kb();
+    query_one(&mut parsed, &io, |row| row.get::<String>(0).unwrap());
+    let after_parsed = max_rss_kb();
+    println!(
+        "peak memory growth for {} bytes of JSON: json_valid() {} kB, json() {} kB",
+        json.len(),
+        after_valid - baseline,
+        after_parsed - after_valid
+    );
+
+    group.bench_function("json_valid() of 10 MB of JSON", |b| {
+        b.iter(|| {
+            assert_eq!(
+                query_one(&mut valid, &io, |row| row.get::<i64>(0).unwrap()),
+                1
+            );
+        });
+    });
+    group.bench_function("json() of 10 MB of JSON", |b| {
+        b.iter(|| {
+            let output = query_one(&mut parsed, &io, |row| row.get::<String>(0).unwrap());
+            assert!(output.len() < json.len());
+        });
+    });
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().with_profiler(PProfProfiler::new(100, Output::Flamegraph(None)));
+    targets = bench, bench_extract, bench_group_array, bench_validate
+}
+criterion_main!(benches);
@@ -41,6 +41,8 @@ pub enum LimboError {
     InvalidModifier(String),
     #[error("Runtime error: {0}")]
     Constraint(String),
+    #[error("Runtime error: JSON cannot hold BLOB values")]
+    BlobInJson,
     #[error("Extension error: {0}")]
     ExtensionError(String),
     #[error("Unbound parameter at index {0}")]
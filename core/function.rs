@@ -71,14 +71,39 @@ impl Display for ExternalFunc {
 #[derive(Debug, Clone, PartialEq)]
 pub enum JsonFunc {
     Json,
+    Jsonb,
     JsonArray,
+    JsonbArray,
     JsonArrayLength,
+    JsonKeys,
+    JsonValues,
     JsonArrowExtract,
     JsonArrowShiftExtract,
     JsonExtract,
+    JsonQuery,
     JsonObject,
+    JsonbObject,
     JsonType,
     JsonErrorPosition,
+    JsonValid,
+    JsonValidDocument,
+    JsonQuote,
+    JsonCanonicalize,
+    JsonEscape,
+    JsonUnescape,
+    JsonInsert,
+    JsonbInsert,
+    JsonReplace,
+    JsonbReplace,
+    JsonSet,
+    JsonbSet,
+    JsonPatch,
+    JsonConcat,
+    JsonContains,
+    JsonEqual,
+    JsonPatchOps,
+    JsonRemove,
+    JsonbRemove,
 }
 
 #[cfg(feature = "json")]
@@ -89,14 +114,39 @@ impl Display for JsonFunc {
             "{}",
             match self {
                 Self::Json => "json".to_string(),
+                Self::Jsonb => "jsonb".to_string(),
                 Self::JsonArray => "json_array".to_string(),
+                Self::JsonbArray => "jsonb_array".to_string(),
                 Self::JsonExtract => "json_extract".to_string(),
+                Self::JsonQuery => "json_query".to_string(),
                 Self::JsonArrayLength => "json_array_length".to_string(),
+                Self::JsonKeys => "json_keys".to_string(),
+                Self::JsonValues => "json_values".to_string(),
                 Self::JsonArrowExtract => "->".to_string(),
                 Self::JsonArrowShiftExtract => "->>".to_string(),
                 Self::JsonObject => "json_object".to_string(),
+                Self::JsonbObject => "jsonb_object".to_string(),
                 Self::JsonType => "json_type".to_string(),
                 Self::JsonErrorPosition => "json_error_position".to_string(),
+                Self::JsonValid => "json_valid".to_string(),
+                Self::JsonValidDocument => "json_valid_document".to_string(),
+                Self::JsonQuote => "json_quote".to_string(),
+                Self::JsonCanonicalize => "json_canonicalize".to_string(),
+                Self::JsonEscape => "json_escape".to_string(),
+                Self::JsonUnescape => "json_unescape".to_string(),
+                Self::JsonInsert => "json_insert".to_string(),
+                Self::JsonbInsert => "jsonb_insert".to_string(),
+                Self::JsonReplace => "json_replace".to_string(),
+                Self::JsonbReplace => "jsonb_replace".to_string(),
+                Self::JsonSet => "json_set".to_string(),
+                Self::JsonbSet => "jsonb_set".to_string(),
+                Self::JsonPatch => "json_patch".to_string(),
+                Self::JsonConcat => "json_concat".to_string(),
+                Self::JsonContains => "json_contains".to_string(),
+                Self::JsonEqual => "json_equal".to_string(),
+                Self::JsonPatchOps => "json_patch_ops".to_string(),
+                Self::JsonRemove => "json_remove".to_string(),
+                Self::JsonbRemove => "jsonb_remove".to_string(),
             }
         )
     }
@@ -113,6 +163,10 @@ pub enum AggFunc {
     StringAgg,
     Sum,
     Total,
+    #[cfg(feature = "json")]
+    JsonGroupArray,
+    #[cfg(feature = "json")]
+    JsonGroupObject,
     External(Rc<ExtFunc>),
 }
 
@@ -127,6 +181,10 @@ impl PartialEq for AggFunc {
             | (Self::StringAgg, Self::StringAgg)
             | (Self::Sum, Self::Sum)
             | (Self::Total, Self::Total) => true,
+            #[cfg(feature = "json")]
+            (Self::JsonGroupArray, Self::JsonGroupArray) => true,
+            #[cfg(feature = "json")]
+            (Self::JsonGroupObject, Self::JsonGroupObject) => true,
             (Self::External(a), Self::External(b)) => Rc::ptr_eq(a, b),
             _ => false,
         }
@@ -145,6 +203,10 @@ impl AggFunc {
             Self::StringAgg => 2,
             Self::Sum => 1,
             Self::Total => 1,
+            #[cfg(feature = "json")]
+            Self::JsonGroupArray => 1,
+            #[cfg(feature = "json")]
+            Self::JsonGroupObject => 2,
             Self::External(func) => func.agg_args().unwrap_or(0),
         }
     }
@@ -160,6 +222,10 @@ impl AggFunc {
             Self::StringAgg => "string_agg",
             Self::Sum => "sum",
             Self::Total => "total",
+            #[cfg(feature = "json")]
+            Self::JsonGroupArray => "json_group_array",
+            #[cfg(feature = "json")]
+            Self::JsonGroupObject => "json_group_object",
             Self::External(_) => "extension function",
         }
     }
@@ -469,6 +535,20 @@ impl Func {
                 }
                 Ok(Self::Agg(AggFunc::Total))
             }
+            #[cfg(feature = "json")]
+            "json_group_array" => {
+                if arg_count != 1 {
+                    crate::bail_parse_error!("wrong number of arguments to function {}()", name)
+                }
+                Ok(Self::Agg(AggFunc::JsonGroupArray))
+            }
+            #[cfg(feature = "json")]
+            "json_group_object" => {
+                if arg_count != 2 {
+                    crate::bail_parse_error!("wrong number of arguments to function {}()", name)
+                }
+                Ok(Self::Agg(AggFunc::JsonGroupObject))
+            }
             "char" => Ok(Self::Scalar(ScalarFunc::Char)),
             "coalesce" => Ok(Self::Scalar(ScalarFunc::Coalesce)),
             "concat" => Ok(Self::Scalar(ScalarFunc::Concat)),
@@ -506,17 +586,67 @@ impl Func {
             #[cfg(feature = "json")]
             "json" => Ok(Self::Json(JsonFunc::Json)),
             #[cfg(feature = "json")]
+            "jsonb" => Ok(Self::Json(JsonFunc::Jsonb)),
+            #[cfg(feature = "json")]
             "json_array_length" => Ok(Self::Json(JsonFunc::JsonArrayLength)),
             #[cfg(feature = "json")]
+            "json_keys" => Ok(Self::Json(JsonFunc::JsonKeys)),
+            #[cfg(feature = "json")]
+            "json_values" => Ok(Self::Json(JsonFunc::JsonValues)),
+            #[cfg(feature = "json")]
             "json_array" => Ok(Self::Json(JsonFunc::JsonArray)),
             #[cfg(feature = "json")]
+            "jsonb_array" => Ok(Self::Json(JsonFunc::JsonbArray)),
+            #[cfg(feature = "json")]
             "json_extract" => Ok(Func::Json(JsonFunc::JsonExtract)),
             #[cfg(feature = "json")]
+            "json_query" => Ok(Func::Json(JsonFunc::JsonQuery)),
+            #[cfg(feature = "json")]
             "json_object" => Ok(Func::Json(JsonFunc::JsonObject)),
             #[cfg(feature = "json")]
+            "jsonb_object" => Ok(Func::Json(JsonFunc::JsonbObject)),
+            #[cfg(feature = "json")]
             "json_type" => Ok(Func::Json(JsonFunc::JsonType)),
             #[cfg(feature = "json")]
             "json_error_position" => Ok(Self::Json(JsonFunc::JsonErrorPosition)),
+            #[cfg(feature = "json")]
+            "json_valid" => Ok(Self::Json(JsonFunc::JsonValid)),
+            #[cfg(feature = "json")]
+            "json_valid_document" => Ok(Self::Json(JsonFunc::JsonValidDocument)),
+            #[cfg(feature = "json")]
+            "json_quote" => Ok(Self::Json(JsonFunc::JsonQuote)),
+            #[cfg(feature = "json")]
+            "json_canonicalize" => Ok(Self::Json(JsonFunc::JsonCanonicalize)),
+            #[cfg(feature = "json")]
+            "json_escape" => Ok(Self::Json(JsonFunc::JsonEscape)),
+            #[cfg(feature = "json")]
+            "json_unescape" => Ok(Self::Json(JsonFunc::JsonUnescape)),
+            #[cfg(feature = "json")]
+            "json_insert" => Ok(Self::Json(JsonFunc::JsonInsert)),
+            #[cfg(feature = "json")]
+            "jsonb_insert" => Ok(Self::Json(JsonFunc::JsonbInsert)),
+            #[cfg(feature = "json")]
+            "json_replace" => Ok(Self::Json(JsonFunc::JsonReplace)),
+            #[cfg(feature = "json")]
+            "jsonb_replace" => Ok(Self::Json(JsonFunc::JsonbReplace)),
+            #[cfg(feature = "json")]
+            "json_set" => Ok(Self::Json(JsonFunc::JsonSet)),
+            #[cfg(feature = "json")]
+            "jsonb_set" => Ok(Self::Json(JsonFunc::JsonbSet)),
+            #[cfg(feature = "json")]
+            "json_patch" => Ok(Self::Json(JsonFunc::JsonPatch)),
+            #[cfg(feature = "json")]
+            "json_concat" => Ok(Self::Json(JsonFunc::JsonConcat)),
+            #[cfg(feature = "json")]
+            "json_contains" => Ok(Self::Json(JsonFunc::JsonContains)),
+            #[cfg(feature = "json")]
+            "json_equal" => Ok(Self::Json(JsonFunc::JsonEqual)),
+            #[cfg(feature = "json")]
+            "json_patch_ops" => Ok(Self::Json(JsonFunc::JsonPatchOps)),
+            #[cfg(feature = "json")]
+            "json_remove" => Ok(Self::Json(JsonFunc::JsonRemove)),
+            #[cfg(feature = "json")]
+            "jsonb_remove" => Ok(Self::Json(JsonFunc::JsonbRemove)),
             "unixepoch" => Ok(Self::Scalar(ScalarFunc::UnixEpoch)),
             "julianday" => Ok(Self::Scalar(ScalarFunc::JulianDay)),
             "hex" => Ok(Self::Scalar(ScalarFunc::Hex)),
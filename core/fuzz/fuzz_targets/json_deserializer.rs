@@ -0,0 +1,15 @@
+//! Feeds arbitrary input to the JSON deserializer, which must return an error for anything it
+//! can't parse rather than panic or overflow the stack. Run from core/ with:
+//!   cargo +nightly fuzz run json_deserializer
+//! The corpus is seeded with JSON and JSON5 inputs from the unit tests.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use limbo_core::{JsonLimits, Val};
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(text) = std::str::from_utf8(data) {
+        let _ = Val::parse(text, &JsonLimits::default());
+    }
+});
@@ -0,0 +1,232 @@
+//! Constructors for building `Val` trees in code, eg. `Val::object().insert("a", 1)`, without
+//! writing JSON text and parsing it back. With the `json_macro` feature, `json_val!` builds them
+//! from JSON-like syntax.
+
+use std::borrow::Cow;
+
+use indexmap::IndexMap;
+
+use crate::json::Val;
+
+impl<'a> Val<'a> {
+    /// An empty object, to be filled with `insert`.
+    pub fn object() -> Self {
+        Val::Object(IndexMap::new())
+    }
+
+    /// An empty array, to be filled with `push`.
+    pub fn array() -> Self {
+        Val::Array(Vec::new())
+    }
+
+    /// Adds the member `key` to the object. Like with json_object(), a key that is already there
+    /// is replaced and moves to the end, as if only the last member with that key had been
+    /// given.
+    ///
+    /// Panics if `self` isn't an object.
+    pub fn insert(mut self, key: impl Into<String>, value: impl Into<Val<'a>>) -> Self {
+        let Val::Object(members) = &mut self else {
+            panic!("insert() called on a JSON {}", self.type_name());
+        };
+        let key = key.into();
+        members.shift_remove(&key);
+        members.insert(key, value.into());
+        self
+    }
+
+    /// Appends `value` to the array.
+    ///
+    /// Panics if `self` isn't an array.
+    pub fn push(mut self, value: impl Into<Val<'a>>) -> Self {
+        let Val::Array(items) = &mut self else {
+            panic!("push() called on a JSON {}", self.type_name());
+        };
+        items.push(value.into());
+        self
+    }
+}
+
+impl From<bool> for Val<'_> {
+    fn from(b: bool) -> Self {
+        Val::Bool(b)
+    }
+}
+
+impl From<i32> for Val<'_> {
+    fn from(i: i32) -> Self {
+        Val::Integer(i.into())
+    }
+}
+
+impl From<i64> for Val<'_> {
+    fn from(i: i64) -> Self {
+        Val::Integer(i)
+    }
+}
+
+impl From<f64> for Val<'_> {
+    fn from(f: f64) -> Self {
+        Val::Float(f)
+    }
+}
+
+impl<'a> From<&'a str> for Val<'a> {
+    fn from(s: &'a str) -> Self {
+        Val::String(Cow::Borrowed(s))
+    }
+}
+
+impl From<String> for Val<'_> {
+    fn from(s: String) -> Self {
+        Val::String(Cow::Owned(s))
+    }
+}
+
+/// `None` becomes `null`.
+impl<'a, T: Into<Val<'a>>> From<Option<T>> for Val<'a> {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Val::Null, Into::into)
+    }
+}
+
+impl<'a, T: Into<Val<'a>>> From<Vec<T>> for Val<'a> {
+    fn from(items: Vec<T>) -> Self {
+        Val::Array(items.into_iter().map(Into::into).collect())
+    }
+}
+
+/// Builds a `Val` from JSON-like syntax. Keys are string literals, `null` is JSON null, and any
+/// other value is either a nested array or object, or an expression converted with `Val::from`:
+///
+/// ```ignore
+/// let tags = vec!["a", "b"];
+/// let value = json_val!({"id": 1, "name": name, "tags": tags, "parent": null, "scores": [1.5, -2]});
+/// ```
+#[cfg(feature = "json_macro")]
+#[macro_export]
+macro_rules! json_val {
+    (null) => {
+        $crate::Val::Null
+    };
+    ([ $($items:tt)* ]) => {{
+        #[allow(unused_mut)]
+        let mut array = $crate::Val::array();
+        $crate::json_val!(@array array $($items)*);
+        array
+    }};
+    ({ $($members:tt)* }) => {{
+        #[allow(unused_mut)]
+        let mut object = $crate::Val::object();
+        $crate::json_val!(@object object $($members)*);
+        object
+    }};
+    ($other:expr) => {
+        $crate::Val::from($other)
+    };
+
+    // Array elements, one at a time.
+    (@array $array:ident) => {};
+    (@array $array:ident [ $($item:tt)* ] $(, $($rest:tt)*)?) => {
+        $array = $array.push($crate::json_val!([ $($item)* ]));
+        $crate::json_val!(@array $array $($($rest)*)?);
+    };
+    (@array $array:ident { $($item:tt)* } $(, $($rest:tt)*)?) => {
+        $array = $array.push($crate::json_val!({ $($item)* }));
+        $crate::json_val!(@array $array $($($rest)*)?);
+    };
+    (@array $array:ident null $(, $($rest:tt)*)?) => {
+        $array = $array.push($crate::Val::Null);
+        $crate::json_val!(@array $array $($($rest)*)?);
+    };
+    (@array $array:ident $item:expr $(, $($rest:tt)*)?) => {
+        $array = $array.push($crate::Val::from($item));
+        $crate::json_val!(@array $array $($($rest)*)?);
+    };
+
+    // Object members, one at a time.
+    (@object $object:ident) => {};
+    (@object $object:ident $key:literal : [ $($value:tt)* ] $(, $($rest:tt)*)?) => {
+        $object = $object.insert($key, $crate::json_val!([ $($value)* ]));
+        $crate::json_val!(@object $object $($($rest)*)?);
+    };
+    (@object $object:ident $key:literal : { $($value:tt)* } $(, $($rest:tt)*)?) => {
+        $object = $object.insert($key, $crate::json_val!({ $($value)* }));
+        $crate::json_val!(@object $object $($($rest)*)?);
+    };
+    (@object $object:ident $key:literal : null $(, $($rest:tt)*)?) => {
+        $object = $object.insert($key, $crate::Val::Null);
+        $crate::json_val!(@object $object $($($rest)*)?);
+    };
+    (@object $object:ident $key:literal : $value:expr $(, $($rest:tt)*)?) => {
+        $object = $object.insert($key, $crate::Val::from($value));
+        $crate::json_val!(@object $object $($($rest)*)?);
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json::{from_str, to_string};
+
+    #[test]
+    fn test_builder() {
+        let value = Val::object()
+            .insert("id", 1)
+            .insert("name", "x")
+            .insert("tags", vec!["a", "b"])
+            .insert("parent", None::<i64>)
+            .insert(
+                "scores",
+                Val::array().push(1.5).push(-2).push(Val::object()),
+            )
+            .insert("id", 2_i64);
+        assert_eq!(
+            to_string(&value).unwrap(),
+            r#"{"name":"x","tags":["a","b"],"parent":null,"scores":[1.5,-2,{}],"id":2}"#
+        );
+        // Duplicate keys end up where parsing puts them.
+        assert_eq!(
+            Val::object().insert("a", 1).insert("b", 2).insert("a", 3),
+            from_str::<Val>(r#"{"a":1,"b":2,"a":3}"#).unwrap()
+        );
+
+        assert_eq!(Val::from(true), Val::Bool(true));
+        assert_eq!(Val::from(String::from("s")), Val::String("s".into()));
+        assert_eq!(Val::from(Some(3)), Val::Integer(3));
+    }
+
+    #[test]
+    #[should_panic(expected = "insert() called on a JSON array")]
+    fn test_builder_insert_into_array() {
+        let _ = Val::array().insert("a", 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "push() called on a JSON object")]
+    fn test_builder_push_to_object() {
+        let _ = Val::object().push(1);
+    }
+
+    #[cfg(feature = "json_macro")]
+    #[test]
+    fn test_json_val_macro() {
+        let name = String::from("x");
+        let tags = vec!["a", "b"];
+        let value = crate::json_val!({
+            "id": 1,
+            "name": name,
+            "tags": tags,
+            "parent": null,
+            "scores": [1.5, -2, 1 + 2, [], {}, null, [true, {"k": false}],],
+            "nested": {"a": {"b": [null]}},
+        });
+        assert_eq!(
+            to_string(&value).unwrap(),
+            r#"{"id":1,"name":"x","tags":["a","b"],"parent":null,"scores":[1.5,-2,3,[],{},null,[true,{"k":false}]],"nested":{"a":{"b":[null]}}}"#
+        );
+        assert_eq!(crate::json_val!(null), Val::Null);
+        assert_eq!(crate::json_val!("s"), Val::String("s".into()));
+        assert_eq!(crate::json_val!([]), Val::array());
+        assert_eq!(crate::json_val!({}), Val::object());
+    }
+}
@@ -1,26 +1,170 @@
-use pest::iterators::Pair;
+use indexmap::IndexMap;
+use pest::iterators::{Pair, Pairs};
 use pest::Parser as P;
 use pest_derive::Parser;
-use serde::de;
+use serde::de::{self, IntoDeserializer, MapAccess, SeqAccess, Visitor};
 use serde::forward_to_deserialize_any;
+use serde::Deserialize;
+use std::borrow::Cow;
 use std::collections::VecDeque;
+use std::iter::Peekable;
 
 use crate::json::error::{self, Error, Result};
+use crate::json::scan::find_string_special;
+use crate::json::{Val, JSON_MAX_DEPTH, JSON_MAX_LENGTH};
 
 #[derive(Parser)]
 #[grammar = "json/json.pest"]
 struct Parser;
 
 /// Deserialize an instance of type `T` from a string of JSON5 text. Can fail if the input is
-/// invalid JSON5, or doesn&rsquo;t match the structure of the target type.
+/// invalid JSON5, or doesn&rsquo;t match the structure of the target type. The input must hold a
+/// single value, with nothing but whitespace and comments around it. A leading byte order mark
+/// is skipped as whitespace.
 pub fn from_str<'a, T>(s: &'a str) -> Result<T>
 where
     T: de::Deserialize<'a>,
 {
-    let mut deserializer = Deserializer::from_str(s)?;
+    from_str_with_limits(s, &JsonLimits::default())
+}
+
+/// Same as `from_str`, but with custom limits on the input, eg. to reject large documents early.
+pub fn from_str_with_limits<'a, T>(s: &'a str, limits: &JsonLimits) -> Result<T>
+where
+    T: de::Deserialize<'a>,
+{
+    let mut deserializer = Deserializer::from_str_with_limits(s, limits)?;
     T::deserialize(&mut deserializer)
 }
 
+/// Limits on the JSON text the parser accepts. They are checked before parsing, so input over a
+/// limit is rejected without building any values.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct JsonLimits {
+    /// How deeply arrays and objects can be nested. The parser is recursive, so raising this far
+    /// above the default risks overflowing the stack.
+    pub max_depth: usize,
+    /// The length of the text in bytes.
+    pub max_length: usize,
+}
+
+/// The limits SQLite has: a depth of 1000 and a length of a billion bytes.
+impl Default for JsonLimits {
+    fn default() -> Self {
+        Self {
+            max_depth: JSON_MAX_DEPTH,
+            max_length: JSON_MAX_LENGTH,
+        }
+    }
+}
+
+impl JsonLimits {
+    /// Fails when `input` goes over any of the limits.
+    fn check(&self, input: &str) -> Result<()> {
+        if input.len() > self.max_length {
+            return Err(Error::TooLong {
+                length: input.len(),
+                max_length: self.max_length,
+            });
+        }
+        check_depth(input, self.max_depth)
+    }
+}
+
+/// The type of the top-level value of a JSON text, as reported by the validators.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum JsonKind {
+    Null,
+    Bool,
+    Number,
+    String,
+    Array,
+    Object,
+}
+
+impl JsonKind {
+    /// Whether the value is an array or an object, rather than a scalar.
+    pub fn is_container(self) -> bool {
+        matches!(self, JsonKind::Array | JsonKind::Object)
+    }
+}
+
+/// Checks whether `s` is JSON5 text that `from_str` would accept, without building any values:
+/// the text is scanned a byte at a time and only numbers and string escapes, which can still fail
+/// to convert, are checked. Returns the type of the top-level value.
+pub fn validate(s: &str) -> Result<JsonKind> {
+    JsonLimits::default().check(s)?;
+    Json5Scanner {
+        input: s,
+        bytes: s.as_bytes(),
+        pos: 0,
+    }
+    .scan_text()
+}
+
+/// Fails when arrays and objects in `input` are nested deeper than `max_depth`. This runs before
+/// parsing, as the parser is recursive and would overflow the stack on pathological input.
+fn check_depth(input: &str, max_depth: usize) -> Result<()> {
+    // Every byte looked at is ASCII, which never occurs inside a multi-byte character.
+    let bytes = input.as_bytes();
+    let mut depth = 0usize;
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            quote @ (b'"' | b'\'') => {
+                i += 1;
+                while i < bytes.len() {
+                    i += find_string_special(&bytes[i..], quote);
+                    match bytes.get(i) {
+                        Some(b'\\') => i += 2,
+                        Some(&b) if b == quote => break,
+                        _ => i += 1,
+                    }
+                }
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'/') => {
+                i += 2;
+                while i < bytes.len() && bytes[i] != b'\n' {
+                    i += 1;
+                }
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'*') => {
+                i += 2;
+                while i < bytes.len() && !bytes[i..].starts_with(b"*/") {
+                    i += 1;
+                }
+                i += 1;
+            }
+            b'[' | b'{' => {
+                depth += 1;
+                if depth > max_depth {
+                    return Err(Error::depth_exceeded_at(input, i));
+                }
+            }
+            b']' | b'}' => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+        i += 1;
+    }
+    Ok(())
+}
+
+/// Converts an error pest reported while parsing `input`. Pest points at the start of the last
+/// token it tried rather than past it, so text that's cut short, eg. `[1,2`, is told apart by
+/// scanning it, which fails at the end of the input then.
+fn parse_error(err: pest::error::Error<Rule>, input: &str) -> Error {
+    let scanned = Json5Scanner {
+        input,
+        bytes: input.as_bytes(),
+        pos: 0,
+    }
+    .scan_text();
+    match scanned {
+        Err(eof @ Error::UnexpectedEof { .. }) => eof,
+        _ => Error::from_pest(err, input),
+    }
+}
+
 /// A Deserializes JSON data into a Rust value.
 pub struct Deserializer<'de> {
     pair: Option<Pair<'de, Rule>>,
@@ -28,10 +172,19 @@ pub struct Deserializer<'de> {
 
 impl<'de> Deserializer<'de> {
     /// Creates a JSON5 deserializer from a `&str`. This parses the input at construction time, so
-    /// can fail if the input is not valid JSON5.
+    /// can fail if the input is not valid JSON5 or goes over the default `JsonLimits`.
     #[allow(clippy::should_implement_trait)]
     pub fn from_str(input: &'de str) -> Result<Self> {
-        let pair = Parser::parse(Rule::text, input)?.next().unwrap();
+        Self::from_str_with_limits(input, &JsonLimits::default())
+    }
+
+    /// Same as `from_str`, with custom limits on the input.
+    pub fn from_str_with_limits(input: &'de str, limits: &JsonLimits) -> Result<Self> {
+        limits.check(input)?;
+        let pair = Parser::parse(Rule::text, input)
+            .map_err(|err| parse_error(err, input))?
+            .next()
+            .unwrap();
         Ok(Deserializer::from_pair(pair))
     }
 
@@ -52,18 +205,16 @@ impl<'de> de::Deserializer<'de> for &mut Deserializer<'de> {
         let mut res = (move || match pair.as_rule() {
             Rule::null => visitor.visit_unit(),
             Rule::boolean => visitor.visit_bool(parse_bool(&pair)),
-            Rule::string | Rule::identifier => visitor.visit_string(parse_string(pair)?),
-            Rule::number => match pair.as_str() {
-                "Infinity" | "+Infinity" => visitor.visit_f64(f64::INFINITY),
-                "-Infinity" => visitor.visit_f64(f64::NEG_INFINITY),
-                "NaN" | "-NaN" => visitor.visit_f64(f64::NAN),
-                _ => {
-                    if is_int(pair.as_str()) {
-                        visitor.visit_i64(parse_integer(&pair)?)
-                    } else {
-                        visitor.visit_f64(parse_number(&pair)?)
-                    }
-                }
+            Rule::string | Rule::identifier => match parse_string(pair)? {
+                Cow::Borrowed(s) => visitor.visit_borrowed_str(s),
+                Cow::Owned(s) => visitor.visit_string(s),
+            },
+            Rule::number => match classify_number(pair.as_str())? {
+                Number::Integer(i) => visitor.visit_i64(i),
+                Number::Float(f) => visitor.visit_f64(f),
+                // Other numbers are passed on as RFC 8259 text, so that re-serializing them
+                // doesn't change their formatting.
+                Number::Raw(text) => visitor.visit_newtype_struct(text.into_deserializer()),
             },
             Rule::array => visitor.visit_seq(Seq::new(pair)),
             Rule::object => visitor.visit_map(Map::new(pair)),
@@ -253,6 +404,87 @@ impl<'de> de::Deserializer<'de> for &mut Deserializer<'de> {
     }
 }
 
+impl<'de> Deserialize<'de> for Val<'de> {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        deserializer.deserialize_any(ValVisitor)
+    }
+}
+
+struct ValVisitor;
+
+impl<'de> Visitor<'de> for ValVisitor {
+    type Value = Val<'de>;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("a JSON value")
+    }
+
+    fn visit_unit<E>(self) -> std::result::Result<Val<'de>, E> {
+        Ok(Val::Null)
+    }
+
+    fn visit_bool<E>(self, v: bool) -> std::result::Result<Val<'de>, E> {
+        Ok(Val::Bool(v))
+    }
+
+    fn visit_i64<E>(self, v: i64) -> std::result::Result<Val<'de>, E> {
+        Ok(Val::Integer(v))
+    }
+
+    fn visit_f64<E>(self, v: f64) -> std::result::Result<Val<'de>, E> {
+        Ok(Val::Float(v))
+    }
+
+    fn visit_borrowed_str<E>(self, v: &'de str) -> std::result::Result<Val<'de>, E> {
+        Ok(Val::String(Cow::Borrowed(v)))
+    }
+
+    fn visit_str<E>(self, v: &str) -> std::result::Result<Val<'de>, E> {
+        Ok(Val::String(Cow::Owned(v.to_string())))
+    }
+
+    fn visit_string<E>(self, v: String) -> std::result::Result<Val<'de>, E> {
+        Ok(Val::String(Cow::Owned(v)))
+    }
+
+    /// The deserializer hands over numbers that don't need interpretation as a newtype
+    /// wrapping their source text.
+    fn visit_newtype_struct<D>(self, deserializer: D) -> std::result::Result<Val<'de>, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        String::deserialize(deserializer).map(Val::RawNumber)
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> std::result::Result<Val<'de>, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let mut items = Vec::new();
+        while let Some(item) = seq.next_element()? {
+            items.push(item);
+        }
+        Ok(Val::Array(items))
+    }
+
+    fn visit_map<A>(self, mut map: A) -> std::result::Result<Val<'de>, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut members = IndexMap::new();
+        while let Some((key, value)) = map.next_entry()? {
+            // Duplicate labels: the last occurrence wins, and takes the last position. This
+            // differs from SQLite, which keeps every occurrence.
+            members.shift_remove(&key);
+            members.insert(key, value);
+        }
+        Ok(Val::Object(members))
+    }
+}
+
 fn parse_bool(pair: &Pair<'_, Rule>) -> bool {
     match pair.as_str() {
         "true" => true,
@@ -261,26 +493,81 @@ fn parse_bool(pair: &Pair<'_, Rule>) -> bool {
     }
 }
 
-fn parse_string(pair: Pair<'_, Rule>) -> Result<String> {
+/// Decodes the escape sequences of `contents`, the text of a double-quoted string without its
+/// quotes. JSON5 escapes are accepted like in a document; a `"` that isn't escaped is an error.
+pub fn unescape(contents: &str) -> Result<String> {
+    let quoted = format!("\"{contents}\"");
+    let pair = Parser::parse(Rule::string, &quoted)
+        .map_err(|err| Error::from_pest(err, &quoted))?
+        .next()
+        .unwrap();
+    if pair.as_str().len() != quoted.len() {
+        return Err(Error::invalid_escape("unescaped quote in string"));
+    }
+    parse_string(pair).map(Cow::into_owned)
+}
+
+/// Returns the contents of a string or identifier. Without escapes, that's a slice of the input.
+fn parse_string(pair: Pair<'_, Rule>) -> Result<Cow<'_, str>> {
+    let text = pair.as_str();
+    let contents = match pair.as_rule() {
+        Rule::identifier => text,
+        _ if text.len() >= 4 && text.starts_with("''") => &text[2..text.len() - 2],
+        _ => &text[1..text.len() - 1],
+    };
+    if !contents.contains('\\') {
+        return Ok(Cow::Borrowed(contents));
+    }
+
     let span = pair.as_span();
-    let mut res = pair
-        .into_inner()
-        .map(|component| match component.as_rule() {
-            Rule::char_literal => Ok(String::from(component.as_str())),
-            Rule::char_escape_sequence => Ok(parse_char_escape_sequence(&component)),
-            Rule::nul_escape_sequence => Ok(String::from("\u{0000}")),
-            Rule::hex_escape_sequence => u8::from_str_radix(component.as_str(), 16)
-                .map(|value| format!("\\u{:04X}", value))
-                .map_err(|_| de::Error::custom("error hex sequence")), // TODO: FIX HEX SEQUENCE TO MATCH SQLITE
-            Rule::unicode_escape_sequence => {
-                let hex_escape = parse_hex(component.as_str())?;
-                Ok(hex_escape.to_string())
+    let mut components = pair.into_inner().peekable();
+    let mut res: Result<String> = (|| {
+        let mut s = String::new();
+        while let Some(component) = components.next() {
+            match component.as_rule() {
+                Rule::char_literal => s.push_str(component.as_str()),
+                Rule::char_escape_sequence => s.push_str(&parse_char_escape_sequence(&component)),
+                Rule::nul_escape_sequence => s.push('\u{0000}'),
+                Rule::hex_escape_sequence => {
+                    // TODO: FIX HEX SEQUENCE TO MATCH SQLITE
+                    let value = u8::from_str_radix(component.as_str(), 16)
+                        .map_err(|_| Error::invalid_escape("error hex sequence"))?;
+                    s.push_str(&format!("\\u{:04X}", value))
+                }
+                Rule::unicode_escape_sequence => {
+                    s.push(parse_unicode_escape(&component, &mut components)?)
+                }
+                _ => unreachable!(),
             }
-            _ => unreachable!(),
-        })
-        .collect();
+        }
+        Ok(s)
+    })();
     error::set_location(&mut res, &span);
-    res
+    res.map(Cow::Owned)
+}
+
+/// Decodes a `\uXXXX` escape. A high surrogate is combined with the low surrogate escape that
+/// must directly follow it; a surrogate on its own is an error.
+fn parse_unicode_escape<'a>(
+    component: &Pair<'a, Rule>,
+    rest: &mut Peekable<Pairs<'a, Rule>>,
+) -> Result<char> {
+    let code_unit = |pair: &Pair<'_, Rule>| u32::from_str_radix(pair.as_str(), 16).ok();
+    let lone_surrogate = || Error::invalid_escape("lone surrogate in unicode escape");
+    let high = code_unit(component).ok_or_else(|| Error::invalid_escape("error unicode escape"))?;
+    let scalar = match high {
+        0xD800..=0xDBFF => {
+            let low = rest
+                .next_if(|next| next.as_rule() == Rule::unicode_escape_sequence)
+                .and_then(|next| code_unit(&next))
+                .filter(|low| (0xDC00..=0xDFFF).contains(low))
+                .ok_or_else(lone_surrogate)?;
+            0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)
+        }
+        0xDC00..=0xDFFF => return Err(lone_surrogate()),
+        _ => high,
+    };
+    char::from_u32(scalar).ok_or_else(lone_surrogate)
 }
 
 fn parse_char_escape_sequence(pair: &Pair<'_, Rule>) -> String {
@@ -306,26 +593,103 @@ fn parse_number(pair: &Pair<'_, Rule>) -> Result<f64> {
             if let Ok(r) = s.parse::<f64>() {
                 if r.is_finite() {
                     Ok(r)
+                } else if let Some(infinity) = written_infinity(s) {
+                    Ok(infinity)
                 } else {
-                    Err(de::Error::custom("error parsing number: too large"))
+                    Err(Error::invalid_number("error parsing number: too large"))
                 }
             } else {
-                Err(de::Error::custom("error parsing number"))
+                Err(Error::invalid_number("error parsing number"))
             }
         }
     }
 }
 
-fn parse_integer(pair: &Pair<'_, Rule>) -> Result<i64> {
-    match pair.as_str() {
-        s if is_hex_literal(s) => {
-            let parsed = parse_hex(s)? as i64;
-            Ok(parsed)
+/// A number token, as handed over to the visitor by `deserialize_any`.
+enum Number<'a> {
+    Integer(i64),
+    Float(f64),
+    Raw(Cow<'a, str>),
+}
+
+fn classify_number(s: &str) -> Result<Number<'_>> {
+    match s {
+        "Infinity" | "+Infinity" => Ok(Number::Float(f64::INFINITY)),
+        "-Infinity" => Ok(Number::Float(f64::NEG_INFINITY)),
+        "NaN" | "-NaN" => Ok(Number::Float(f64::NAN)),
+        _ => {
+            let number = normalize_number(s)?;
+            if is_int(&number) {
+                // Integers that don't fit in 64 bits keep their digits instead of being rounded
+                // to a float, so that they're written back exactly, like SQLite does. So does
+                // `-0`, which would otherwise lose its sign.
+                match number.parse::<i64>() {
+                    Ok(i) if i != 0 || !number.starts_with('-') => Ok(Number::Integer(i)),
+                    _ => Ok(Number::Raw(number)),
+                }
+            } else {
+                match number.parse::<f64>() {
+                    Ok(r) if r.is_finite() => Ok(Number::Raw(number)),
+                    Ok(_) => written_infinity(&number)
+                        .map(Number::Float)
+                        .ok_or_else(|| Error::invalid_number("error parsing number: too large")),
+                    Err(_) => Err(Error::invalid_number("error parsing number")),
+                }
+            }
+        }
+    }
+}
+
+/// Reads `9e999` and `-9e999`, the way infinities are written (like in SQLite), as infinities, so
+/// that JSON with infinities parses again once it's been written out. Other reals too large for
+/// an `f64`, eg. `1e999`, are an error.
+fn written_infinity(s: &str) -> Option<f64> {
+    match s {
+        "9e999" => Some(f64::INFINITY),
+        "-9e999" => Some(f64::NEG_INFINITY),
+        _ => None,
+    }
+}
+
+/// Rewrites a JSON5 number literal into RFC 8259 form, the same way SQLite does: hex literals
+/// become decimal integers, a leading `+` is dropped and missing digits around the decimal point
+/// are filled in, eg. `+.5` becomes `0.5` and `5.e1` becomes `5.0e1`.
+fn normalize_number(s: &str) -> Result<Cow<'_, str>> {
+    let (sign, unsigned) = match s.as_bytes().first() {
+        Some(b'-') => ("-", &s[1..]),
+        Some(b'+') => ("", &s[1..]),
+        _ => ("", s),
+    };
+
+    if is_hex_literal(s) {
+        return match u64::from_str_radix(&unsigned[2..], 16) {
+            Ok(value) => Ok(Cow::Owned(format!("{}{}", sign, value))),
+            Err(_) => Err(Error::invalid_number("error parsing hex")),
+        };
+    }
+
+    let dot = unsigned.find('.');
+    let missing_leading_digit = dot == Some(0);
+    let missing_trailing_digit =
+        dot.is_some_and(|dot| !unsigned[dot + 1..].starts_with(|c: char| c.is_ascii_digit()));
+    if !s.starts_with('+') && !missing_leading_digit && !missing_trailing_digit {
+        return Ok(Cow::Borrowed(s));
+    }
+
+    let mut normalized = String::with_capacity(s.len() + 2);
+    normalized.push_str(sign);
+    if missing_leading_digit {
+        normalized.push('0');
+    }
+    match dot {
+        Some(dot) if missing_trailing_digit => {
+            normalized.push_str(&unsigned[..=dot]);
+            normalized.push('0');
+            normalized.push_str(&unsigned[dot + 1..]);
         }
-        s => s
-            .parse()
-            .map_err(|_| de::Error::custom("error parsing integer")),
+        _ => normalized.push_str(unsigned),
     }
+    Ok(Cow::Owned(normalized))
 }
 
 fn is_int(s: &str) -> bool {
@@ -340,7 +704,7 @@ fn parse_hex(s: &str) -> Result<i32> {
     };
     i32::from_str_radix(trimmed, 16)
         .map(|v| v * sign)
-        .map_err(|_| de::Error::custom("error parsing hex"))
+        .map_err(|_| Error::invalid_number("error parsing hex"))
 }
 
 fn is_hex_literal(s: &str) -> bool {
@@ -348,6 +712,971 @@ fn is_hex_literal(s: &str) -> bool {
     trimmed.len() > 2 && (&trimmed[..2] == "0x" || &trimmed[..2] == "0X")
 }
 
+/// Checks whether `input` is a single RFC 8259 JSON text, returning the type of its top-level
+/// value if it is. Unlike `from_str`, none of the JSON5 extensions (comments, unquoted keys,
+/// trailing commas, hex numbers...) are accepted.
+pub fn strict_json_kind(input: &str) -> Option<JsonKind> {
+    let mut scanner = StrictScanner {
+        bytes: input.as_bytes(),
+        pos: 0,
+    };
+    if !scanner.scan_text() {
+        return None;
+    }
+    let kind = match input.trim_start_matches([' ', '\t', '\n', '\r']).as_bytes()[0] {
+        b'n' => JsonKind::Null,
+        b't' | b'f' => JsonKind::Bool,
+        b'"' => JsonKind::String,
+        b'[' => JsonKind::Array,
+        b'{' => JsonKind::Object,
+        _ => JsonKind::Number,
+    };
+    Some(kind)
+}
+
+/// Returns the json_type() name of the top-level value of `input` from its first token alone,
+/// without looking at the rest of the text. Numbers are told apart by scanning their literal for
+/// a fraction or an exponent. `input` must already be known to be valid, eg. checked with
+/// `validate` or written by the serializer. Returns `None` when it doesn't start like a strict
+/// JSON value, eg. with a comment, a hex number or `Infinity`, whose type takes parsing to tell.
+pub fn top_level_type(input: &str) -> Option<&'static str> {
+    let trimmed = input.trim_start_matches([' ', '\t', '\n', '\r']);
+    let type_name = match trimmed.as_bytes().first()? {
+        b'{' => "object",
+        b'[' => "array",
+        b'"' => "text",
+        b't' => "true",
+        b'f' => "false",
+        b'n' => "null",
+        b'-' | b'0'..=b'9' => {
+            let len = trimmed
+                .bytes()
+                .position(|b| !matches!(b, b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9'))
+                .unwrap_or(trimmed.len());
+            let (literal, rest) = trimmed.split_at(len);
+            if !literal.bytes().any(|b| b.is_ascii_digit())
+                || rest
+                    .bytes()
+                    .next()
+                    .is_some_and(|b| b.is_ascii_alphanumeric())
+            {
+                return None;
+            }
+            if literal.contains(['.', 'e', 'E']) {
+                "real"
+            } else {
+                "integer"
+            }
+        }
+        _ => return None,
+    };
+    Some(type_name)
+}
+
+/// Finds the member `key` of a strict JSON object and returns the text of its value, without
+/// building any values, so that a single member can be extracted without parsing its siblings.
+/// Like when the object is parsed, the last member wins when a key is duplicated. Returns `None`
+/// when `input` isn't a strict JSON object, eg. JSON5 text, and `Some(None)` when it has no
+/// such member.
+pub fn find_object_member<'a>(input: &'a str, key: &str) -> Option<Option<&'a str>> {
+    let mut scanner = StrictScanner {
+        bytes: input.as_bytes(),
+        pos: 0,
+    };
+    let mut found = None;
+
+    scanner.skip_whitespace();
+    if scanner.next() != Some(b'{') {
+        return None;
+    }
+    scanner.skip_whitespace();
+    if scanner.peek() == Some(b'}') {
+        scanner.pos += 1;
+    } else {
+        loop {
+            scanner.skip_whitespace();
+            let key_start = scanner.pos;
+            if !scanner.scan_key() {
+                return None;
+            }
+            let member_key = input[key_start..scanner.pos - 1].trim_end();
+            scanner.skip_whitespace();
+            let value_start = scanner.pos;
+            if !scanner.scan_value(1) {
+                return None;
+            }
+            if key_matches(&member_key[1..member_key.len() - 1], key) {
+                found = Some(&input[value_start..scanner.pos]);
+            }
+            scanner.skip_whitespace();
+            match scanner.next() {
+                Some(b',') => {}
+                Some(b'}') => break,
+                _ => return None,
+            }
+        }
+    }
+
+    scanner.skip_whitespace();
+    (scanner.pos == input.len()).then_some(found)
+}
+
+/// Counts the elements of a strict JSON array, skipping over them without building any values,
+/// so that nested arrays and objects and brackets in strings don't count. Returns `None` when
+/// `input` isn't a strict JSON array, eg. JSON5 text or any other value.
+pub fn strict_array_length(input: &str) -> Option<usize> {
+    let mut scanner = StrictScanner {
+        bytes: input.as_bytes(),
+        pos: 0,
+    };
+    let mut length = 0;
+
+    scanner.skip_whitespace();
+    if scanner.next() != Some(b'[') {
+        return None;
+    }
+    scanner.skip_whitespace();
+    if scanner.peek() == Some(b']') {
+        scanner.pos += 1;
+    } else {
+        loop {
+            if !scanner.scan_value(1) {
+                return None;
+            }
+            length += 1;
+            scanner.skip_whitespace();
+            match scanner.next() {
+                Some(b',') => {}
+                Some(b']') => break,
+                _ => return None,
+            }
+        }
+    }
+
+    scanner.skip_whitespace();
+    (scanner.pos == input.len()).then_some(length)
+}
+
+/// Parses the elements of a top-level JSON array one at a time, as they're asked for, so that
+/// only the element being parsed is in memory rather than the whole array. Each element is
+/// delimited by scanning it as strict JSON and then parsed on its own. When the scan fails, eg.
+/// on JSON5 text, the whole text is parsed at that point, and the elements left are taken from
+/// it, so the cursor accepts the same text as `from_str` and reports the same errors. Elements
+/// before a malformed one have been yielded by then, and nothing follows the error.
+///
+/// `json_each` iterates over an array at the top of its input with a cursor.
+#[derive(Debug)]
+pub struct ArrayCursor<'a> {
+    input: &'a str,
+    state: CursorState<'a>,
+}
+
+#[derive(Debug)]
+enum CursorState<'a> {
+    /// Nothing has been read yet.
+    Start,
+    /// Element `index` starts at byte `pos`, after any whitespace.
+    Scanning {
+        pos: usize,
+        index: usize,
+    },
+    /// The elements left, taken from parsing the whole text.
+    Parsed(std::vec::IntoIter<Val<'a>>),
+    Done,
+}
+
+impl<'a> ArrayCursor<'a> {
+    pub fn new(input: &'a str) -> Self {
+        Self {
+            input,
+            state: CursorState::Start,
+        }
+    }
+
+    fn scanner(&self, pos: usize) -> StrictScanner<'a> {
+        StrictScanner {
+            bytes: self.input.as_bytes(),
+            pos,
+        }
+    }
+
+    /// Parses the whole text, and goes on with the elements after the first `index` ones.
+    fn parse_rest(&mut self, index: usize) -> Option<Result<Val<'a>>> {
+        self.state = CursorState::Done;
+        match from_str::<Val>(self.input) {
+            Ok(Val::Array(mut items)) => {
+                items.drain(..index.min(items.len()));
+                self.state = CursorState::Parsed(items.into_iter());
+                self.next()
+            }
+            Ok(_) => Some(Err(Error::Message {
+                msg: "expected a JSON array".to_string(),
+                location: None,
+                token: None,
+            })),
+            Err(err) => Some(Err(err)),
+        }
+    }
+}
+
+impl<'a> Iterator for ArrayCursor<'a> {
+    type Item = Result<Val<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match std::mem::replace(&mut self.state, CursorState::Done) {
+            CursorState::Start => {
+                if let Err(err) = JsonLimits::default().check(self.input) {
+                    return Some(Err(err));
+                }
+                let mut scanner = self.scanner(0);
+                scanner.skip_whitespace();
+                if scanner.next() != Some(b'[') {
+                    return self.parse_rest(0);
+                }
+                scanner.skip_whitespace();
+                if scanner.peek() == Some(b']') {
+                    scanner.pos += 1;
+                    scanner.skip_whitespace();
+                    return if scanner.pos == self.input.len() {
+                        None
+                    } else {
+                        self.parse_rest(0)
+                    };
+                }
+                self.state = CursorState::Scanning {
+                    pos: scanner.pos,
+                    index: 0,
+                };
+                self.next()
+            }
+            CursorState::Scanning { pos, index } => {
+                let mut scanner = self.scanner(pos);
+                if !scanner.scan_value(1) {
+                    return self.parse_rest(index);
+                }
+                let element = &self.input[pos..scanner.pos];
+                scanner.skip_whitespace();
+                match scanner.next() {
+                    Some(b',') => {
+                        scanner.skip_whitespace();
+                        self.state = CursorState::Scanning {
+                            pos: scanner.pos,
+                            index: index + 1,
+                        };
+                    }
+                    Some(b']') => {
+                        scanner.skip_whitespace();
+                        if scanner.pos != self.input.len() {
+                            return self.parse_rest(index);
+                        }
+                    }
+                    _ => return self.parse_rest(index),
+                }
+                match from_str::<Val>(element) {
+                    Ok(value) => Some(Ok(value)),
+                    // Reported by parsing the whole text, for the error to point into it.
+                    Err(_) => self.parse_rest(index),
+                }
+            }
+            CursorState::Parsed(mut items) => {
+                let item = items.next()?;
+                self.state = CursorState::Parsed(items);
+                Some(Ok(item))
+            }
+            CursorState::Done => None,
+        }
+    }
+}
+
+/// Whether the contents of a JSON string, escapes included, spell `key`.
+fn key_matches(raw: &str, key: &str) -> bool {
+    if !raw.contains('\\') {
+        return raw == key;
+    }
+    let quoted = format!("\"{}\"", raw);
+    matches!(from_str::<Val>(&quoted), Ok(Val::String(unescaped)) if unescaped == key)
+}
+
+/// Scans strict JSON without building any values. Containers are tracked with an explicit stack
+/// so deeply nested input can't overflow the call stack.
+struct StrictScanner<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl StrictScanner<'_> {
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<u8> {
+        let b = self.peek();
+        if b.is_some() {
+            self.pos += 1;
+        }
+        b
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.peek() {
+            self.pos += 1;
+        }
+    }
+
+    fn scan_text(&mut self) -> bool {
+        if !self.scan_value(0) {
+            return false;
+        }
+        self.skip_whitespace();
+        self.pos == self.bytes.len()
+    }
+
+    /// Scans a single value nested in `depth` containers, stopping right after it.
+    fn scan_value(&mut self, depth: usize) -> bool {
+        let mut containers = Vec::new();
+
+        loop {
+            self.skip_whitespace();
+            match self.peek() {
+                Some(open @ (b'[' | b'{')) => {
+                    if depth + containers.len() >= JSON_MAX_DEPTH {
+                        return false;
+                    }
+                    self.pos += 1;
+                    self.skip_whitespace();
+                    let close = if open == b'[' { b']' } else { b'}' };
+                    if self.peek() == Some(close) {
+                        self.pos += 1;
+                    } else {
+                        if open == b'{' && !self.scan_key() {
+                            return false;
+                        }
+                        containers.push(open);
+                        continue;
+                    }
+                }
+                Some(b'"') => {
+                    if !self.scan_string() {
+                        return false;
+                    }
+                }
+                Some(b'-' | b'0'..=b'9') => {
+                    if !self.scan_number() {
+                        return false;
+                    }
+                }
+                Some(b't') => {
+                    if !self.scan_literal(b"true") {
+                        return false;
+                    }
+                }
+                Some(b'f') => {
+                    if !self.scan_literal(b"false") {
+                        return false;
+                    }
+                }
+                Some(b'n') => {
+                    if !self.scan_literal(b"null") {
+                        return false;
+                    }
+                }
+                _ => return false,
+            }
+
+            // A value was just completed: close finished containers until a separator shows up.
+            while let Some(&container) = containers.last() {
+                self.skip_whitespace();
+                match container {
+                    b'[' => match self.next() {
+                        Some(b',') => break,
+                        Some(b']') => {
+                            containers.pop();
+                        }
+                        _ => return false,
+                    },
+                    _ => match self.next() {
+                        Some(b',') => {
+                            if !self.scan_key() {
+                                return false;
+                            }
+                            break;
+                        }
+                        Some(b'}') => {
+                            containers.pop();
+                        }
+                        _ => return false,
+                    },
+                }
+            }
+            if containers.is_empty() {
+                return true;
+            }
+        }
+    }
+
+    fn scan_key(&mut self) -> bool {
+        self.skip_whitespace();
+        if self.peek() != Some(b'"') || !self.scan_string() {
+            return false;
+        }
+        self.skip_whitespace();
+        self.next() == Some(b':')
+    }
+
+    fn scan_literal(&mut self, literal: &[u8]) -> bool {
+        if self.bytes[self.pos..].starts_with(literal) {
+            self.pos += literal.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn scan_string(&mut self) -> bool {
+        self.pos += 1; // opening quote
+        loop {
+            self.pos += find_string_special(&self.bytes[self.pos..], b'"');
+            match self.next() {
+                Some(b'"') => return true,
+                Some(b'\\') => match self.next() {
+                    Some(b'"' | b'\\' | b'/' | b'b' | b'f' | b'n' | b'r' | b't') => {}
+                    Some(b'u') => {
+                        for _ in 0..4 {
+                            if !self.next().is_some_and(|b| b.is_ascii_hexdigit()) {
+                                return false;
+                            }
+                        }
+                    }
+                    _ => return false,
+                },
+                Some(0x00..=0x1F) | None => return false,
+                Some(_) => {}
+            }
+        }
+    }
+
+    fn scan_digits(&mut self) -> bool {
+        let start = self.pos;
+        while self.peek().is_some_and(|b| b.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        self.pos > start
+    }
+
+    fn scan_number(&mut self) -> bool {
+        let start = self.pos;
+        let mut is_float = false;
+        if self.peek() == Some(b'-') {
+            self.pos += 1;
+        }
+        match self.peek() {
+            Some(b'0') => self.pos += 1,
+            Some(b'1'..=b'9') => {
+                self.scan_digits();
+            }
+            _ => return false,
+        }
+        if self.peek() == Some(b'.') {
+            self.pos += 1;
+            is_float = true;
+            if !self.scan_digits() {
+                return false;
+            }
+        }
+        if let Some(b'e' | b'E') = self.peek() {
+            self.pos += 1;
+            is_float = true;
+            if let Some(b'+' | b'-') = self.peek() {
+                self.pos += 1;
+            }
+            if !self.scan_digits() {
+                return false;
+            }
+        }
+        // Like the parser, reject reals too large for an f64, eg. 1e999, but for infinities.
+        !is_float
+            || std::str::from_utf8(&self.bytes[start..self.pos])
+                .ok()
+                .is_some_and(|number| {
+                    number.parse::<f64>().is_ok_and(f64::is_finite)
+                        || written_infinity(number).is_some()
+                })
+    }
+}
+
+/// Scans JSON5 text the way the grammar in json.pest parses it, without building any values or
+/// tokens. Like `StrictScanner`, containers are tracked with an explicit stack. Errors are
+/// reported where the text can't go on, so input that's cut short fails at its end.
+struct Json5Scanner<'a> {
+    input: &'a str,
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+/// How a string or identifier scanned by `Json5Scanner` ended.
+enum Json5Token {
+    /// It ends right before the byte offset, and its escapes hold a lone surrogate if the flag is
+    /// set.
+    Complete(usize, bool),
+    /// It can't go on at the byte offset.
+    Failed(usize),
+}
+
+impl Json5Scanner<'_> {
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.pos).copied()
+    }
+
+    fn peek_char(&self) -> Option<char> {
+        self.input[self.pos..].chars().next()
+    }
+
+    fn unexpected(&self) -> Error {
+        Error::syntax_at(self.input, self.pos)
+    }
+
+    fn scan_text(&mut self) -> Result<JsonKind> {
+        self.skip_whitespace();
+        let kind = match self.peek() {
+            Some(b'n') => JsonKind::Null,
+            Some(b't' | b'f') => JsonKind::Bool,
+            Some(b'"' | b'\'') => JsonKind::String,
+            Some(b'[') => JsonKind::Array,
+            Some(b'{') => JsonKind::Object,
+            _ => JsonKind::Number,
+        };
+        self.scan_value()?;
+        self.skip_whitespace();
+        if self.pos < self.bytes.len() {
+            return Err(self.unexpected());
+        }
+        Ok(kind)
+    }
+
+    /// Skips whitespace and comments. An unterminated block comment is left in place, to fail as
+    /// the next token.
+    fn skip_whitespace(&mut self) {
+        loop {
+            let rest = &self.bytes[self.pos..];
+            match rest {
+                [b'\t' | b'\n' | 0x0B | 0x0C | b'\r' | b' ', ..] => self.pos += 1,
+                [b'/', b'*', comment @ ..] => match comment.windows(2).position(|w| w == b"*/") {
+                    Some(end) => self.pos += 2 + end + 2,
+                    None => return,
+                },
+                [b'/', b'/', ..] => {
+                    self.pos += 2;
+                    while let Some(c) = self.peek_char().filter(|&c| !is_line_terminator(c)) {
+                        self.pos += c.len_utf8();
+                    }
+                }
+                [b, ..] if *b >= 0x80 => match self.peek_char() {
+                    Some(c) if is_json5_whitespace(c) => self.pos += c.len_utf8(),
+                    _ => return,
+                },
+                _ => return,
+            }
+        }
+    }
+
+    /// Scans a single value, stopping right after it.
+    fn scan_value(&mut self) -> Result<()> {
+        let mut containers = Vec::new();
+
+        loop {
+            self.skip_whitespace();
+            match self.peek() {
+                Some(open @ (b'[' | b'{')) => {
+                    self.pos += 1;
+                    self.skip_whitespace();
+                    let close = if open == b'[' { b']' } else { b'}' };
+                    if self.peek() == Some(close) {
+                        self.pos += 1;
+                    } else {
+                        if open == b'{' {
+                            self.scan_key()?;
+                        }
+                        containers.push(close);
+                        continue;
+                    }
+                }
+                Some(b'"' | b'\'') => self.scan_string()?,
+                Some(b'n') => self.scan_literal("null")?,
+                Some(b't') => self.scan_literal("true")?,
+                Some(b'f') => self.scan_literal("false")?,
+                _ => self.scan_number()?,
+            }
+
+            // A value was just completed: close finished containers until a separator shows up.
+            while let Some(&close) = containers.last() {
+                self.skip_whitespace();
+                match self.peek() {
+                    Some(b',') => {
+                        self.pos += 1;
+                        self.skip_whitespace();
+                        // A trailing comma
+                        if self.peek() == Some(close) {
+                            self.pos += 1;
+                            containers.pop();
+                            continue;
+                        }
+                        if close == b'}' {
+                            self.scan_key()?;
+                        }
+                        break;
+                    }
+                    Some(b) if b == close => {
+                        self.pos += 1;
+                        containers.pop();
+                    }
+                    _ => return Err(self.unexpected()),
+                }
+            }
+            if containers.is_empty() {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Scans an object key and the colon after it.
+    fn scan_key(&mut self) -> Result<()> {
+        match self.peek() {
+            Some(b'"' | b'\'') => self.scan_string()?,
+            _ => self.scan_identifier()?,
+        }
+        self.skip_whitespace();
+        if self.peek() != Some(b':') {
+            return Err(self.unexpected());
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn scan_literal(&mut self, literal: &str) -> Result<()> {
+        if !self.input[self.pos..].starts_with(literal) {
+            return Err(self.unexpected());
+        }
+        self.pos += literal.len();
+        Ok(())
+    }
+
+    /// Scans a number, which must also convert the way `deserialize_any` converts it.
+    fn scan_number(&mut self) -> Result<()> {
+        let start = self.pos;
+        let mut digits_start = start;
+        if let Some(b'+' | b'-') = self.peek() {
+            digits_start += 1;
+        }
+        let end = self
+            .hex_end(digits_start)
+            .or_else(|| self.decimal_end(digits_start))
+            .or_else(|| {
+                ["Infinity", "NaN"]
+                    .into_iter()
+                    .find(|literal| self.input[digits_start..].starts_with(literal))
+                    .map(|literal| digits_start + literal.len())
+            });
+        let Some(end) = end else {
+            return Err(self.unexpected());
+        };
+        self.pos = end;
+
+        let mut res = classify_number(&self.input[start..end]).map(|_| ());
+        if let Some(span) = pest::Span::new(self.input, start, end) {
+            error::set_location(&mut res, &span);
+        }
+        res
+    }
+
+    fn digits_end(&self, pos: usize) -> usize {
+        pos + self.bytes[pos..]
+            .iter()
+            .take_while(|b| b.is_ascii_digit())
+            .count()
+    }
+
+    /// `hex_integer_literal` of the grammar, which has a sign of its own.
+    fn hex_end(&self, mut pos: usize) -> Option<usize> {
+        if let Some(b'+' | b'-') = self.bytes.get(pos) {
+            pos += 1;
+        }
+        if !matches!(self.bytes.get(pos..pos + 2), Some([b'0', b'x' | b'X'])) {
+            return None;
+        }
+        pos += 2;
+        let digits = self.bytes[pos..]
+            .iter()
+            .take_while(|b| b.is_ascii_hexdigit())
+            .count();
+        (digits > 0).then_some(pos + digits)
+    }
+
+    /// `decimal_literal` of the grammar: an integer part, a fraction or both, then an exponent.
+    fn decimal_end(&self, pos: usize) -> Option<usize> {
+        let integer_end = match self.bytes.get(pos) {
+            Some(b'0') => Some(pos + 1),
+            Some(b'1'..=b'9') => Some(self.digits_end(pos)),
+            _ => None,
+        };
+        let end = match integer_end {
+            Some(end) if self.bytes.get(end) == Some(&b'.') => self.digits_end(end + 1),
+            Some(end) => end,
+            None if self.bytes.get(pos) == Some(&b'.') => {
+                let fraction_end = self.digits_end(pos + 1);
+                if fraction_end == pos + 1 {
+                    return None;
+                }
+                fraction_end
+            }
+            None => return None,
+        };
+        Some(self.exponent_end(end))
+    }
+
+    fn exponent_end(&self, pos: usize) -> usize {
+        if !matches!(self.bytes.get(pos), Some(b'e' | b'E')) {
+            return pos;
+        }
+        let mut digits_start = pos + 1;
+        if let Some(b'+' | b'-') = self.bytes.get(digits_start) {
+            digits_start += 1;
+        }
+        match self.digits_end(digits_start) {
+            end if end > digits_start => end,
+            _ => pos,
+        }
+    }
+
+    /// Scans a string quoted with `"`, `'` or `''`, whose escapes mustn't hold lone surrogates.
+    fn scan_string(&mut self) -> Result<()> {
+        let start = self.pos;
+        let end = if self.bytes[start..].starts_with(b"''") {
+            match self.string_end(start + 2, b'\'', true) {
+                Json5Token::Complete(end, lone_surrogate) => {
+                    Json5Token::Complete(end, lone_surrogate)
+                }
+                // When the `''` string doesn't end, the grammar takes `''` as an empty string.
+                Json5Token::Failed(_) => Json5Token::Complete(start + 2, false),
+            }
+        } else {
+            self.string_end(start + 1, self.bytes[start], false)
+        };
+        self.finish_token(start, end)
+    }
+
+    /// Scans an unquoted object key.
+    fn scan_identifier(&mut self) -> Result<()> {
+        let start = self.pos;
+        let mut pos = start;
+        let mut surrogates = SurrogateCheck::default();
+        loop {
+            let rest = &self.input[pos..];
+            if let Some(code_unit) = rest.strip_prefix("\\u").and_then(hex_code_unit) {
+                surrogates.escape(code_unit);
+                pos += 6;
+                continue;
+            }
+            match rest.chars().next() {
+                Some(c) if is_identifier_char(c, pos == start) => {
+                    surrogates.char();
+                    pos += c.len_utf8();
+                }
+                _ => break,
+            }
+        }
+        let end = if pos == start {
+            Json5Token::Failed(start)
+        } else {
+            Json5Token::Complete(pos, surrogates.finish())
+        };
+        self.finish_token(start, end)
+    }
+
+    /// Moves past a string or identifier that starts at `start`, or fails where it stopped.
+    fn finish_token(&mut self, start: usize, end: Json5Token) -> Result<()> {
+        match end {
+            Json5Token::Complete(end, lone_surrogate) => {
+                self.pos = end;
+                let mut res = match lone_surrogate {
+                    true => Err(Error::invalid_escape("lone surrogate in unicode escape")),
+                    false => Ok(()),
+                };
+                if let Some(span) = pest::Span::new(self.input, start, end) {
+                    error::set_location(&mut res, &span);
+                }
+                res
+            }
+            Json5Token::Failed(pos) => {
+                self.pos = pos;
+                Err(self.unexpected())
+            }
+        }
+    }
+
+    /// Finds the end of a string whose characters start at `pos`. With `doubled`, the string
+    /// ends at two `quote`s, which can appear alone inside it.
+    fn string_end(&self, mut pos: usize, quote: u8, doubled: bool) -> Json5Token {
+        let mut surrogates = SurrogateCheck::default();
+        loop {
+            let plain = find_string_special(&self.bytes[pos..], quote);
+            if let Some(offset) = find_line_separator(&self.bytes[pos..pos + plain]) {
+                return Json5Token::Failed(pos + offset);
+            }
+            if plain > 0 {
+                surrogates.char();
+            }
+            pos += plain;
+
+            match self.bytes.get(pos) {
+                None | Some(b'\n' | b'\r') => return Json5Token::Failed(pos),
+                Some(&b) if b == quote => {
+                    if !doubled {
+                        return Json5Token::Complete(pos + 1, surrogates.finish());
+                    }
+                    if self.bytes.get(pos + 1) == Some(&quote) {
+                        return Json5Token::Complete(pos + 2, surrogates.finish());
+                    }
+                    surrogates.char();
+                    pos += 1;
+                }
+                Some(b'\\') => match self.escape_end(pos + 1, &mut surrogates) {
+                    Ok(end) => pos = end,
+                    Err(at) => return Json5Token::Failed(at),
+                },
+                // Other control characters can appear as they are.
+                Some(_) => {
+                    surrogates.char();
+                    pos += 1;
+                }
+            }
+        }
+    }
+
+    /// Finds the end of the escape sequence or line continuation after the backslash that ends
+    /// right before `pos`, or where it fails.
+    fn escape_end(
+        &self,
+        pos: usize,
+        surrogates: &mut SurrogateCheck,
+    ) -> std::result::Result<usize, usize> {
+        let rest = &self.input[pos..];
+        let Some(c) = rest.chars().next() else {
+            return Err(pos);
+        };
+        match c {
+            // A line continuation, which isn't part of the string.
+            '\r' if rest[1..].starts_with('\n') => return Ok(pos + 2),
+            c if is_line_terminator(c) => return Ok(pos + c.len_utf8()),
+            'x' => {
+                let hex = rest.as_bytes().get(1..3).unwrap_or_default();
+                if hex.len() < 2 || !hex.iter().all(u8::is_ascii_hexdigit) {
+                    return Err(pos + 1);
+                }
+                surrogates.char();
+                return Ok(pos + 3);
+            }
+            'u' => {
+                let code_unit = hex_code_unit(&rest[1..]).ok_or(pos + 1)?;
+                surrogates.escape(code_unit);
+                return Ok(pos + 5);
+            }
+            '1'..='9' => return Err(pos),
+            _ => surrogates.char(),
+        }
+        Ok(pos + c.len_utf8())
+    }
+}
+
+/// Tracks whether the characters and unicode escapes of a string pair their surrogates.
+#[derive(Default)]
+struct SurrogateCheck {
+    /// A high surrogate was just escaped, and the low one must follow.
+    high: bool,
+    lone: bool,
+}
+
+impl SurrogateCheck {
+    fn char(&mut self) {
+        self.lone |= std::mem::take(&mut self.high);
+    }
+
+    fn escape(&mut self, code_unit: u32) {
+        match code_unit {
+            0xD800..=0xDBFF => {
+                self.char();
+                self.high = true;
+            }
+            0xDC00..=0xDFFF if self.high => self.high = false,
+            0xDC00..=0xDFFF => self.lone = true,
+            _ => self.char(),
+        }
+    }
+
+    /// Whether a surrogate was left unpaired.
+    fn finish(mut self) -> bool {
+        self.char();
+        self.lone
+    }
+}
+
+/// The code unit of the four hex digits at the start of `s`.
+fn hex_code_unit(s: &str) -> Option<u32> {
+    let hex = s.get(..4)?;
+    if !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    u32::from_str_radix(hex, 16).ok()
+}
+
+/// Returns the offset of the first U+2028 or U+2029 in `bytes`, which end a line in JSON5 and
+/// can't appear in strings as they are.
+fn find_line_separator(bytes: &[u8]) -> Option<usize> {
+    let mut offset = 0;
+    while let Some(lead) = bytes[offset..].iter().position(|&b| b == 0xE2) {
+        offset += lead;
+        if let [0x80, 0xA8 | 0xA9, ..] = bytes[offset + 1..] {
+            return Some(offset);
+        }
+        offset += 1;
+    }
+    None
+}
+
+fn is_line_terminator(c: char) -> bool {
+    matches!(c, '\n' | '\r' | '\u{2028}' | '\u{2029}')
+}
+
+/// `WHITESPACE` of the grammar.
+fn is_json5_whitespace(c: char) -> bool {
+    matches!(
+        c,
+        '\t' | '\u{000B}' | '\u{000C}' | ' ' | '\u{00A0}' | '\u{FEFF}'
+    ) || is_line_terminator(c)
+        || pest::unicode::SPACE_SEPARATOR(c)
+}
+
+/// `identifier_start` of the grammar, or `identifier_part` unless `first`, but for escapes.
+fn is_identifier_char(c: char, first: bool) -> bool {
+    use pest::unicode::*;
+
+    let is_start = c == '$'
+        || c == '_'
+        || UPPERCASE_LETTER(c)
+        || LOWERCASE_LETTER(c)
+        || TITLECASE_LETTER(c)
+        || MODIFIER_LETTER(c)
+        || OTHER_LETTER(c)
+        || LETTER_NUMBER(c);
+    is_start
+        || !first
+            && (NONSPACING_MARK(c)
+                || DIACRITIC(c)
+                || DECIMAL_NUMBER(c)
+                || CONNECTOR_PUNCTUATION(c)
+                || c == '\u{200C}'
+                || c == '\u{200D}')
+}
+
 struct Seq<'de> {
     pairs: VecDeque<Pair<'de, Rule>>,
 }
@@ -0,0 +1,591 @@
+use std::borrow::Cow;
+use std::rc::Rc;
+
+use crate::json::json_path::{json_path, PathElement};
+use crate::json::{
+    convert_json_to_db_type, get_json_value, json_extract_single, malformed_json, ArrayCursor, Val,
+};
+use crate::types::OwnedValue;
+
+/// A row of the json_each() and json_tree() table-valued functions.
+/// https://sqlite.org/json1.html#jeach
+#[derive(Debug, Clone, PartialEq)]
+pub struct JsonEachRow {
+    /// Array index or object label of the element, NULL for the top-level element.
+    pub key: OwnedValue,
+    /// The element itself. Arrays and objects are JSON text, scalars are SQL values.
+    pub value: OwnedValue,
+    /// The JSON type of the element, as returned by json_type().
+    pub json_type: &'static str,
+    /// The SQL value of a scalar element, NULL for arrays and objects.
+    pub atom: OwnedValue,
+    /// Identifies the element within the rows of a single call.
+    /// Unlike SQLite, this is not an offset into the JSONB encoding of the input.
+    pub id: i64,
+    /// Identifier of the containing element. Always NULL for json_each() and for the top-level
+    /// element of json_tree().
+    pub parent: Option<i64>,
+    /// Path to the element.
+    pub fullkey: String,
+    /// Path to the container of the element.
+    pub path: String,
+}
+
+impl JsonEachRow {
+    /// Returns the columns in the order they're declared by SQLite:
+    /// key, value, type, atom, id, parent, fullkey, path.
+    pub fn columns(&self) -> [OwnedValue; 8] {
+        [
+            self.key.clone(),
+            self.value.clone(),
+            OwnedValue::build_text(Rc::new(self.json_type.to_string())),
+            self.atom.clone(),
+            OwnedValue::Integer(self.id),
+            self.parent.map_or(OwnedValue::Null, OwnedValue::Integer),
+            OwnedValue::build_text(Rc::new(self.fullkey.clone())),
+            OwnedValue::build_text(Rc::new(self.path.clone())),
+        ]
+    }
+}
+
+/// Iterates over the immediate children of a JSON value, or over the value itself if it's
+/// neither an array nor an object.
+#[derive(Debug)]
+pub struct JsonEach<'a> {
+    root: Option<Val<'a>>,
+    /// Parses the elements of an array at the top of JSON text, in place of `root`.
+    cursor: Option<ArrayCursor<'a>>,
+    root_path: String,
+    next: usize,
+}
+
+/// Implements json_each(json[, path]), yielding one row per immediate child of the element at
+/// `path`. Yields no rows if the input is NULL or the path doesn't resolve.
+///
+/// Without a path, the elements of an array at the top of JSON text are parsed as they're
+/// iterated over, so memory is bounded by the largest element rather than the whole array. A
+/// malformed element is then reported when it's reached, after the rows before it.
+///
+/// The planner doesn't support table-valued functions yet, so this is only available as an
+/// iterator, not from SQL.
+pub fn json_each<'a>(
+    json: &'a OwnedValue,
+    path: Option<&OwnedValue>,
+) -> crate::Result<JsonEach<'a>> {
+    if let (OwnedValue::Text(t), None) = (json, path) {
+        if t.value
+            .trim_start_matches([' ', '\t', '\n', '\r'])
+            .starts_with('[')
+        {
+            return Ok(JsonEach {
+                root: None,
+                cursor: Some(ArrayCursor::new(&t.value)),
+                root_path: "$".to_string(),
+                next: 0,
+            });
+        }
+    }
+
+    let (root, root_path) = match (json, path) {
+        (OwnedValue::Null, _) | (_, Some(OwnedValue::Null)) => (None, "$".to_string()),
+        (json, None) => (Some(get_json_value(json)?.into_owned()), "$".to_string()),
+        (json, Some(path)) => {
+            let json = get_json_value(json)?;
+            let root = json_extract_single(&json, path, true)?
+                .map(|root| Cow::into_owned(root).into_owned());
+            (root, path.to_string())
+        }
+    };
+
+    Ok(JsonEach {
+        root,
+        cursor: None,
+        root_path,
+        next: 0,
+    })
+}
+
+impl Iterator for JsonEach<'_> {
+    type Item = crate::Result<JsonEachRow>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let idx = self.next;
+        if let Some(cursor) = &mut self.cursor {
+            let value = match cursor.next()? {
+                Ok(value) => value,
+                Err(err) => return Some(Err(malformed_json(err))),
+            };
+            self.next += 1;
+            return Some(Ok(build_row(
+                OwnedValue::Integer(idx as i64),
+                &value,
+                idx as i64 + 1,
+                None,
+                format!("{}[{}]", self.root_path, idx),
+                self.root_path.clone(),
+            )));
+        }
+
+        let (key, value, fullkey) = match self.root.as_ref()? {
+            Val::Array(array) => (
+                OwnedValue::Integer(idx as i64),
+                array.get(idx)?,
+                format!("{}[{}]", self.root_path, idx),
+            ),
+            Val::Object(map) => {
+                let (key, value) = map.get_index(idx)?;
+                (
+                    OwnedValue::build_text(Rc::new(key.clone())),
+                    value,
+                    append_object_key(&self.root_path, key),
+                )
+            }
+            scalar if idx == 0 => (OwnedValue::Null, scalar, self.root_path.clone()),
+            _ => return None,
+        };
+        self.next += 1;
+
+        let id = if let OwnedValue::Null = key {
+            0
+        } else {
+            idx as i64 + 1
+        };
+
+        Some(Ok(build_row(
+            key,
+            value,
+            id,
+            None,
+            fullkey,
+            self.root_path.clone(),
+        )))
+    }
+}
+
+/// Iterates over a JSON value and all of its descendants, depth-first. Rows are built as they're
+/// iterated over, from a stack of the elements not visited yet.
+#[derive(Debug)]
+pub struct JsonTree<'a> {
+    /// Popped in depth-first order: the children of a container are pushed in reverse.
+    stack: Vec<TreeNode<'a>>,
+    next_id: i64,
+}
+
+/// An element of json_tree() that hasn't been visited yet.
+#[derive(Debug)]
+struct TreeNode<'a> {
+    key: OwnedValue,
+    value: Val<'a>,
+    parent: Option<i64>,
+    fullkey: String,
+    path: String,
+}
+
+/// Implements json_tree(json[, path]), yielding the element at `path` followed by all of its
+/// descendants, depth-first. Yields no rows if the input is NULL or the path doesn't resolve.
+///
+/// Like json_each(), this is only available as an iterator, not from SQL.
+pub fn json_tree<'a>(
+    json: &'a OwnedValue,
+    path: Option<&OwnedValue>,
+) -> crate::Result<JsonTree<'a>> {
+    let root = match (json, path) {
+        (OwnedValue::Null, _) | (_, Some(OwnedValue::Null)) => None,
+        (json, None) => Some(TreeNode {
+            key: OwnedValue::Null,
+            value: get_json_value(json)?,
+            parent: None,
+            fullkey: "$".to_string(),
+            path: "$".to_string(),
+        }),
+        (json, Some(path)) => {
+            let json = get_json_value(json)?;
+            json_extract_single(&json, path, true)?.map(|root| {
+                let fullkey = path.to_string();
+                let (parent_path, key) = root_key(&json, &fullkey);
+                TreeNode {
+                    key,
+                    value: Cow::into_owned(root).into_owned(),
+                    parent: None,
+                    fullkey,
+                    path: parent_path,
+                }
+            })
+        }
+    };
+
+    Ok(JsonTree {
+        stack: root.into_iter().collect(),
+        next_id: 0,
+    })
+}
+
+impl Iterator for JsonTree<'_> {
+    type Item = JsonEachRow;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let TreeNode {
+            key,
+            value,
+            parent,
+            fullkey,
+            path,
+        } = self.stack.pop()?;
+        let id = self.next_id;
+        self.next_id += 1;
+
+        let row = build_row(key, &value, id, parent, fullkey, path);
+
+        match value {
+            Val::Array(array) => {
+                for (idx, child) in array.into_iter().enumerate().rev() {
+                    self.stack.push(TreeNode {
+                        key: OwnedValue::Integer(idx as i64),
+                        value: child,
+                        parent: Some(id),
+                        fullkey: format!("{}[{}]", row.fullkey, idx),
+                        path: row.fullkey.clone(),
+                    });
+                }
+            }
+            Val::Object(map) => {
+                for (label, child) in map.into_iter().rev() {
+                    self.stack.push(TreeNode {
+                        fullkey: append_object_key(&row.fullkey, &label),
+                        key: OwnedValue::build_text(Rc::new(label)),
+                        value: child,
+                        parent: Some(id),
+                        path: row.fullkey.clone(),
+                    });
+                }
+            }
+            _ => {}
+        }
+
+        Some(row)
+    }
+}
+
+/// Builds the row describing `value`.
+fn build_row(
+    key: OwnedValue,
+    value: &Val,
+    id: i64,
+    parent: Option<i64>,
+    fullkey: String,
+    path: String,
+) -> JsonEachRow {
+    JsonEachRow {
+        key,
+        value: convert_json_to_db_type(value, false).unwrap_or(OwnedValue::Null),
+        json_type: value.type_name(),
+        atom: atom(value),
+        id,
+        parent,
+        fullkey,
+        path,
+    }
+}
+
+/// Splits the path of the top-level json_tree() element into the path of its container and its
+/// key, eg. `$.a[1]` into `$.a` and 1. The key of the root of the document is NULL.
+fn root_key(json: &Val, path: &str) -> (String, OwnedValue) {
+    let Ok(parsed) = json_path(path) else {
+        return (path.to_string(), OwnedValue::Null);
+    };
+    let parent_path = path[..last_segment_start(path)].to_string();
+
+    let key = match parsed.elements.last() {
+        Some(PathElement::Key(key)) => OwnedValue::build_text(Rc::new(key.clone())),
+        Some(PathElement::ArrayLocator(idx)) => OwnedValue::Integer(*idx),
+        Some(PathElement::ArrayLocatorFromEnd(offset)) => {
+            let parent = OwnedValue::build_text(Rc::new(parent_path.clone()));
+            match json_extract_single(json, &parent, true) {
+                Ok(Some(parent)) => match parent.as_ref() {
+                    Val::Array(array) => OwnedValue::Integer(array.len() as i64 - *offset),
+                    _ => OwnedValue::Null,
+                },
+                _ => OwnedValue::Null,
+            }
+        }
+        Some(PathElement::Root() | PathElement::Wildcard | PathElement::Slice { .. }) | None => {
+            return (path.to_string(), OwnedValue::Null)
+        }
+    };
+
+    (parent_path, key)
+}
+
+/// Returns the byte offset where the last `.key` or `[index]` segment of a path starts.
+fn last_segment_start(path: &str) -> usize {
+    let mut start = path.len();
+    let mut in_quoted_key = false;
+    let mut escaped = false;
+
+    for (offset, c) in path.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_quoted_key => escaped = true,
+            '"' => in_quoted_key = !in_quoted_key,
+            '.' | '[' if !in_quoted_key => start = offset,
+            _ => {}
+        }
+    }
+
+    start
+}
+
+/// Returns the SQL value of a scalar, or NULL for arrays and objects.
+fn atom(value: &Val) -> OwnedValue {
+    match value {
+        Val::Array(_) | Val::Object(_) => OwnedValue::Null,
+        scalar => convert_json_to_db_type(scalar, true).unwrap_or(OwnedValue::Null),
+    }
+}
+
+/// Appends an object label to a path, quoting it unless it's a plain identifier,
+/// the same way SQLite does, eg. `$.a`, `$."b c"`.
+fn append_object_key(path: &str, key: &str) -> String {
+    let mut chars = key.chars();
+    let is_identifier = chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+        && chars.all(|c| c.is_ascii_alphanumeric());
+
+    if is_identifier {
+        format!("{}.{}", path, key)
+    } else {
+        let escaped = key.replace('\\', "\\\\").replace('"', "\\\"");
+        format!("{}.\"{}\"", path, escaped)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::TextSubtype;
+
+    fn text(value: &str) -> OwnedValue {
+        OwnedValue::build_text(Rc::new(value.to_string()))
+    }
+
+    fn each_rows(json: &OwnedValue, path: Option<&OwnedValue>) -> Vec<JsonEachRow> {
+        json_each(json, path)
+            .unwrap()
+            .collect::<crate::Result<_>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_json_each_array() {
+        let rows = each_rows(&text(r#"[1,"x",[2]]"#), None);
+
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[0].key, OwnedValue::Integer(0));
+        assert_eq!(rows[0].value, OwnedValue::Integer(1));
+        assert_eq!(rows[0].json_type, "integer");
+        assert_eq!(rows[0].atom, OwnedValue::Integer(1));
+        assert_eq!(rows[0].fullkey, "$[0]");
+        assert_eq!(rows[0].path, "$");
+
+        assert_eq!(rows[1].value, text("x"));
+        assert_eq!(rows[1].json_type, "text");
+
+        let OwnedValue::Text(nested) = &rows[2].value else {
+            panic!("Expected OwnedValue::Text");
+        };
+        assert_eq!(nested.value.as_str(), "[2]");
+        assert_eq!(nested.subtype, TextSubtype::Json);
+        assert_eq!(rows[2].json_type, "array");
+        assert_eq!(rows[2].atom, OwnedValue::Null);
+        assert_eq!(rows[2].fullkey, "$[2]");
+    }
+
+    #[test]
+    fn test_json_each_object() {
+        let rows = each_rows(&text(r#"{"a":1,"b c":true}"#), None);
+
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].key, text("a"));
+        assert_eq!(rows[0].fullkey, "$.a");
+        assert_eq!(rows[1].key, text("b c"));
+        assert_eq!(rows[1].json_type, "true");
+        assert_eq!(rows[1].atom, OwnedValue::Integer(1));
+        assert_eq!(rows[1].fullkey, r#"$."b c""#);
+        assert!(rows.iter().all(|row| row.parent.is_none()));
+    }
+
+    #[test]
+    fn test_json_each_object_key_escapes() {
+        let rows = each_rows(&text(r#"{"a\\b":1,"c\"d":2,"e\\\"":3}"#), None);
+
+        assert_eq!(rows[0].key, text(r"a\b"));
+        assert_eq!(rows[0].fullkey, r#"$."a\\b""#);
+        assert_eq!(rows[1].fullkey, r#"$."c\"d""#);
+        assert_eq!(rows[2].fullkey, r#"$."e\\\"""#);
+    }
+
+    #[test]
+    fn test_json_each_with_path() {
+        let rows = each_rows(&text(r#"{"a":{"b":[1,2]}}"#), Some(&text("$.a.b")));
+
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].fullkey, "$.a.b[0]");
+        assert_eq!(rows[1].fullkey, "$.a.b[1]");
+        assert_eq!(rows[1].path, "$.a.b");
+    }
+
+    #[test]
+    fn test_json_each_scalar() {
+        let rows = each_rows(&text("1"), None);
+
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].key, OwnedValue::Null);
+        assert_eq!(rows[0].fullkey, "$");
+        assert_eq!(rows[0].path, "$");
+        assert_eq!(
+            rows[0].columns(),
+            [
+                OwnedValue::Null,
+                OwnedValue::Integer(1),
+                text("integer"),
+                OwnedValue::Integer(1),
+                OwnedValue::Integer(0),
+                OwnedValue::Null,
+                text("$"),
+                text("$"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_json_each_no_rows() {
+        assert_eq!(json_each(&OwnedValue::Null, None).unwrap().count(), 0);
+        assert_eq!(
+            json_each(&text("[1]"), Some(&text("$.a"))).unwrap().count(),
+            0
+        );
+        assert_eq!(json_each(&text("[]"), None).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_json_each_malformed_json() {
+        assert!(json_each(&text(r#"{"a":"#), None).is_err());
+        assert!(json_each(&text("[1"), Some(&text("$"))).is_err());
+
+        // The elements of a top-level array are parsed as they're reached.
+        let json = text(r#"[1, {"a": [2]}, [3 4], 5]"#);
+        let mut rows = json_each(&json, None).unwrap();
+        let row = rows.next().unwrap().unwrap();
+        assert_eq!(row.value, OwnedValue::Integer(1));
+        let row = rows.next().unwrap().unwrap();
+        assert_eq!(row.fullkey, "$[1]");
+        assert_eq!(row.json_type, "object");
+        match rows.next() {
+            Some(Err(err)) => assert!(err.to_string().contains("malformed JSON"), "{}", err),
+            other => panic!("Expected an error, got {:?}", other),
+        }
+        assert!(rows.next().is_none());
+
+        let rows: crate::Result<Vec<_>> = json_each(&text("[1"), None).unwrap().collect();
+        assert!(rows.is_err());
+    }
+
+    #[test]
+    fn test_json_each_top_level_array() {
+        // Arrays parsed an element at a time give the same rows as when parsed whole.
+        for json in [
+            r#"[1,"x",[2,{"a":null}],{"b":[]},-1.5e3,true]"#,
+            "  [ ]",
+            // JSON5, parsed whole once the scan reaches it.
+            "[1, 0x10, 'a', [2,],]",
+            "[1, /* two */ 2]",
+        ] {
+            let streamed = each_rows(&text(json), None);
+            let whole = each_rows(&text(json), Some(&text("$")));
+            assert_eq!(streamed, whole, "{}", json);
+        }
+        assert_eq!(each_rows(&text("[1, 0x10, 'a', [2,],]"), None).len(), 4);
+    }
+
+    #[test]
+    fn test_json_tree() {
+        let rows: Vec<_> = json_tree(&text(r#"{"a":[1,{"b":null}],"c":2}"#), None)
+            .unwrap()
+            .collect();
+
+        let summary: Vec<_> = rows
+            .iter()
+            .map(|row| (row.id, row.parent, row.fullkey.as_str(), row.path.as_str()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (0, None, "$", "$"),
+                (1, Some(0), "$.a", "$"),
+                (2, Some(1), "$.a[0]", "$.a"),
+                (3, Some(1), "$.a[1]", "$.a"),
+                (4, Some(3), "$.a[1].b", "$.a[1]"),
+                (5, Some(0), "$.c", "$"),
+            ]
+        );
+
+        assert_eq!(rows[0].key, OwnedValue::Null);
+        assert_eq!(rows[0].json_type, "object");
+        assert_eq!(rows[0].atom, OwnedValue::Null);
+        assert_eq!(rows[1].key, text("a"));
+        assert_eq!(rows[1].atom, OwnedValue::Null);
+        assert_eq!(rows[2].key, OwnedValue::Integer(0));
+        assert_eq!(rows[2].atom, OwnedValue::Integer(1));
+        assert_eq!(rows[4].json_type, "null");
+        assert_eq!(rows[5].atom, OwnedValue::Integer(2));
+    }
+
+    #[test]
+    fn test_json_tree_with_path() {
+        let rows: Vec<_> = json_tree(&text(r#"{"a":{"b c":1}}"#), Some(&text("$.a")))
+            .unwrap()
+            .collect();
+
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].key, text("a"));
+        assert_eq!(rows[0].parent, None);
+        assert_eq!(rows[0].fullkey, "$.a");
+        assert_eq!(rows[0].path, "$");
+        assert_eq!(rows[1].key, text("b c"));
+        assert_eq!(rows[1].parent, Some(0));
+        assert_eq!(rows[1].fullkey, r#"$.a."b c""#);
+        assert_eq!(rows[1].path, "$.a");
+    }
+
+    #[test]
+    fn test_json_tree_with_array_path() {
+        let rows: Vec<_> = json_tree(&text("[1,[2]]"), Some(&text("$[#-1]")))
+            .unwrap()
+            .collect();
+
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].key, OwnedValue::Integer(1));
+        assert_eq!(rows[0].path, "$");
+        assert_eq!(rows[1].fullkey, "$[#-1][0]");
+    }
+
+    #[test]
+    fn test_json_tree_scalar() {
+        let rows: Vec<_> = json_tree(&text("3"), None).unwrap().collect();
+
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].key, OwnedValue::Null);
+        assert_eq!(rows[0].atom, OwnedValue::Integer(3));
+        assert_eq!(rows[0].fullkey, "$");
+    }
+
+    #[test]
+    fn test_json_tree_no_rows() {
+        assert_eq!(json_tree(&OwnedValue::Null, None).unwrap().count(), 0);
+        assert_eq!(
+            json_tree(&text("[1]"), Some(&text("$[1]")))
+                .unwrap()
+                .count(),
+            0
+        );
+    }
+}
@@ -7,47 +7,208 @@ use crate::json::de::Rule;
 /// Alias for a `Result` with error type `json5::Error`
 pub type Result<T> = std::result::Result<T, Error>;
 
-/// One-based line and column at which the error was detected.
+/// One-based line, column and offset at which the error was detected.
 #[derive(Clone, Debug, PartialEq)]
 pub struct Location {
     /// The one-based line number of the error.
     pub line: usize,
     /// The one-based column number of the error.
     pub column: usize,
+    /// The one-based character (not byte) offset of the error from the start of the input.
+    pub offset: usize,
+}
+
+impl Location {
+    fn new(input: &str, byte_offset: usize, (line, column): (usize, usize)) -> Self {
+        Self {
+            line,
+            column,
+            offset: input[..byte_offset].chars().count() + 1,
+        }
+    }
 }
 
 impl From<&Span<'_>> for Location {
     fn from(s: &Span<'_>) -> Self {
-        let (line, column) = s.start_pos().line_col();
-        Self { line, column }
+        let start = s.start_pos();
+        Self::new(s.get_input(), start.pos(), start.line_col())
     }
 }
 
-/// A bare bones error type which currently just collapses all the underlying errors in to a single
-/// string... This is fine for displaying to the user, but not very useful otherwise. Work to be
-/// done here.
+/// An error parsing or serializing JSON. Parse errors carry where in the input they happened,
+/// which is part of the alternate form of the message (`{:#}`).
 #[derive(Clone, Debug, PartialEq)]
 pub enum Error {
-    /// Just shove everything in a single variant for now.
+    /// Input that can't appear at this point, eg. the `x` of `[1,x]`.
+    UnexpectedToken {
+        /// The unexpected input.
+        token: String,
+        location: Location,
+    },
+    /// The input ended before the value was complete, eg. `[1,`.
+    UnexpectedEof { location: Location },
+    /// An escape sequence that doesn't decode, eg. `\1` or a lone surrogate like `\uD800`.
+    InvalidEscape {
+        msg: String,
+        location: Option<Location>,
+        /// The string the escape is in.
+        token: Option<String>,
+    },
+    /// A number that can't be represented, eg. `1e999`.
+    InvalidNumber {
+        msg: String,
+        location: Option<Location>,
+        /// The number as written in the input.
+        token: Option<String>,
+    },
+    /// Arrays and objects nested deeper than allowed.
+    DepthExceeded { location: Option<Location> },
+    /// Input longer than allowed, rejected before parsing.
+    TooLong {
+        /// Length of the input, in bytes.
+        length: usize,
+        max_length: usize,
+    },
+    /// Any other error, eg. one reported through serde.
     Message {
-        /// The error message.
         msg: String,
-        /// The location of the error, if applicable.
         location: Option<Location>,
+        /// The input at `location`, if any.
+        token: Option<String>,
     },
 }
 
-impl From<pest::error::Error<Rule>> for Error {
-    fn from(err: pest::error::Error<Rule>) -> Self {
-        let (line, column) = match err.line_col {
+impl Error {
+    /// Converts an error pest reported while parsing `input`.
+    pub fn from_pest(err: pest::error::Error<Rule>, input: &str) -> Self {
+        let line_col = match err.line_col {
             pest::error::LineColLocation::Pos((l, c)) => (l, c),
             pest::error::LineColLocation::Span((l, c), (_, _)) => (l, c),
         };
-        Self::Message {
-            msg: err.to_string(),
-            location: Some(Location { line, column }),
+        let byte_offset = match err.location {
+            pest::error::InputLocation::Pos(pos) => pos,
+            pest::error::InputLocation::Span((start, _)) => start,
+        };
+        Self::syntax(input, byte_offset, line_col)
+    }
+
+    /// An error for input that can't go on at `byte_offset` of `input`: an invalid escape
+    /// sequence when it's in one, the end of the input when it's there, or else the token there.
+    pub fn syntax_at(input: &str, byte_offset: usize) -> Self {
+        let line_col = pest::Position::new(input, byte_offset).map_or((1, 1), |pos| pos.line_col());
+        Self::syntax(input, byte_offset, line_col)
+    }
+
+    fn syntax(input: &str, byte_offset: usize, line_col: (usize, usize)) -> Self {
+        let location = Location::new(input, byte_offset, line_col);
+        if let Some(start) = escape_start(input, byte_offset) {
+            // As much of the input as the escape would span, eg. `\1` or `\u12` of `"\u12"`
+            let len = match input.as_bytes().get(start + 1) {
+                Some(b'u') => 6,
+                Some(b'x') => 4,
+                _ => 2,
+            };
+            let token = input[start..]
+                .chars()
+                .take(len)
+                .take_while(|&c| c != '"' && c != '\'')
+                .collect();
+            return Self::InvalidEscape {
+                msg: "invalid escape sequence".to_string(),
+                location: Some(location),
+                token: Some(token),
+            };
+        }
+        match unexpected_token(&input[byte_offset..]) {
+            None => Self::UnexpectedEof { location },
+            Some(token) => Self::UnexpectedToken { token, location },
         }
     }
+
+    /// An error for nesting deeper than allowed, detected at `byte_offset` of `input`.
+    pub fn depth_exceeded_at(input: &str, byte_offset: usize) -> Self {
+        let line_col = pest::Position::new(input, byte_offset).map_or((1, 1), |pos| pos.line_col());
+        Self::DepthExceeded {
+            location: Some(Location::new(input, byte_offset, line_col)),
+        }
+    }
+
+    /// An invalid escape sequence. `set_location` adds where it is.
+    pub fn invalid_escape(msg: &str) -> Self {
+        Self::InvalidEscape {
+            msg: msg.to_string(),
+            location: None,
+            token: None,
+        }
+    }
+
+    /// A number that can't be represented. `set_location` adds where it is.
+    pub fn invalid_number(msg: &str) -> Self {
+        Self::InvalidNumber {
+            msg: msg.to_string(),
+            location: None,
+            token: None,
+        }
+    }
+
+    /// Where in the input the error happened, if known.
+    pub fn location(&self) -> Option<&Location> {
+        match self {
+            Self::UnexpectedToken { location, .. } | Self::UnexpectedEof { location } => {
+                Some(location)
+            }
+            Self::InvalidEscape { location, .. }
+            | Self::InvalidNumber { location, .. }
+            | Self::DepthExceeded { location }
+            | Self::Message { location, .. } => location.as_ref(),
+            Self::TooLong { .. } => None,
+        }
+    }
+
+    /// Whether the input or value was nested deeper than allowed.
+    pub fn is_excessive_nesting(&self) -> bool {
+        matches!(self, Self::DepthExceeded { .. })
+    }
+
+    /// Whether the input was rejected for going over a limit rather than for being malformed.
+    pub fn is_limit_exceeded(&self) -> bool {
+        matches!(self, Self::DepthExceeded { .. } | Self::TooLong { .. })
+    }
+}
+
+/// Message of the error for arrays and objects nested deeper than allowed.
+pub const EXCESSIVE_NESTING: &str = "excessive nesting";
+
+/// Returns where the escape sequence that `byte_offset` of `input` is in starts, if it is in one:
+/// right after a backslash that isn't escaped itself, or within the digits of a `\x` or `\u`
+/// escape.
+fn escape_start(input: &str, byte_offset: usize) -> Option<usize> {
+    let before = &input[..byte_offset];
+    let start = before.rfind('\\')?;
+    let is_escape_prefix = match &before.as_bytes()[start + 1..] {
+        [] => true,
+        [b'x', hex @ ..] => hex.len() < 2 && hex.iter().all(u8::is_ascii_hexdigit),
+        [b'u', hex @ ..] => hex.len() < 4 && hex.iter().all(u8::is_ascii_hexdigit),
+        _ => false,
+    };
+    let preceding_backslashes = before[..start]
+        .bytes()
+        .rev()
+        .take_while(|&b| b == b'\\')
+        .count();
+    (is_escape_prefix && preceding_backslashes % 2 == 0).then_some(start)
+}
+
+/// Returns the token at the start of `rest`: a run of characters up to the next whitespace or
+/// structural character, or just the first character if it is one of those.
+fn unexpected_token(rest: &str) -> Option<String> {
+    let is_delimiter = |c: char| c.is_whitespace() || "[]{}:,".contains(c);
+    let token: String = rest.chars().take_while(|c| !is_delimiter(*c)).collect();
+    if token.is_empty() {
+        rest.chars().next().map(String::from)
+    } else {
+        Some(token)
+    }
 }
 
 impl From<std::io::Error> for Error {
@@ -55,6 +216,7 @@ impl From<std::io::Error> for Error {
         Self::Message {
             msg: err.to_string(),
             location: None,
+            token: None,
         }
     }
 }
@@ -64,6 +226,7 @@ impl From<std::str::Utf8Error> for Error {
         Self::Message {
             msg: err.to_string(),
             location: None,
+            token: None,
         }
     }
 }
@@ -73,6 +236,7 @@ impl ser::Error for Error {
         Self::Message {
             msg: msg.to_string(),
             location: None,
+            token: None,
         }
     }
 }
@@ -82,27 +246,87 @@ impl de::Error for Error {
         Self::Message {
             msg: msg.to_string(),
             location: None,
+            token: None,
         }
     }
 }
 
+/// Only the message is displayed by default; the alternate form (`{:#}`) also includes where the
+/// error happened and the input there, eg. `... at line 1, column 4 near "x"`.
 impl Display for Error {
     fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            Self::Message { ref msg, .. } => write!(formatter, "{}", msg),
+        let token = match self {
+            Self::UnexpectedToken { token, .. } => {
+                write!(formatter, "unexpected {:?}", token)?;
+                // Already part of the message
+                None
+            }
+            Self::UnexpectedEof { .. } => {
+                write!(formatter, "unexpected end of input")?;
+                None
+            }
+            Self::DepthExceeded { .. } => {
+                write!(formatter, "{}", EXCESSIVE_NESTING)?;
+                None
+            }
+            Self::TooLong { length, max_length } => {
+                write!(
+                    formatter,
+                    "input too long: {} bytes, the limit is {}",
+                    length, max_length
+                )?;
+                None
+            }
+            Self::InvalidEscape { msg, token, .. }
+            | Self::InvalidNumber { msg, token, .. }
+            | Self::Message { msg, token, .. } => {
+                write!(formatter, "{}", msg)?;
+                token.as_ref()
+            }
+        };
+        if formatter.alternate() {
+            if let Some(location) = self.location() {
+                write!(
+                    formatter,
+                    " at line {}, column {}",
+                    location.line, location.column
+                )?;
+            }
+            if let Some(token) = token {
+                write!(formatter, " near {:?}", token)?;
+            }
         }
+        Ok(())
     }
 }
 
 impl std::error::Error for Error {}
 
-/// Adds location information from `span`, if `res` is an error.
+/// The crate error keeps the location of the error, along with the message.
+impl From<Error> for crate::LimboError {
+    fn from(err: Error) -> Self {
+        Self::ParseError(format!("{:#}", err))
+    }
+}
+
+/// Adds location information from `span`, if `res` is an error that doesn't have it yet.
 pub fn set_location<T>(res: &mut Result<T>, span: &Span<'_>) {
-    if let Err(ref mut e) = res {
-        let Error::Message { location, .. } = e;
-        if location.is_none() {
-            let (line, column) = span.start_pos().line_col();
-            *location = Some(Location { line, column });
+    match res {
+        Err(Error::InvalidEscape {
+            location, token, ..
+        })
+        | Err(Error::InvalidNumber {
+            location, token, ..
+        })
+        | Err(Error::Message {
+            location, token, ..
+        }) if location.is_none() => {
+            *location = Some(Location::from(span));
+            *token = unexpected_token(span.as_str());
+        }
+        Err(Error::DepthExceeded { location }) if location.is_none() => {
+            *location = Some(Location::from(span));
         }
+        _ => {}
     }
 }
@@ -0,0 +1,164 @@
+use serde_json::{Map, Number, Value};
+
+use crate::json::error::EXCESSIVE_NESTING;
+use crate::json::{Val, JSON_MAX_DEPTH};
+use crate::LimboError;
+
+/// Converts a `Val` into a `serde_json::Value`, keeping the order of object members.
+/// Integers map to integer numbers and floats to float numbers. Raw numbers map to an integer
+/// number when their text is one, to a float number otherwise. serde_json can't hold NaN or
+/// infinities, so those become `null`, like `json()` writes NaN.
+impl From<Val<'_>> for Value {
+    fn from(val: Val<'_>) -> Self {
+        match val {
+            Val::Null => Value::Null,
+            Val::Bool(b) => Value::Bool(b),
+            Val::Integer(i) => Value::Number(i.into()),
+            Val::Float(f) => float_to_value(f),
+            Val::RawNumber(s) => {
+                if let Ok(i) = s.parse::<i64>() {
+                    Value::Number(i.into())
+                } else if let Ok(u) = s.parse::<u64>() {
+                    Value::Number(u.into())
+                } else {
+                    s.parse::<f64>().map_or(Value::Null, float_to_value)
+                }
+            }
+            Val::RawJson(s) => crate::json::from_str::<Val>(&s).map_or(Value::Null, Value::from),
+            Val::String(s) => Value::String(s.into_owned()),
+            Val::Array(items) => Value::Array(items.into_iter().map(Value::from).collect()),
+            Val::Object(members) => Value::Object(
+                members
+                    .into_iter()
+                    .map(|(key, value)| (key, Value::from(value)))
+                    .collect::<Map<_, _>>(),
+            ),
+        }
+    }
+}
+
+fn float_to_value(f: f64) -> Value {
+    Number::from_f64(f).map_or(Value::Null, Value::Number)
+}
+
+/// Converts a `serde_json::Value` into a `Val`, keeping the order of object members. Numbers that
+/// fit an `i64` become integers, other integers keep their text as raw numbers, and the rest
+/// become floats. Fails for arrays and objects nested deeper than `json()` accepts.
+impl TryFrom<Value> for Val<'static> {
+    type Error = LimboError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        value_to_val(value, 0)
+    }
+}
+
+fn value_to_val(value: Value, depth: usize) -> crate::Result<Val<'static>> {
+    let val = match value {
+        Value::Null => Val::Null,
+        Value::Bool(b) => Val::Bool(b),
+        Value::Number(n) => {
+            if let Some(i) = n.as_i64() {
+                Val::Integer(i)
+            } else if n.is_u64() {
+                Val::RawNumber(n.to_string())
+            } else {
+                Val::Float(n.as_f64().unwrap_or(f64::NAN))
+            }
+        }
+        Value::String(s) => Val::String(s.into()),
+        Value::Array(_) | Value::Object(_) if depth == JSON_MAX_DEPTH => {
+            crate::bail_parse_error!("{}", EXCESSIVE_NESTING)
+        }
+        Value::Array(items) => Val::Array(
+            items
+                .into_iter()
+                .map(|item| value_to_val(item, depth + 1))
+                .collect::<crate::Result<_>>()?,
+        ),
+        Value::Object(members) => Val::Object(
+            members
+                .into_iter()
+                .map(|(key, value)| Ok((key, value_to_val(value, depth + 1)?)))
+                .collect::<crate::Result<_>>()?,
+        ),
+    };
+    Ok(val)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json::{from_str, to_string};
+    use serde_json::json;
+
+    #[test]
+    fn test_val_to_serde_json() {
+        let val: Val = from_str(r#"{"b":[1,2.5,"x",true,null],"a":{"c":-3}}"#).unwrap();
+        let value = Value::from(val);
+
+        assert_eq!(
+            value,
+            json!({"b": [1, 2.5, "x", true, null], "a": {"c": -3}})
+        );
+        let keys: Vec<_> = value.as_object().unwrap().keys().collect();
+        assert_eq!(keys, ["b", "a"]);
+    }
+
+    #[test]
+    fn test_val_to_serde_json_numbers() {
+        assert_eq!(Value::from(Val::Integer(i64::MIN)), json!(i64::MIN));
+        assert_eq!(Value::from(Val::Float(1.0)), json!(1.0));
+        assert!(Value::from(Val::Float(1.0)).is_f64());
+        assert_eq!(Value::from(Val::RawNumber("1.50".to_string())), json!(1.5));
+        assert_eq!(
+            Value::from(Val::RawNumber("18446744073709551615".to_string())),
+            json!(u64::MAX)
+        );
+        assert_eq!(Value::from(Val::Float(f64::NAN)), Value::Null);
+        assert_eq!(Value::from(Val::Float(f64::INFINITY)), Value::Null);
+    }
+
+    #[test]
+    fn test_serde_json_to_val() {
+        let value = json!({"z": [1, -2.5, u64::MAX, "s"], "a": {}});
+        let val = Val::try_from(value).unwrap();
+
+        assert_eq!(
+            to_string(&val).unwrap(),
+            r#"{"z":[1,-2.5,18446744073709551615,"s"],"a":{}}"#
+        );
+        let Val::Object(members) = &val else {
+            panic!("Expected Val::Object");
+        };
+        assert_eq!(members["z"].type_name(), "array");
+        let Val::Array(items) = &members["z"] else {
+            panic!("Expected Val::Array");
+        };
+        assert_eq!(items[0], Val::Integer(1));
+        assert_eq!(items[1], Val::Float(-2.5));
+    }
+
+    #[test]
+    fn test_serde_json_round_trip() {
+        let value = json!({"k": [null, false, {"n": 7}], "f": 0.1});
+        let val = Val::try_from(value.clone()).unwrap();
+        assert_eq!(Value::from(val), value);
+    }
+
+    #[test]
+    fn test_serde_json_to_val_excessive_nesting() {
+        // Unoptimized builds need more stack per level than the 2 MiB test threads get by default.
+        let handle = std::thread::Builder::new()
+            .stack_size(8 << 20)
+            .spawn(|| {
+                let mut value = json!(1);
+                for _ in 0..=JSON_MAX_DEPTH {
+                    value = Value::Array(vec![value]);
+                }
+                Val::try_from(value).map(|_| ())
+            })
+            .unwrap();
+        let err = handle.join().unwrap().unwrap_err();
+        assert!(err.to_string().contains(EXCESSIVE_NESTING));
+    }
+}
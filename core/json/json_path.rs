@@ -1,5 +1,6 @@
 use pest::Parser as P;
 use pest_derive::Parser;
+use std::fmt::{self, Display};
 
 #[derive(Parser)]
 #[grammar = "json/json.pest"]
@@ -12,6 +13,13 @@ pub struct JsonPath {
     pub elements: Vec<PathElement>,
 }
 
+impl JsonPath {
+    /// Whether the path is just '$', which addresses the whole document.
+    pub fn is_root(&self) -> bool {
+        matches!(self.elements.as_slice(), [PathElement::Root()])
+    }
+}
+
 /// PathElement describes a single element of a JSON path.
 #[derive(Clone, Debug, PartialEq)]
 pub enum PathElement {
@@ -19,12 +27,92 @@ pub enum PathElement {
     Root(),
     /// JSON key
     Key(String),
-    /// Array locator, eg. [2], [#-5]
-    ArrayLocator(i32),
+    /// Array locator, eg. [2]. Indices beyond the range of i64 are saturated to it, which is past
+    /// the end of any array anyway.
+    ArrayLocator(i64),
+    /// Array locator counting back from the array length, eg. [#] (one past the last element),
+    /// [#-1] (the last element). Offsets are saturated like indices.
+    ArrayLocatorFromEnd(i64),
+    /// All the immediate children of an object or array, eg. $.* or $[*]. Only json_query
+    /// accepts it: SQLite rejects such paths.
+    Wildcard,
+    /// The elements of an array from `start` up to but excluding `end`, as a new array, eg.
+    /// $[1:3], $[1:] or $[-2:]. Like in Python, missing bounds default to the ends of the array,
+    /// negative bounds count back from its end and bounds out of range are clamped. Only
+    /// json_query accepts it, like wildcards.
+    Slice {
+        start: Option<i64>,
+        end: Option<i64>,
+    },
+}
+
+/// Writes the path back in its canonical form, eg. `$.a[0]."b c"`.
+impl Display for JsonPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.elements.iter().try_for_each(|element| element.fmt(f))
+    }
+}
+
+/// Writes the element the way it appears in a path, eg. `.a`, `."a b"`, `[2]` or `[#-1]`.
+impl Display for PathElement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PathElement::Root() => write!(f, "$"),
+            PathElement::Key(key) if is_bare_key(key) => write!(f, ".{}", key),
+            PathElement::Key(key) => {
+                write!(f, ".\"")?;
+                for c in key.chars() {
+                    if matches!(c, '"' | '\\') {
+                        write!(f, "\\")?;
+                    }
+                    write!(f, "{}", c)?;
+                }
+                write!(f, "\"")
+            }
+            PathElement::ArrayLocator(index) => write!(f, "[{}]", index),
+            PathElement::ArrayLocatorFromEnd(0) => write!(f, "[#]"),
+            PathElement::ArrayLocatorFromEnd(offset) => write!(f, "[#-{}]", offset),
+            PathElement::Wildcard => write!(f, ".*"),
+            PathElement::Slice { start, end } => {
+                write!(f, "[")?;
+                if let Some(start) = start {
+                    write!(f, "{}", start)?;
+                }
+                write!(f, ":")?;
+                if let Some(end) = end {
+                    write!(f, "{}", end)?;
+                }
+                write!(f, "]")
+            }
+        }
+    }
+}
+
+/// Whether a key can be written without quotes: an identifier that isn't the wildcard.
+fn is_bare_key(key: &str) -> bool {
+    let mut chars = key.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
 }
 
 /// Parses path into a Vec of Strings, where each string is a key or an array locator.
+/// Like in SQLite, wildcards and slices are rejected.
 pub fn json_path(path: &str) -> crate::Result<JsonPath> {
+    let parsed = json_path_with_extensions(path)?;
+    if parsed
+        .elements
+        .iter()
+        .any(|e| matches!(e, PathElement::Wildcard | PathElement::Slice { .. }))
+    {
+        crate::bail_constraint_error!("JSON path error near: {:?}", path.to_string());
+    }
+    Ok(parsed)
+}
+
+/// Parses a path that may also contain wildcards and slices, for json_query.
+pub fn json_path_with_extensions(path: &str) -> crate::Result<JsonPath> {
     let parsed = Parser::parse(Rule::path, path);
 
     if let Ok(mut parsed) = parsed {
@@ -34,7 +122,26 @@ pub fn json_path(path: &str) -> crate::Result<JsonPath> {
             match pair.as_rule() {
                 Rule::EOI => (),
                 Rule::root => result.push(PathElement::Root()),
-                Rule::json_path_key => result.push(PathElement::Key(pair.as_str().to_string())),
+                Rule::wildcard => result.push(PathElement::Wildcard),
+                Rule::json_path_key => {
+                    let key = pair.into_inner().next().unwrap();
+
+                    match key.as_rule() {
+                        Rule::quoted_key => match unescape_quoted_key(key.as_str()) {
+                            Ok(unescaped) => result.push(PathElement::Key(unescaped)),
+                            Err(offset) => crate::bail_constraint_error!(
+                                "JSON path error near: {:?}: {}",
+                                path.to_string(),
+                                describe_error_at(
+                                    path,
+                                    key.as_span().start() + offset,
+                                    "invalid escape"
+                                )
+                            ),
+                        },
+                        _ => result.push(PathElement::Key(key.as_str().to_string())),
+                    }
+                }
                 Rule::array_locator => {
                     let mut array_locator = pair.into_inner();
                     let index_or_negative_indicator = array_locator.next().unwrap();
@@ -42,22 +149,38 @@ pub fn json_path(path: &str) -> crate::Result<JsonPath> {
                     match index_or_negative_indicator.as_rule() {
                         Rule::negative_index_indicator => {
                             let negative_offset = array_locator.next().unwrap();
-                            // TODO: sqlite is able to parse arbitrarily big numbers, but they
-                            //  always get overflown and cast to i32. Handle this.
-                            let parsed = negative_offset
-                                .as_str()
-                                .parse::<i128>()
-                                .unwrap_or(i128::MAX);
-
-                            result.push(PathElement::ArrayLocator(-parsed as i32));
+                            let parsed = negative_offset.as_str().parse().unwrap_or(i64::MAX);
+
+                            result.push(PathElement::ArrayLocatorFromEnd(parsed));
+                        }
+                        Rule::array_length_indicator => {
+                            result.push(PathElement::ArrayLocatorFromEnd(0));
+                        }
+                        Rule::wildcard => result.push(PathElement::Wildcard),
+                        Rule::array_slice => {
+                            let mut start = None;
+                            let mut end = None;
+                            for bound in index_or_negative_indicator.into_inner() {
+                                // Bounds beyond the range of i64 are saturated like indices.
+                                let parsed = bound.as_str().parse().unwrap_or(
+                                    if bound.as_str().starts_with('-') {
+                                        i64::MIN
+                                    } else {
+                                        i64::MAX
+                                    },
+                                );
+                                match bound.as_rule() {
+                                    Rule::slice_start => start = Some(parsed),
+                                    _ => end = Some(parsed),
+                                }
+                            }
+                            result.push(PathElement::Slice { start, end });
                         }
                         Rule::array_offset => {
                             let array_offset = index_or_negative_indicator.as_str();
-                            // TODO: sqlite is able to parse arbitrarily big numbers, but they
-                            //  always get overflown and cast to i32. Handle this.
-                            let parsed = array_offset.parse::<i128>().unwrap_or(i128::MAX);
+                            let parsed = array_offset.parse().unwrap_or(i64::MAX);
 
-                            result.push(PathElement::ArrayLocator(parsed as i32));
+                            result.push(PathElement::ArrayLocator(parsed));
                         }
                         _ => unreachable!(
                             "Unexpected rule: {:?}",
@@ -73,8 +196,191 @@ pub fn json_path(path: &str) -> crate::Result<JsonPath> {
 
         Ok(JsonPath { elements: result })
     } else {
-        crate::bail_constraint_error!("JSON path error near: {:?}", path.to_string());
+        if has_unterminated_quoted_key(path) {
+            crate::bail_parse_error!("unterminated quoted key in JSON path: {:?}", path);
+        }
+        let offset = match parsed.unwrap_err().location {
+            pest::error::InputLocation::Pos(pos) => pos,
+            pest::error::InputLocation::Span((start, _)) => start,
+        };
+        let found = match path[offset..].chars().next() {
+            Some(c) => format!("unexpected {:?}", c),
+            None => "unexpected end of path".to_string(),
+        };
+        crate::bail_constraint_error!(
+            "JSON path error near: {:?}: {}",
+            path.to_string(),
+            describe_error_at(path, offset, &found)
+        );
+    }
+}
+
+/// Parses a JSON Pointer (RFC 6901), eg. `/a/0` or `/a~1b`, into its reference tokens, with the
+/// `~1` and `~0` escapes decoded to '/' and '~'. The empty pointer addresses the whole document.
+/// Whether a token is a key or an array index depends on the document it's applied to.
+pub fn json_pointer(pointer: &str) -> crate::Result<Vec<String>> {
+    if pointer.is_empty() {
+        return Ok(vec![]);
+    }
+    if !pointer.starts_with('/') {
+        crate::bail_constraint_error!(
+            "JSON pointer error near: {:?}: {}",
+            pointer,
+            describe_error_at(pointer, 0, "expected '/'")
+        );
+    }
+
+    let mut tokens = vec![];
+    let mut chars = pointer.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '/' => tokens.push(String::new()),
+            '~' => {
+                let unescaped = match chars.next().map(|(_, c)| c) {
+                    Some('0') => '~',
+                    Some('1') => '/',
+                    _ => crate::bail_constraint_error!(
+                        "JSON pointer error near: {:?}: {}",
+                        pointer,
+                        describe_error_at(pointer, i, "invalid escape")
+                    ),
+                };
+                tokens.last_mut().unwrap().push(unescaped);
+            }
+            c => tokens.last_mut().unwrap().push(c),
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// Translates a JSON Pointer (RFC 6901), eg. `/foo/0/bar`, into path elements, starting with the
+/// root. Without a document to look at, tokens that are array indices in canonical form (no
+/// leading zeros) become array locators, `-` becomes `[#]` and any other token a key.
+// Not called yet: json_patch_ops resolves pointer tokens against the document it edits instead.
+#[allow(dead_code)]
+pub fn from_pointer(pointer: &str) -> crate::Result<Vec<PathElement>> {
+    let mut elements = vec![PathElement::Root()];
+
+    for token in json_pointer(pointer)? {
+        let is_index = token == "0"
+            || (!token.is_empty()
+                && !token.starts_with('0')
+                && token.bytes().all(|b| b.is_ascii_digit()));
+        let element = if token == "-" {
+            PathElement::ArrayLocatorFromEnd(0)
+        } else if is_index {
+            match token.parse::<i64>() {
+                Ok(idx) => PathElement::ArrayLocator(idx),
+                Err(_) => crate::bail_constraint_error!(
+                    "JSON pointer error near: {:?}: array index out of range: {}",
+                    pointer,
+                    token
+                ),
+            }
+        } else {
+            PathElement::Key(token)
+        };
+        elements.push(element);
+    }
+
+    Ok(elements)
+}
+
+/// Describes a problem at byte `offset` of an invalid path, with its one-based character
+/// position, followed by the path and a caret under that character, eg.
+/// ```text
+/// unexpected 'x' at position 5
+///   $.a[x]
+///       ^
+/// ```
+fn describe_error_at(path: &str, offset: usize, problem: &str) -> String {
+    let position = path[..offset].chars().count() + 1;
+    format!(
+        "{} at position {}\n  {}\n  {}^",
+        problem,
+        position,
+        path,
+        " ".repeat(position - 1)
+    )
+}
+
+/// Decodes the escapes in the contents of a quoted key, eg. $."a\"b" or $."caf\u00e9", the same
+/// ones as in JSON strings. Returns the byte offset of the backslash of an invalid escape.
+fn unescape_quoted_key(key: &str) -> Result<String, usize> {
+    let mut result = String::with_capacity(key.len());
+    let mut chars = key.char_indices();
+
+    while let Some((i, c)) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        let unescaped = match chars.next().map(|(_, c)| c) {
+            Some('"') => '"',
+            Some('\\') => '\\',
+            Some('/') => '/',
+            Some('b') => '\u{8}',
+            Some('f') => '\u{c}',
+            Some('n') => '\n',
+            Some('r') => '\r',
+            Some('t') => '\t',
+            Some('u') => {
+                let code = match next_hex4(&mut chars).ok_or(i)? {
+                    high @ 0xD800..=0xDBFF => {
+                        let low = match (chars.next(), chars.next()) {
+                            (Some((_, '\\')), Some((_, 'u'))) => next_hex4(&mut chars),
+                            _ => None,
+                        };
+                        match low {
+                            Some(low @ 0xDC00..=0xDFFF) => {
+                                0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)
+                            }
+                            _ => return Err(i),
+                        }
+                    }
+                    code => code,
+                };
+                char::from_u32(code).ok_or(i)?
+            }
+            _ => return Err(i),
+        };
+        result.push(unescaped);
+    }
+
+    Ok(result)
+}
+
+/// Consumes the 4 hex digits of a \u escape.
+fn next_hex4(chars: &mut std::str::CharIndices<'_>) -> Option<u32> {
+    let hex = chars.as_str().get(..4)?;
+    if !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    chars.nth(3);
+    u32::from_str_radix(hex, 16).ok()
+}
+
+/// Checks whether a quoted key, eg. $."a.b", is missing its closing quote.
+fn has_unterminated_quoted_key(path: &str) -> bool {
+    let mut in_quoted_key = false;
+    let mut chars = path.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if in_quoted_key => {
+                chars.next();
+            }
+            '"' if in_quoted_key => in_quoted_key = false,
+            '.' if chars.peek() == Some(&'"') => {
+                chars.next();
+                in_quoted_key = true;
+            }
+            _ => {}
+        }
     }
+
+    in_quoted_key
 }
 
 #[cfg(test)]
@@ -109,13 +415,212 @@ mod tests {
         let path = json_path("$[#-2]").unwrap();
         assert_eq!(path.elements.len(), 2);
         assert_eq!(path.elements[0], PathElement::Root());
-        assert_eq!(path.elements[1], PathElement::ArrayLocator(-2));
+        assert_eq!(path.elements[1], PathElement::ArrayLocatorFromEnd(2));
+    }
+
+    #[test]
+    fn test_json_path_array_length_locator() {
+        let path = json_path("$[#]").unwrap();
+        assert_eq!(path.elements.len(), 2);
+        assert_eq!(path.elements[0], PathElement::Root());
+        assert_eq!(path.elements[1], PathElement::ArrayLocatorFromEnd(0));
+    }
+
+    #[test]
+    fn test_json_path_wildcards() {
+        let path = json_path_with_extensions("$.a.*[*]").unwrap();
+        assert_eq!(
+            path.elements,
+            vec![
+                PathElement::Root(),
+                PathElement::Key("a".to_string()),
+                PathElement::Wildcard,
+                PathElement::Wildcard,
+            ]
+        );
+        assert_eq!(
+            json_path(r#"$."*""#).unwrap().elements[1],
+            PathElement::Key("*".to_string())
+        );
+    }
+
+    #[test]
+    fn test_json_path_rejects_extensions() {
+        for value in ["$.*", "$[*]", "$.a.*", "$.a[1:3]", "$[:]"] {
+            assert!(
+                matches!(
+                    json_path(value),
+                    Err(crate::error::LimboError::Constraint(_))
+                ),
+                "{}",
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn test_json_path_slices() {
+        for (value, start, end) in [
+            ("$[0:2]", Some(0), Some(2)),
+            ("$[1:]", Some(1), None),
+            ("$[:-1]", None, Some(-1)),
+            ("$[-2:]", Some(-2), None),
+            ("$[:]", None, None),
+            ("$[99999999999:]", Some(99999999999), None),
+            ("$[-99999999999:]", Some(-99999999999), None),
+            ("$[99999999999999999999:]", Some(i64::MAX), None),
+            ("$[-99999999999999999999:]", Some(i64::MIN), None),
+        ] {
+            let path = json_path_with_extensions(value).unwrap();
+            assert_eq!(
+                path.elements,
+                vec![PathElement::Root(), PathElement::Slice { start, end }],
+                "{}",
+                value
+            );
+        }
+        for value in ["$.a[1:3]", "$[:-1][0]", "$[-2:]"] {
+            assert_eq!(json_path_with_extensions(value).unwrap().to_string(), value);
+        }
+        for value in ["$[1:2:3]", "$[:x]", "$[- 1:]", "$[1 :]", "$[#-1:]", "$[*:]"] {
+            assert!(
+                matches!(
+                    json_path_with_extensions(value),
+                    Err(crate::error::LimboError::Constraint(_))
+                ),
+                "{}",
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn test_json_path_quoted_key() {
+        let path = json_path(r#"$."a.b"[0]."weird[key]""#).unwrap();
+        assert_eq!(path.elements.len(), 4);
+        assert_eq!(path.elements[0], PathElement::Root());
+        assert_eq!(path.elements[1], PathElement::Key("a.b".to_string()));
+        assert_eq!(path.elements[2], PathElement::ArrayLocator(0));
+        assert_eq!(path.elements[3], PathElement::Key("weird[key]".to_string()));
+    }
+
+    #[test]
+    fn test_json_path_numeric_key() {
+        let path = json_path("$.123.0abc[4].5").unwrap();
+        assert_eq!(
+            path.elements,
+            vec![
+                PathElement::Root(),
+                PathElement::Key("123".to_string()),
+                PathElement::Key("0abc".to_string()),
+                PathElement::ArrayLocator(4),
+                PathElement::Key("5".to_string()),
+            ]
+        );
+        // Such keys are written quoted, which reads back the same.
+        assert_eq!(path.to_string(), r#"$."123"."0abc"[4]."5""#);
+        assert!(json_path("$.1-2").is_err());
+    }
+
+    #[test]
+    fn test_json_path_quoted_key_escapes() {
+        let path = json_path(r#"$."a\"b\\c""#).unwrap();
+        assert_eq!(path.elements.len(), 2);
+        assert_eq!(path.elements[1], PathElement::Key(r#"a"b\c"#.to_string()));
+    }
+
+    #[test]
+    fn test_json_path_quoted_key_unicode_escapes() {
+        for (value, expected) in [
+            (r#"$."caf\u00e9""#, "café"),
+            (r#"$."\uD83D\ude00!""#, "😀!"),
+            (r#"$."a\/b\tc""#, "a/b\tc"),
+        ] {
+            let path = json_path(value).unwrap();
+            assert_eq!(path.elements[1], PathElement::Key(expected.to_string()));
+        }
+    }
+
+    #[test]
+    fn test_json_path_quoted_key_invalid_escapes() {
+        for (value, position) in [
+            (r#"$."caf\u00zz""#, 7),
+            (r#"$.a."\x""#, 6),
+            (r#"$."é\uD83D""#, 5),
+            (r#"$."\udE00""#, 4),
+            (r#"$."\uD83Dx\uDE00""#, 4),
+        ] {
+            match json_path(value) {
+                Err(crate::error::LimboError::Constraint(msg)) => assert!(
+                    msg.contains(&format!("invalid escape at position {}\n", position)),
+                    "path: {:?}, message: {:?}",
+                    value,
+                    msg
+                ),
+                result => panic!("Expected error for: {:?}, got: {:?}", value, result),
+            }
+        }
+    }
+
+    #[test]
+    fn test_json_path_unterminated_quoted_key() {
+        for value in [r#"$."a.b"#, r#"$.a."b\""#] {
+            let path = json_path(value);
+
+            match path {
+                Err(crate::error::LimboError::ParseError(_)) => {
+                    // happy path
+                }
+                _ => panic!("Expected parse error for: {:?}, got: {:?}", value, path),
+            }
+        }
+    }
+
+    #[test]
+    fn test_json_path_display() {
+        for value in [
+            "$",
+            "$.a[0]",
+            "$.a_1.b[#].c[#-2]",
+            r#"$."a b"."1x"."a\"b\\c"."""#,
+            "$.a.*",
+        ] {
+            assert_eq!(json_path_with_extensions(value).unwrap().to_string(), value);
+        }
+        assert_eq!(
+            json_path_with_extensions(r#"$."a"[*]"#)
+                .unwrap()
+                .to_string(),
+            "$.a.*"
+        );
+    }
+
+    #[test]
+    fn test_json_path_error_position() {
+        for (value, expected) in [
+            ("$.a[x]", "unexpected 'x' at position 5\n  $.a[x]\n      ^"),
+            ("x", "unexpected 'x' at position 1"),
+            ("$.", "unexpected end of path at position 3"),
+            ("$.é[", "unexpected end of path at position 5"),
+            ("$[-1]", "unexpected '-' at position 3"),
+        ] {
+            match json_path(value) {
+                Err(crate::error::LimboError::Constraint(msg)) => assert!(
+                    msg.contains(expected),
+                    "path: {:?}, message: {:?}",
+                    value,
+                    msg
+                ),
+                result => panic!("Expected error for: {:?}, got: {:?}", value, result),
+            }
+        }
     }
 
     #[test]
     fn test_json_path_invalid() {
         let invalid_values = vec![
-            "", "$$$", "$.", "$ ", "$[", "$]", "$[-1]", "x", "[]", "$[0", "$[0x]", "$\"",
+            "", "$$$", "$.", "$ ", "$[", "$]", "$[-1]", "x", "[]", "$[0", "$[0x]", "$\"", "$[#0]",
+            "$[##]",
         ];
 
         for value in invalid_values {
@@ -140,4 +645,127 @@ mod tests {
         assert_eq!(path.elements[3], PathElement::ArrayLocator(0));
         assert_eq!(path.elements[4], PathElement::Key("title".to_string()));
     }
+
+    #[test]
+    fn test_json_path_consecutive_array_locators() {
+        let path = json_path("$[0][1][#-2][#]").unwrap();
+        assert_eq!(
+            path.elements,
+            vec![
+                PathElement::Root(),
+                PathElement::ArrayLocator(0),
+                PathElement::ArrayLocator(1),
+                PathElement::ArrayLocatorFromEnd(2),
+                PathElement::ArrayLocatorFromEnd(0),
+            ]
+        );
+
+        let path = json_path("$.a[0].b[1][2].c").unwrap();
+        assert_eq!(
+            path.elements,
+            vec![
+                PathElement::Root(),
+                PathElement::Key("a".to_string()),
+                PathElement::ArrayLocator(0),
+                PathElement::Key("b".to_string()),
+                PathElement::ArrayLocator(1),
+                PathElement::ArrayLocator(2),
+                PathElement::Key("c".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_json_pointer() {
+        assert_eq!(json_pointer("").unwrap(), Vec::<String>::new());
+        assert_eq!(json_pointer("/").unwrap(), vec![""]);
+        assert_eq!(json_pointer("/a/0").unwrap(), vec!["a", "0"]);
+        assert_eq!(
+            json_pointer("/a~1b/c~0d/-").unwrap(),
+            vec!["a/b", "c~d", "-"]
+        );
+        assert_eq!(json_pointer("/~01").unwrap(), vec!["~1"]);
+        assert_eq!(json_pointer("//é").unwrap(), vec!["", "é"]);
+    }
+
+    #[test]
+    fn test_json_pointer_invalid() {
+        for (value, expected) in [
+            ("a", "expected '/' at position 1"),
+            ("$.a", "expected '/' at position 1"),
+            ("/a~2", "invalid escape at position 3"),
+            ("/a~", "invalid escape at position 3"),
+        ] {
+            match json_pointer(value) {
+                Err(crate::error::LimboError::Constraint(msg)) => assert!(
+                    msg.contains(expected),
+                    "pointer: {:?}, message: {:?}",
+                    value,
+                    msg
+                ),
+                result => panic!("Expected error for: {:?}, got: {:?}", value, result),
+            }
+        }
+    }
+
+    #[test]
+    fn test_from_pointer() {
+        assert_eq!(from_pointer("").unwrap(), vec![PathElement::Root()]);
+        assert_eq!(
+            from_pointer("/foo/0/bar").unwrap(),
+            vec![
+                PathElement::Root(),
+                PathElement::Key("foo".to_string()),
+                PathElement::ArrayLocator(0),
+                PathElement::Key("bar".to_string()),
+            ]
+        );
+        assert_eq!(
+            from_pointer("/a~1b/m~0n/~01/-").unwrap(),
+            vec![
+                PathElement::Root(),
+                PathElement::Key("a/b".to_string()),
+                PathElement::Key("m~n".to_string()),
+                PathElement::Key("~1".to_string()),
+                PathElement::ArrayLocatorFromEnd(0),
+            ]
+        );
+        assert_eq!(
+            from_pointer("//01/-1/12").unwrap(),
+            vec![
+                PathElement::Root(),
+                PathElement::Key("".to_string()),
+                PathElement::Key("01".to_string()),
+                PathElement::Key("-1".to_string()),
+                PathElement::ArrayLocator(12),
+            ]
+        );
+        assert_eq!(
+            from_pointer("/9223372036854775807").unwrap(),
+            vec![PathElement::Root(), PathElement::ArrayLocator(i64::MAX)]
+        );
+    }
+
+    #[test]
+    fn test_from_pointer_invalid() {
+        for (value, expected) in [
+            (
+                "/9223372036854775808",
+                "array index out of range: 9223372036854775808",
+            ),
+            ("/a/99999999999999999999", "array index out of range"),
+            ("a/0", "expected '/'"),
+            ("/a~", "invalid escape"),
+        ] {
+            match from_pointer(value) {
+                Err(crate::error::LimboError::Constraint(msg)) => assert!(
+                    msg.contains(expected),
+                    "pointer: {:?}, message: {:?}",
+                    value,
+                    msg
+                ),
+                result => panic!("Expected error for: {:?}, got: {:?}", value, result),
+            }
+        }
+    }
 }
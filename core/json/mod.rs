@@ -1,130 +1,499 @@
+mod builder;
 mod de;
+mod each;
 mod error;
+#[cfg(feature = "serde_json_interop")]
+mod interop;
 mod json_path;
+mod scan;
 mod ser;
+mod visit;
 
+use std::borrow::Cow;
+use std::cell::RefCell;
 use std::rc::Rc;
 
-pub use crate::json::de::from_str;
+use crate::json::de::from_str_with_limits;
+use crate::json::de::{
+    find_object_member, strict_array_length, strict_json_kind, top_level_type, unescape, validate,
+    JsonKind,
+};
+pub use crate::json::de::{from_str, ArrayCursor, JsonLimits};
+pub use crate::json::each::{json_each, json_tree, JsonEach, JsonEachRow, JsonTree};
 use crate::json::error::Error as JsonError;
-use crate::json::json_path::{json_path, JsonPath, PathElement};
+use crate::json::json_path::{
+    json_path, json_path_with_extensions, json_pointer, JsonPath, PathElement,
+};
+use crate::json::scan::is_compact;
 pub use crate::json::ser::to_string;
+use crate::json::ser::{escape, val_to_canonical_string, val_to_string, write_object, write_val};
+pub use crate::json::visit::{JsonVisitor, VisitControl};
 use crate::types::{LimboText, OwnedValue, TextSubtype};
 use indexmap::IndexMap;
 use jsonb::Error as JsonbError;
-use serde::{Deserialize, Serialize};
+use serde::Serialize;
 
-#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
-#[serde(untagged)]
-pub enum Val {
+/// `json_valid()` flag: the input is RFC 8259 JSON text.
+const JSON_VALID_RFC_8259: i64 = 0x01;
+/// `json_valid()` flag: the input is JSON5 text.
+const JSON_VALID_JSON5: i64 = 0x02;
+/// `json_valid()` flag: the input looks like JSONB. Checked as strictly as `JSON_VALID_JSONB`.
+const JSON_VALID_JSONB_SUPERFICIAL: i64 = 0x04;
+/// `json_valid()` flag: the input is JSONB.
+const JSON_VALID_JSONB: i64 = 0x08;
+
+/// Number of parsed JSON texts kept around by `get_json_value_cached`.
+const JSON_CACHE_SIZE: usize = 4;
+
+thread_local! {
+    /// Recently parsed JSON texts, least recently used first.
+    static JSON_CACHE: RefCell<Vec<(Rc<String>, Rc<Val>)>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Maximum nesting depth of arrays and objects, the same as SQLite's. Deeper input fails with an
+/// "excessive nesting" error instead of overflowing the stack.
+const JSON_MAX_DEPTH: usize = 1000;
+
+/// Maximum length of JSON text in bytes, the same as SQLite's limit for any string or blob.
+const JSON_MAX_LENGTH: usize = 1_000_000_000;
+
+/// Newtype struct name the serializer recognizes to emit `Val::RawNumber` text unquoted.
+const RAW_NUMBER_TOKEN: &str = "$limbo::json::RawNumber";
+
+/// Newtype struct name the serializer recognizes to emit `Val::RawJson` text as it is.
+const RAW_JSON_TOKEN: &str = "$limbo::json::RawJson";
+
+/// A parsed JSON value. Strings without escapes borrow from the text they were parsed from, so
+/// parsing doesn't allocate for them; `into_owned` detaches a value from its source, eg. to store
+/// it past the lifetime of the text.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Val<'a> {
     Null,
     Bool(bool),
     Integer(i64),
     Float(f64),
-    String(String),
-    Array(Vec<Val>),
-    Object(IndexMap<String, Val>),
+    /// A number kept in its source form (e.g. `1.0`, `1E5` or an integer too large for `i64`),
+    /// so that it's written back exactly as it was read, like SQLite does: the sign of `-0.0`,
+    /// the case of the exponent and its `+` sign are all kept.
+    RawNumber(String),
+    /// Text of a SQL value with the JSON subtype, embedded into a document being built. Values
+    /// with the JSON subtype were produced by JSON functions, so they're trusted to be valid and
+    /// are written out as they are, without being parsed again.
+    RawJson(Cow<'a, str>),
+    String(Cow<'a, str>),
+    Array(Vec<Val<'a>>),
+    Object(IndexMap<String, Val<'a>>),
+}
+
+impl Serialize for Val<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        match self {
+            Val::Null => serializer.serialize_unit(),
+            Val::Bool(b) => serializer.serialize_bool(*b),
+            Val::Integer(i) => serializer.serialize_i64(*i),
+            Val::Float(f) => serializer.serialize_f64(*f),
+            Val::RawNumber(s) => serializer.serialize_newtype_struct(RAW_NUMBER_TOKEN, s),
+            Val::RawJson(s) => serializer.serialize_newtype_struct(RAW_JSON_TOKEN, s),
+            Val::String(s) => serializer.serialize_str(s),
+            Val::Array(items) => items.serialize(serializer),
+            Val::Object(members) => members.serialize(serializer),
+        }
+    }
+}
+
+impl Val<'_> {
+    /// Copies the borrowed strings, so that the value no longer depends on the parsed text.
+    pub fn into_owned(self) -> Val<'static> {
+        match self {
+            Val::Null => Val::Null,
+            Val::Bool(b) => Val::Bool(b),
+            Val::Integer(i) => Val::Integer(i),
+            Val::Float(f) => Val::Float(f),
+            Val::RawNumber(s) => Val::RawNumber(s),
+            Val::RawJson(s) => Val::RawJson(Cow::Owned(s.into_owned())),
+            Val::String(s) => Val::String(Cow::Owned(s.into_owned())),
+            Val::Array(items) => Val::Array(items.into_iter().map(Val::into_owned).collect()),
+            Val::Object(members) => Val::Object(
+                members
+                    .into_iter()
+                    .map(|(key, value)| (key, value.into_owned()))
+                    .collect(),
+            ),
+        }
+    }
+
+    /// Returns the JSON type name of the value, as reported by json_type().
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Val::Null => "null",
+            Val::Bool(true) => "true",
+            Val::Bool(false) => "false",
+            Val::Integer(_) => "integer",
+            Val::RawNumber(s) if !s.contains(['.', 'e', 'E']) => "integer",
+            Val::Float(_) | Val::RawNumber(_) => "real",
+            Val::String(_) => "text",
+            Val::Array(_) => "array",
+            Val::Object(_) => "object",
+            // The text was written by the serializer, so its first token tells the type.
+            Val::RawJson(s) => top_level_type(s).unwrap_or("null"),
+        }
+    }
+}
+
+impl<'a> Val<'a> {
+    /// Parses JSON5 text the way the JSON functions do, but within `limits` rather than the
+    /// default ones, eg. to reject documents that are too large for the application early.
+    pub fn parse(text: &'a str, limits: &JsonLimits) -> crate::Result<Self> {
+        from_str_with_limits(text, limits).map_err(malformed_json)
+    }
+
+    // The accessors below see `RawJson` as opaque text, so they return None/false for it.
+
+    /// The contents of a JSON string.
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Val::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// The value of an integer, including one kept in its source form that fits in an `i64`.
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Val::Integer(i) => Some(*i),
+            Val::RawNumber(s) => s.parse().ok(),
+            _ => None,
+        }
+    }
+
+    /// The value of any number, integer or real.
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Val::Integer(i) => Some(*i as f64),
+            Val::Float(f) => Some(*f),
+            Val::RawNumber(s) => s.parse().ok(),
+            _ => None,
+        }
+    }
+
+    /// The value of `true` or `false`.
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Val::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    /// The elements of an array.
+    pub fn as_array(&self) -> Option<&[Val<'a>]> {
+        match self {
+            Val::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+
+    /// The members of an object.
+    pub fn as_object(&self) -> Option<&IndexMap<String, Val<'a>>> {
+        match self {
+            Val::Object(members) => Some(members),
+            _ => None,
+        }
+    }
+
+    /// Whether the value is JSON null.
+    pub fn is_null(&self) -> bool {
+        matches!(self, Val::Null)
+    }
 }
 
 pub fn get_json(json_value: &OwnedValue) -> crate::Result<OwnedValue> {
     match json_value {
         OwnedValue::Text(ref t) => {
             // optimization: once we know the subtype is a valid JSON, we do not have
-            // to go through parsing JSON and serializing it back to string
-            if t.subtype == TextSubtype::Json {
-                return Ok(json_value.to_owned());
+            // to go through parsing JSON and serializing it back to string, unless it has
+            // whitespace to drop
+            if t.subtype == TextSubtype::Json && is_compact(&t.value) {
+                return Ok(OwnedValue::Text(LimboText::json(t.value.clone())));
             }
 
             let json_val = get_json_value(json_value)?;
-            let json = to_string(&json_val).unwrap();
+            let json = val_to_string(&json_val)?;
 
-            Ok(OwnedValue::Text(LimboText::json(Rc::new(json))))
-        }
-        OwnedValue::Blob(b) => {
-            // TODO: use get_json_value after we implement a single Struct
-            //   to represent both JSON and JSONB
-            if let Ok(json) = jsonb::from_slice(b) {
-                Ok(OwnedValue::Text(LimboText::json(Rc::new(json.to_string()))))
+            // Text that is already minified JSON shares the input's string instead of a copy.
+            let json = if json == *t.value {
+                t.value.clone()
             } else {
-                crate::bail_parse_error!("malformed JSON");
-            }
+                Rc::new(json)
+            };
+            Ok(OwnedValue::Text(LimboText::json(json)))
         }
         OwnedValue::Null => Ok(OwnedValue::Null),
+        // JSONB goes through `Val` as well, so that it's written exactly like the same text.
         _ => {
             let json_val = get_json_value(json_value)?;
-            let json = to_string(&json_val).unwrap();
+            let json = val_to_string(&json_val)?;
 
             Ok(OwnedValue::Text(LimboText::json(Rc::new(json))))
         }
     }
 }
 
-fn get_json_value(json_value: &OwnedValue) -> crate::Result<Val> {
+/// Returns the JSONB encoding of the input, the counterpart of get_json for jsonb(). Text is
+/// parsed the same way as in get_json (JSON5 is accepted). Blobs that are already JSONB are only
+/// validated and returned as they are, sharing the input's bytes, while blobs holding JSON text
+/// are encoded like text.
+/// https://sqlite.org/json1.html#jminib
+pub fn get_jsonb(json_value: &OwnedValue) -> crate::Result<OwnedValue> {
     match json_value {
-        OwnedValue::Text(ref t) => match from_str::<Val>(&t.value) {
-            Ok(json) => Ok(json),
-            Err(_) => {
-                crate::bail_parse_error!("malformed JSON")
-            }
-        },
-        OwnedValue::Blob(b) => {
-            if let Ok(_json) = jsonb::from_slice(b) {
-                todo!("jsonb to json conversion");
-            } else {
-                crate::bail_parse_error!("malformed JSON");
-            }
+        OwnedValue::Null => Ok(OwnedValue::Null),
+        OwnedValue::Blob(b) if is_jsonb(b) => Ok(OwnedValue::Blob(b.clone())),
+        _ => {
+            let json_val = get_json_value(json_value)?;
+            Ok(OwnedValue::Blob(Rc::new(val_to_jsonb(&json_val)?.to_vec())))
         }
+    }
+}
+
+/// Returns the input as minified JSON text like get_json, or as JSONB like get_jsonb, whatever
+/// the representation of the input. `JsonRepr::of` keeps the representation of the input.
+pub fn get_json_with_format(
+    json_value: &OwnedValue,
+    format: JsonRepr,
+) -> crate::Result<OwnedValue> {
+    match format {
+        JsonRepr::Text => get_json(json_value),
+        JsonRepr::Jsonb => get_jsonb(json_value),
+    }
+}
+
+/// Returns the RFC 8785 canonical form of the JSON text or JSONB, see `val_to_canonical_string`.
+pub fn json_canonicalize(json_value: &OwnedValue) -> crate::Result<OwnedValue> {
+    if let OwnedValue::Null = json_value {
+        return Ok(OwnedValue::Null);
+    }
+    let json_val = get_json_value(json_value)?;
+    let json = val_to_canonical_string(&json_val)?;
+    Ok(OwnedValue::Text(LimboText::json(Rc::new(json))))
+}
+
+fn get_json_value(json_value: &OwnedValue) -> crate::Result<Val<'_>> {
+    match json_value {
+        OwnedValue::Text(ref t) => from_str::<Val>(&t.value).map_err(malformed_json),
+        OwnedValue::Blob(b) => match jsonb::from_slice(b) {
+            Ok(json) => Ok(jsonb_to_val(&json)),
+            Err(_) => json_text_from_blob(b),
+        },
         OwnedValue::Null => Ok(Val::Null),
         OwnedValue::Float(f) => Ok(Val::Float(*f)),
         OwnedValue::Integer(i) => Ok(Val::Integer(*i)),
-        _ => Ok(Val::String(json_value.to_string())),
+        _ => Ok(Val::String(json_value.to_string().into())),
     }
 }
 
-pub fn json_array(values: &[OwnedValue]) -> crate::Result<OwnedValue> {
-    let mut s = String::new();
-    s.push('[');
+/// Turns an error parsing JSON text into the error of the function that was given the text.
+/// Errors other than too deep nesting are reported as malformed JSON, like SQLite does, followed
+/// by what's wrong and where.
+fn malformed_json(err: JsonError) -> crate::LimboError {
+    if err.is_limit_exceeded() {
+        return err.into();
+    }
+    crate::LimboError::ParseError(format!("malformed JSON: {:#}", err))
+}
 
-    // TODO: use `convert_db_type_to_json` and map each value with that function,
-    // so we can construct a `Val::Array` with each value and then serialize it directly.
-    for (idx, value) in values.iter().enumerate() {
-        match value {
-            OwnedValue::Blob(_) => crate::bail_constraint_error!("JSON cannot hold BLOB values"),
-            OwnedValue::Text(t) => {
-                if t.subtype == TextSubtype::Json {
-                    s.push_str(&t.value);
-                } else {
-                    match to_string(&*t.value) {
-                        Ok(json) => s.push_str(&json),
-                        Err(_) => crate::bail_parse_error!("malformed JSON"),
-                    }
-                }
-            }
-            OwnedValue::Integer(i) => match to_string(&i) {
-                Ok(json) => s.push_str(&json),
-                Err(_) => crate::bail_parse_error!("malformed JSON"),
-            },
-            OwnedValue::Float(f) => match to_string(&f) {
-                Ok(json) => s.push_str(&json),
-                Err(_) => crate::bail_parse_error!("malformed JSON"),
-            },
-            OwnedValue::Null => s.push_str("null"),
-            _ => unreachable!(),
+/// Parses a blob that isn't valid JSONB as UTF-8 JSON text, since some applications store JSON
+/// text in BLOB columns.
+fn json_text_from_blob(b: &[u8]) -> crate::Result<Val<'_>> {
+    match std::str::from_utf8(b) {
+        Ok(text) => from_str::<Val>(text).map_err(malformed_json),
+        Err(_) => crate::bail_parse_error!("malformed JSON"),
+    }
+}
+
+/// Converts a decoded JSONB value into a `Val`.
+fn jsonb_to_val(json: &jsonb::Value) -> Val<'static> {
+    match json {
+        jsonb::Value::Null => Val::Null,
+        jsonb::Value::Bool(b) => Val::Bool(*b),
+        jsonb::Value::Number(jsonb::Number::Int64(i)) => Val::Integer(*i),
+        jsonb::Value::Number(jsonb::Number::UInt64(u)) => match i64::try_from(*u) {
+            Ok(i) => Val::Integer(i),
+            Err(_) => Val::RawNumber(u.to_string()),
+        },
+        jsonb::Value::Number(jsonb::Number::Float64(f)) if *f == 0.0 && f.is_sign_negative() => {
+            Val::RawNumber("-0".to_string())
         }
+        jsonb::Value::Number(jsonb::Number::Float64(f)) => Val::Float(*f),
+        jsonb::Value::String(s) => Val::String(s.to_string().into()),
+        jsonb::Value::Array(items) => Val::Array(items.iter().map(jsonb_to_val).collect()),
+        jsonb::Value::Object(members) => Val::Object(
+            members
+                .iter()
+                .map(|(key, value)| (key.clone(), jsonb_to_val(value)))
+                .collect(),
+        ),
+    }
+}
+
+/// Converts a `Val` into the jsonb crate's representation, for encoding it as JSONB.
+fn val_to_jsonb(json: &Val) -> crate::Result<jsonb::Value<'static>> {
+    let value = match json {
+        Val::Null => jsonb::Value::Null,
+        Val::Bool(b) => jsonb::Value::Bool(*b),
+        Val::Integer(i) => jsonb::Value::Number(jsonb::Number::Int64(*i)),
+        // Negative zero is written as `0.0`, so its sign is dropped here. That leaves a negative
+        // zero in JSONB meaning `-0` from JSON text.
+        Val::Float(f) if *f == 0.0 => jsonb::Value::Number(jsonb::Number::Float64(0.0)),
+        Val::Float(f) => jsonb::Value::Number(jsonb::Number::Float64(*f)),
+        // Integers that fit a u64 are kept exactly. JSONB numbers have no other form, so larger
+        // integers, reals and `-0` are stored as floats.
+        Val::RawNumber(s) => match (s.parse::<i64>(), s.parse::<u64>()) {
+            (Ok(i), _) if i.to_string() == *s => jsonb::Value::Number(jsonb::Number::Int64(i)),
+            (_, Ok(u)) if u.to_string() == *s => jsonb::Value::Number(jsonb::Number::UInt64(u)),
+            _ => jsonb::Value::Number(jsonb::Number::Float64(s.parse().unwrap_or(f64::NAN))),
+        },
+        // JSONB has its own encoding, so embedded JSON text has to be parsed after all.
+        Val::RawJson(s) => val_to_jsonb(&from_str::<Val>(s).map_err(malformed_json)?)?,
+        Val::String(s) => jsonb::Value::String(Cow::Owned(s.to_string())),
+        Val::Array(items) => jsonb::Value::Array(
+            items
+                .iter()
+                .map(val_to_jsonb)
+                .collect::<crate::Result<_>>()?,
+        ),
+        Val::Object(members) => jsonb::Value::Object(
+            members
+                .iter()
+                .map(|(key, value)| Ok((key.clone(), val_to_jsonb(value)?)))
+                .collect::<crate::Result<_>>()?,
+        ),
+    };
+    Ok(value)
+}
+
+/// Whether the JSON text is in the cache of `get_json_value_cached`.
+fn is_json_cached(text: &Rc<String>) -> bool {
+    JSON_CACHE.with_borrow(|cache| cache.iter().any(|(cached, _)| cached == text))
+}
+
+/// Like `get_json_value`, but text inputs go through a small cache of recently parsed documents,
+/// so that extracting several paths from the same value (e.g. chained json_extract calls over
+/// the same column) only parses it once. Similar to SQLite's JSON parse cache.
+fn get_json_value_cached(json_value: &OwnedValue) -> crate::Result<Rc<Val<'_>>> {
+    let OwnedValue::Text(t) = json_value else {
+        return get_json_value(json_value).map(Rc::new);
+    };
 
-        if idx < values.len() - 1 {
-            s.push(',');
+    let cached = JSON_CACHE.with_borrow_mut(|cache| {
+        let idx = cache.iter().position(|(text, _)| *text == t.value)?;
+        let entry = cache.remove(idx);
+        let json = entry.1.clone();
+        cache.push(entry);
+        Some(json)
+    });
+    if let Some(json) = cached {
+        return Ok(json);
+    }
+
+    // Only successfully parsed texts are cached, so malformed input keeps failing every time.
+    let json = Rc::new(get_json_value(json_value)?.into_owned());
+    JSON_CACHE.with_borrow_mut(|cache| {
+        if cache.len() == JSON_CACHE_SIZE {
+            cache.remove(0);
         }
+        cache.push((t.value.clone(), json.clone()));
+    });
+    Ok(json)
+}
+
+pub fn json_array(values: &[OwnedValue]) -> crate::Result<OwnedValue> {
+    let items = json_array_items(values)?;
+    let result = val_to_string(&Val::Array(items))?;
+    Ok(OwnedValue::Text(LimboText::json(Rc::new(result))))
+}
+
+/// Same as json_array, but returns the array encoded as JSONB.
+pub fn jsonb_array(values: &[OwnedValue]) -> crate::Result<OwnedValue> {
+    let items = json_array_items(values)?;
+    Ok(OwnedValue::Blob(Rc::new(
+        val_to_jsonb(&Val::Array(items))?.to_vec(),
+    )))
+}
+
+/// Converts the arguments of json_array/jsonb_array into array items.
+fn json_array_items(values: &[OwnedValue]) -> crate::Result<Vec<Val<'_>>> {
+    values.iter().map(owned_value_to_val).collect()
+}
+
+/// Converts a single SQL value into its JSON representation. Text is always quoted, never
+/// parsed, unless it already carries the JSON subtype, in which case it is returned like json()
+/// returns it.
+/// https://sqlite.org/json1.html#jquote
+pub fn json_quote(value: &OwnedValue) -> crate::Result<OwnedValue> {
+    let json = match value {
+        OwnedValue::Text(t) if t.subtype == TextSubtype::Json => return get_json(value),
+        OwnedValue::Text(t) => to_string(&*t.value),
+        OwnedValue::Integer(i) => to_string(i),
+        OwnedValue::Float(f) => to_string(f),
+        OwnedValue::Null => Ok("null".to_string()),
+        OwnedValue::Blob(_) => return Err(crate::LimboError::BlobInJson),
+        unsupported_value => crate::bail_constraint_error!(
+            "JSON cannot hold this type of value: {unsupported_value:?}"
+        ),
+    };
+
+    match json {
+        Ok(json) => Ok(OwnedValue::Text(LimboText::json(Rc::new(json)))),
+        Err(_) => crate::bail_parse_error!("malformed JSON"),
+    }
+}
+
+/// Escapes the text of a value for a JSON string literal, like json_quote but without the quotes.
+pub fn json_escape(value: &OwnedValue) -> crate::Result<OwnedValue> {
+    match value {
+        OwnedValue::Null => Ok(OwnedValue::Null),
+        OwnedValue::Blob(_) => Err(crate::LimboError::BlobInJson),
+        value => Ok(OwnedValue::build_text(Rc::new(escape(&value.to_string())))),
     }
+}
 
-    s.push(']');
-    Ok(OwnedValue::Text(LimboText::json(Rc::new(s))))
+/// Decodes the escape sequences of a JSON string given without its quotes, reversing json_escape.
+pub fn json_unescape(value: &OwnedValue) -> crate::Result<OwnedValue> {
+    match value {
+        OwnedValue::Null => Ok(OwnedValue::Null),
+        OwnedValue::Blob(_) => Err(crate::LimboError::BlobInJson),
+        value => match unescape(&value.to_string()) {
+            Ok(text) => Ok(OwnedValue::build_text(Rc::new(text))),
+            Err(_) => crate::bail_parse_error!("malformed JSON string"),
+        },
+    }
 }
 
+/// Returns the number of elements of the array at the given path, 0 if the path resolves to
+/// any other JSON value, or NULL if it doesn't resolve.
+/// https://sqlite.org/json1.html#jarraylen
 pub fn json_array_length(
     json_value: &OwnedValue,
     json_path: Option<&OwnedValue>,
 ) -> crate::Result<OwnedValue> {
+    if let OwnedValue::Null = json_value {
+        return Ok(OwnedValue::Null);
+    }
+    if let OwnedValue::Blob(blob) = json_value {
+        if let Some(len) = jsonb_array_length(blob, json_path)? {
+            return Ok(OwnedValue::Integer(len as i64));
+        }
+    }
+    // Without a path, the elements of a top-level array are counted by scanning the text, so the
+    // common json_array_length(column) doesn't build values just to count them.
+    if let (OwnedValue::Text(t), None) = (json_value, json_path) {
+        if let Some(len) = strict_array_length(&t.value) {
+            return Ok(OwnedValue::Integer(len as i64));
+        }
+    }
+
     let json = get_json_value(json_value)?;
 
     let arr_val = if let Some(path) = json_path {
@@ -133,36 +502,113 @@ pub fn json_array_length(
             None => return Ok(OwnedValue::Null),
         }
     } else {
-        &json
+        Cow::Borrowed(&json)
     };
 
-    match arr_val {
-        Val::Array(val) => Ok(OwnedValue::Integer(val.len() as i64)),
-        Val::Null => Ok(OwnedValue::Null),
-        _ => Ok(OwnedValue::Integer(0)),
+    let length = arr_val.as_array().map_or(0, <[Val]>::len);
+    Ok(OwnedValue::Integer(length as i64))
+}
+
+/// Returns an array of the keys of the object at the given path, or NULL if there is none.
+pub fn json_keys(
+    json_value: &OwnedValue,
+    json_path: Option<&OwnedValue>,
+) -> crate::Result<OwnedValue> {
+    list_container(json_value, json_path, |container| {
+        let members = container.as_object()?;
+        Some(
+            members
+                .keys()
+                .map(|key| Val::String(Cow::Borrowed(key)))
+                .collect(),
+        )
+    })
+}
+
+/// Returns an array of the values of the object or array at the given path, or NULL if there is
+/// neither.
+pub fn json_values(
+    json_value: &OwnedValue,
+    json_path: Option<&OwnedValue>,
+) -> crate::Result<OwnedValue> {
+    list_container(json_value, json_path, |container| match container {
+        Val::Object(members) => Some(members.values().cloned().collect()),
+        Val::Array(items) => Some(items.clone()),
+        _ => None,
+    })
+}
+
+/// Finds the value at `json_path`, or the root without a path, and returns the array that `list`
+/// makes of it, or NULL if `list` returns None or the path doesn't resolve.
+fn list_container(
+    json_value: &OwnedValue,
+    json_path: Option<&OwnedValue>,
+    list: impl for<'a> FnOnce(&'a Val<'a>) -> Option<Vec<Val<'a>>>,
+) -> crate::Result<OwnedValue> {
+    if let OwnedValue::Null = json_value {
+        return Ok(OwnedValue::Null);
+    }
+
+    let json = get_json_value(json_value)?;
+
+    let container = if let Some(path) = json_path {
+        match json_extract_single(&json, path, true)? {
+            Some(val) => val,
+            None => return Ok(OwnedValue::Null),
+        }
+    } else {
+        Cow::Borrowed(&json)
+    };
+
+    let Some(items) = list(container.as_ref()) else {
+        return Ok(OwnedValue::Null);
+    };
+
+    let json = val_to_string(&Val::Array(items))?;
+    Ok(OwnedValue::Text(LimboText::json(Rc::new(json))))
+}
+
+/// Reads the length of a JSONB array from its header, without decoding the elements. Returns
+/// None unless the path, which can't have wildcards, leads to an array; the other cases are left
+/// to json_array_length's general path, which decodes the document.
+fn jsonb_array_length(blob: &[u8], path: Option<&OwnedValue>) -> crate::Result<Option<usize>> {
+    // Blobs that are valid UTF-8 may hold JSON text, so they take the general path. JSONB arrays
+    // never do, as their first byte can't start a UTF-8 sequence.
+    if std::str::from_utf8(blob).is_ok() {
+        return Ok(None);
     }
+    let target = match path {
+        None => Cow::Borrowed(blob),
+        Some(path) => {
+            let Some(path) = CompiledPath::new(path, true)? else {
+                return Ok(None);
+            };
+            match jsonb_navigate(blob, &path.elements) {
+                Some((target, [])) => target,
+                _ => return Ok(None),
+            }
+        }
+    };
+    Ok(jsonb::array_length(&target))
 }
 
-/// Implements the -> operator. Always returns a proper JSON value.
+/// Implements the -> operator. Always returns a proper JSON value, including a JSON `null`
+/// when the path doesn't resolve. The path may also be a bare key or a bare array index.
 /// https://sqlite.org/json1.html#the_and_operators
 pub fn json_arrow_extract(value: &OwnedValue, path: &OwnedValue) -> crate::Result<OwnedValue> {
-    if let OwnedValue::Null = value {
+    if let (OwnedValue::Null, _) | (_, OwnedValue::Null) = (value, path) {
         return Ok(OwnedValue::Null);
     }
 
-    let json = get_json_value(value)?;
-    let extracted = json_extract_single(&json, path, false)?;
-
-    if let Some(val) = extracted {
-        let json = to_string(val).unwrap();
+    let json = get_json_value_cached(value)?;
+    let extracted = json_extract_single(&json, path, false)?.unwrap_or(Cow::Owned(Val::Null));
+    let json = val_to_string(extracted.as_ref())?;
 
-        Ok(OwnedValue::Text(LimboText::json(Rc::new(json))))
-    } else {
-        Ok(OwnedValue::Null)
-    }
+    Ok(OwnedValue::Text(LimboText::json(Rc::new(json))))
 }
 
-/// Implements the ->> operator. Always returns a SQL representation of the JSON subcomponent.
+/// Implements the ->> operator. Always returns a SQL representation of the JSON subcomponent,
+/// or NULL when the path doesn't resolve. Accepts the same path shorthands as the -> operator.
 /// https://sqlite.org/json1.html#the_and_operators
 pub fn json_arrow_shift_extract(
     value: &OwnedValue,
@@ -172,57 +618,111 @@ pub fn json_arrow_shift_extract(
         return Ok(OwnedValue::Null);
     }
 
-    let json = get_json_value(value)?;
-    let extracted = json_extract_single(&json, path, false)?.unwrap_or_else(|| &Val::Null);
+    let json = get_json_value_cached(value)?;
+    let extracted = json_extract_single(&json, path, false)?.unwrap_or(Cow::Owned(Val::Null));
 
-    convert_json_to_db_type(extracted, true)
+    convert_json_to_db_type(&extracted, true)
 }
 
 /// Extracts a JSON value from a JSON object or array.
 /// If there's only a single path, the return value might be either a TEXT or a database type.
+/// Unlike in SQLite, an array or object extracted with a single path from JSONB is returned as
+/// JSONB rather than as text.
+/// Without paths, the result is NULL, like in SQLite. Note that json_remove without paths
+/// returns the document instead: both match SQLite, so they shouldn't be made consistent.
 /// https://sqlite.org/json1.html#the_json_extract_function
 pub fn json_extract(value: &OwnedValue, paths: &[OwnedValue]) -> crate::Result<OwnedValue> {
+    match value {
+        OwnedValue::Null => return Ok(OwnedValue::Null),
+        OwnedValue::Blob(_) => return jsonb_extract(value, paths),
+        _ => {}
+    }
+
+    json_extract_compiled(value, &compile_json_paths(paths)?)
+}
+
+/// Extracts values like json_extract, with paths that can also contain wildcards and slices.
+pub fn json_query(value: &OwnedValue, paths: &[OwnedValue]) -> crate::Result<OwnedValue> {
     if let OwnedValue::Null = value {
         return Ok(OwnedValue::Null);
     }
+    json_extract_compiled(value, &compile_paths(paths, CompiledPath::with_extensions)?)
+}
+
+/// Same as json_extract, with the paths already compiled by `compile_json_paths`, so that
+/// constant paths don't have to be parsed again for every row.
+pub fn json_extract_compiled(
+    value: &OwnedValue,
+    paths: &[Option<CompiledPath>],
+) -> crate::Result<OwnedValue> {
+    match value {
+        OwnedValue::Null => return Ok(OwnedValue::Null),
+        OwnedValue::Blob(_) => return jsonb_extract_compiled(value, paths),
+        _ => {}
+    }
 
     if paths.is_empty() {
         return Ok(OwnedValue::Null);
-    } else if paths.len() == 1 {
-        let json = get_json_value(value)?;
-        let extracted = json_extract_single(&json, &paths[0], true)?.unwrap_or_else(|| &Val::Null);
+    } else if let [path] = paths {
+        // An array or object with the JSON subtype is already the text to return.
+        if let (Some(path), OwnedValue::Text(t)) = (path, value) {
+            if path.is_root() && t.subtype == TextSubtype::Json && t.value.starts_with(['[', '{']) {
+                return Ok(value.to_owned());
+            }
+        }
+        if let (Some(path), OwnedValue::Text(t)) = (path, value) {
+            if let [PathElement::Root(), PathElement::Key(key)] = path.elements.as_slice() {
+                if let Some(extracted) = extract_object_member(&t.value, key)? {
+                    return Ok(extracted);
+                }
+            }
+        }
+        let json = get_json_value_cached(value)?;
+        let extracted = path
+            .as_ref()
+            .and_then(|path| json_extract_single_compiled(&json, path))
+            .unwrap_or(Cow::Owned(Val::Null));
 
-        return convert_json_to_db_type(extracted, false);
+        return convert_json_to_db_type(&extracted, false);
     }
 
-    let json = get_json_value(value)?;
-    let mut result = "[".to_string();
-
-    for path in paths {
-        match path {
-            OwnedValue::Null => {
-                return Ok(OwnedValue::Null);
-            }
-            _ => {
-                let extracted =
-                    json_extract_single(&json, path, true)?.unwrap_or_else(|| &Val::Null);
-
-                if paths.len() == 1 && extracted == &Val::Null {
-                    return Ok(OwnedValue::Null);
-                }
+    let json = get_json_value_cached(value)?;
+    // The extracted values are serialized straight into the result array, without copying them.
+    let mut result = String::from("[");
 
-                result.push_str(&to_string(&extracted).unwrap());
-                result.push(',');
-            }
+    for (i, path) in paths.iter().enumerate() {
+        let Some(path) = path else {
+            return Ok(OwnedValue::Null);
+        };
+        if i > 0 {
+            result.push(',');
         }
+        let extracted = json_extract_single_compiled(&json, path);
+        write_val(&mut result, extracted.as_deref().unwrap_or(&Val::Null))?;
     }
 
-    result.pop(); // remove the final comma
     result.push(']');
-
     Ok(OwnedValue::Text(LimboText::json(Rc::new(result))))
 }
 
+/// Extracts `$.key` from JSON text by scanning the object for the member, so that only its value
+/// is parsed rather than the whole document. Returns `None` when the text is already in the parse
+/// cache, or isn't a strict JSON object (eg. JSON5), for the regular path to handle.
+fn extract_object_member(text: &Rc<String>, key: &str) -> crate::Result<Option<OwnedValue>> {
+    if is_json_cached(text) {
+        return Ok(None);
+    }
+    let Some(member) = find_object_member(text, key) else {
+        return Ok(None);
+    };
+    let extracted = match member.map(from_str::<Val>) {
+        None => Val::Null,
+        Some(Ok(json)) => json,
+        Some(Err(err)) => return Err(malformed_json(err)),
+    };
+    convert_json_to_db_type(&extracted, false).map(Some)
+}
+
 /// Returns a value with type defined by SQLite documentation:
 ///   > the SQL datatype of the result is NULL for a JSON null,
 ///   > INTEGER or REAL for a JSON numeric value,
@@ -236,8 +736,17 @@ pub fn json_extract(value: &OwnedValue, paths: &[OwnedValue]) -> crate::Result<O
 /// *all_as_db* - if true, objects and arrays will be returned as pure TEXT without the JSON subtype
 fn convert_json_to_db_type(extracted: &Val, all_as_db: bool) -> crate::Result<OwnedValue> {
     match extracted {
+        // NaN is written as null, so it's extracted as one too. Infinities are written as
+        // `9e999` and `-9e999`, which are extracted as infinities like `Infinity` is.
         Val::Null => Ok(OwnedValue::Null),
+        Val::Float(f) if f.is_nan() => Ok(OwnedValue::Null),
         Val::Float(f) => Ok(OwnedValue::Float(*f)),
+        // `-0` is kept in its source form but is extracted as the integer 0, like in SQLite.
+        Val::RawNumber(s) => match (s.parse::<i64>(), s.parse::<f64>()) {
+            (Ok(i), _) => Ok(OwnedValue::Integer(i)),
+            (_, Ok(f)) => Ok(OwnedValue::Float(f)),
+            _ => crate::bail_parse_error!("malformed JSON number: {}", s),
+        },
         Val::Integer(i) => Ok(OwnedValue::Integer(*i)),
         Val::Bool(b) => {
             if *b {
@@ -246,9 +755,9 @@ fn convert_json_to_db_type(extracted: &Val, all_as_db: bool) -> crate::Result<Ow
                 Ok(OwnedValue::Integer(0))
             }
         }
-        Val::String(s) => Ok(OwnedValue::Text(LimboText::new(Rc::new(s.clone())))),
+        Val::String(s) => Ok(OwnedValue::Text(LimboText::new(Rc::new(s.to_string())))),
         _ => {
-            let json = to_string(&extracted).unwrap();
+            let json = val_to_string(extracted)?;
             if all_as_db {
                 Ok(OwnedValue::Text(LimboText::new(Rc::new(json))))
             } else {
@@ -258,22 +767,26 @@ fn convert_json_to_db_type(extracted: &Val, all_as_db: bool) -> crate::Result<Ow
     }
 }
 
-/// Converts a DB value (`OwnedValue`) to a JSON representation (`Val`).
-/// Note that when the internal text value is a json,
-/// the returned `Val` will be an object. If the internal text value is a regular text,
-/// then a string will be returned. This is useful to track if the value came from a json
-/// function and therefore we must interpret it as json instead of raw text when working with it.
-fn convert_db_type_to_json(value: &OwnedValue) -> crate::Result<Val> {
+/// Converts a DB value (`OwnedValue`) to a JSON representation (`Val`), the way all the functions
+/// that embed SQL values into JSON (json_array, json_object, json_set, ...) do.
+/// Integers, floats and NULL become JSON numbers and null. Text with the JSON subtype came from
+/// another JSON function, so it is embedded as JSON, verbatim and without being validated again,
+/// as long as it's compact; otherwise it's parsed, so that whitespace doesn't end up in the
+/// compact document it's embedded into. Any other text becomes a JSON string. Blobs can't be
+/// embedded and yield an error.
+fn owned_value_to_val(value: &OwnedValue) -> crate::Result<Val<'_>> {
     let val = match value {
         OwnedValue::Null => Val::Null,
         OwnedValue::Float(f) => Val::Float(*f),
         OwnedValue::Integer(i) => Val::Integer(*i),
         OwnedValue::Text(t) => match t.subtype {
-            // Convert only to json if the subtype is json (if we got it from another json function)
-            TextSubtype::Json => get_json_value(value)?,
-            TextSubtype::Text => Val::String(t.value.to_string()),
+            TextSubtype::Json if is_compact(&t.value) => {
+                Val::RawJson(Cow::Borrowed(t.value.as_str()))
+            }
+            TextSubtype::Json => from_str::<Val>(&t.value).map_err(malformed_json)?,
+            TextSubtype::Text => Val::String(Cow::Borrowed(t.value.as_str())),
         },
-        OwnedValue::Blob(_) => crate::bail_constraint_error!("JSON cannot hold BLOB values"),
+        OwnedValue::Blob(_) => return Err(crate::LimboError::BlobInJson),
         unsupported_value => crate::bail_constraint_error!(
             "JSON cannot hold this type of value: {unsupported_value:?}"
         ),
@@ -281,11 +794,27 @@ fn convert_db_type_to_json(value: &OwnedValue) -> crate::Result<Val> {
     Ok(val)
 }
 
+/// Returns the JSON type name of the value at the given path, or of the whole document without
+/// a path. A well-formed path that doesn't resolve gives NULL, while a path that can't be parsed,
+/// eg. one that doesn't start with '$', is an error.
+/// https://sqlite.org/json1.html#jtype
 pub fn json_type(value: &OwnedValue, path: Option<&OwnedValue>) -> crate::Result<OwnedValue> {
     if let OwnedValue::Null = value {
         return Ok(OwnedValue::Null);
     }
 
+    // Without a path only the type of the top-level value matters, which valid text tells by its
+    // first token, so the text is only validated rather than parsed into values.
+    if let (OwnedValue::Text(t), None) = (value, path) {
+        if t.subtype == TextSubtype::Json || validate(&t.value).is_ok() {
+            if let Some(type_name) = top_level_type(&t.value) {
+                return Ok(OwnedValue::Text(LimboText::new(Rc::new(
+                    type_name.to_string(),
+                ))));
+            }
+        }
+    }
+
     let json = get_json_value(value)?;
 
     let json = if let Some(path) = path {
@@ -294,486 +823,4946 @@ pub fn json_type(value: &OwnedValue, path: Option<&OwnedValue>) -> crate::Result
             None => return Ok(OwnedValue::Null),
         }
     } else {
-        &json
-    };
-
-    let val = match json {
-        Val::Null => "null",
-        Val::Bool(v) => {
-            if *v {
-                "true"
-            } else {
-                "false"
-            }
-        }
-        Val::Integer(_) => "integer",
-        Val::Float(_) => "real",
-        Val::String(_) => "text",
-        Val::Array(_) => "array",
-        Val::Object(_) => "object",
+        Cow::Borrowed(&json)
     };
 
-    Ok(OwnedValue::Text(LimboText::json(Rc::new(val.to_string()))))
+    let mut type_name = TypeName("null");
+    json.walk(&mut type_name);
+    Ok(OwnedValue::Text(LimboText::new(Rc::new(
+        type_name.0.to_string(),
+    ))))
 }
 
-/// Returns the value at the given JSON path. If the path does not exist, it returns None.
-/// If the path is an invalid path, returns an error.
-///
-/// *strict* - if false, we will try to resolve the path even if it does not start with "$"
-///   in a way that's compatible with the `->` and `->>` operators. See examples in the docs:
-///   https://sqlite.org/json1.html#the_and_operators
-fn json_extract_single<'a>(
-    json: &'a Val,
-    path: &OwnedValue,
-    strict: bool,
-) -> crate::Result<Option<&'a Val>> {
-    let json_path = if strict {
-        match path {
-            OwnedValue::Text(t) => json_path(t.value.as_str())?,
-            OwnedValue::Null => return Ok(None),
-            _ => crate::bail_constraint_error!("JSON path error near: {:?}", path.to_string()),
-        }
-    } else {
-        match path {
-            OwnedValue::Text(t) => {
-                if t.value.starts_with("$") {
-                    json_path(t.value.as_str())?
-                } else {
-                    JsonPath {
-                        elements: vec![PathElement::Root(), PathElement::Key(t.value.to_string())],
-                    }
-                }
-            }
-            OwnedValue::Null => return Ok(None),
-            OwnedValue::Integer(i) => JsonPath {
-                elements: vec![PathElement::Root(), PathElement::ArrayLocator(*i as i32)],
-            },
-            OwnedValue::Float(f) => JsonPath {
-                elements: vec![PathElement::Root(), PathElement::Key(f.to_string())],
-            },
-            _ => crate::bail_constraint_error!("JSON path error near: {:?}", path.to_string()),
-        }
-    };
-
-    let mut current_element = &Val::Null;
+/// Takes the type name of the value it visits first, and stops there rather than walking the
+/// members or elements.
+struct TypeName(&'static str);
 
-    for element in json_path.elements.iter() {
-        match element {
-            PathElement::Root() => {
-                current_element = json;
-            }
-            PathElement::Key(key) => {
-                let key = key.as_str();
+impl JsonVisitor for TypeName {
+    fn visit_scalar(&mut self, value: &Val) -> VisitControl {
+        self.0 = value.type_name();
+        VisitControl::Stop
+    }
 
-                match current_element {
-                    Val::Object(map) => {
-                        if let Some(value) = map.get(key) {
-                            current_element = value;
-                        } else {
-                            return Ok(None);
-                        }
-                    }
-                    _ => return Ok(None),
-                }
-            }
-            PathElement::ArrayLocator(idx) => match current_element {
-                Val::Array(array) => {
-                    let mut idx = *idx;
+    fn enter_object(&mut self, _members: &IndexMap<String, Val>) -> VisitControl {
+        self.0 = "object";
+        VisitControl::Stop
+    }
 
-                    if idx < 0 {
-                        idx += array.len() as i32;
-                    }
+    fn enter_array(&mut self, _items: &[Val]) -> VisitControl {
+        self.0 = "array";
+        VisitControl::Stop
+    }
+}
 
-                    if idx < array.len() as i32 {
-                        current_element = &array[idx as usize];
-                    } else {
-                        return Ok(None);
-                    }
-                }
-                _ => return Ok(None),
-            },
-        }
+/// Same as json_extract, but for JSONB blobs. The paths are resolved by navigating the binary
+/// encoding, so only the extracted values get decoded instead of the whole document, and with a
+/// single path, an extracted array or object isn't decoded at all.
+pub fn jsonb_extract(value: &OwnedValue, paths: &[OwnedValue]) -> crate::Result<OwnedValue> {
+    if !matches!(value, OwnedValue::Blob(_)) {
+        return json_extract(value, paths);
     }
 
-    Ok(Some(current_element))
+    jsonb_extract_compiled(value, &compile_json_paths(paths)?)
 }
 
-pub fn json_error_position(json: &OwnedValue) -> crate::Result<OwnedValue> {
-    match json {
-        OwnedValue::Text(t) => match from_str::<Val>(&t.value) {
-            Ok(_) => Ok(OwnedValue::Integer(0)),
-            Err(JsonError::Message { location, .. }) => {
-                if let Some(loc) = location {
-                    Ok(OwnedValue::Integer(loc.column as i64))
-                } else {
-                    Err(crate::error::LimboError::InternalError(
-                        "failed to determine json error position".into(),
-                    ))
+fn jsonb_extract_compiled(
+    value: &OwnedValue,
+    paths: &[Option<CompiledPath>],
+) -> crate::Result<OwnedValue> {
+    let OwnedValue::Blob(blob) = value else {
+        return json_extract_compiled(value, paths);
+    };
+
+    if paths.is_empty() {
+        return Ok(OwnedValue::Null);
+    } else if let [path] = paths {
+        let Some((target, rest)) = path
+            .as_ref()
+            .and_then(|path| jsonb_navigate(blob, &path.elements))
+        else {
+            return Ok(OwnedValue::Null);
+        };
+
+        // Arrays and objects are returned as JSONB, so that they can be passed on to other
+        // functions without being written out as text and parsed again. A subdocument that the
+        // path leads to is copied out as it's encoded. Blobs holding JSON text still give text.
+        let as_jsonb = !is_json_text_blob(blob);
+        if as_jsonb
+            && rest.is_empty()
+            && (jsonb::array_length(&target).is_some() || jsonb::is_object(&target))
+        {
+            let subdocument = match target {
+                Cow::Borrowed(_) => blob.clone(),
+                Cow::Owned(target) => Rc::new(target),
+            };
+            return Ok(OwnedValue::Blob(subdocument));
+        }
+
+        let extracted = jsonb_decode_at(&target, rest)?.unwrap_or(Val::Null);
+        return match extracted {
+            Val::Array(_) | Val::Object(_) if as_jsonb => Ok(OwnedValue::Blob(Rc::new(
+                val_to_jsonb(&extracted)?.to_vec(),
+            ))),
+            _ => convert_json_to_db_type(&extracted, false),
+        };
+    }
+
+    let mut result = String::from("[");
+
+    for (i, path) in paths.iter().enumerate() {
+        let Some(path) = path else {
+            return Ok(OwnedValue::Null);
+        };
+        if i > 0 {
+            result.push(',');
+        }
+        let extracted = jsonb_extract_single(blob, path)?.unwrap_or(Val::Null);
+        write_val(&mut result, &extracted)?;
+    }
+
+    result.push(']');
+    Ok(OwnedValue::Text(LimboText::json(Rc::new(result))))
+}
+
+/// Returns the value at the given JSON path of a JSONB blob, or None if the path doesn't exist.
+fn jsonb_extract_single(blob: &[u8], path: &CompiledPath) -> crate::Result<Option<Val<'static>>> {
+    let Some((current, rest)) = jsonb_navigate(blob, &path.elements) else {
+        return Ok(None);
+    };
+    jsonb_decode_at(&current, rest)
+}
+
+/// Decodes a value reached by `jsonb_navigate` and resolves the `rest` of the path on it.
+fn jsonb_decode_at(current: &[u8], rest: &[PathElement]) -> crate::Result<Option<Val<'static>>> {
+    // Only the extracted value is decoded.
+    let json = match jsonb::from_slice(current) {
+        Ok(json) => jsonb_to_val(&json),
+        Err(_) => crate::bail_parse_error!("malformed JSON"),
+    };
+    if rest.is_empty() {
+        Ok(Some(json))
+    } else {
+        // Wildcards and slices build a new array, so the rest of the path is resolved on the
+        // decoded value.
+        Ok(resolve_path(&json, rest).map(Cow::into_owned))
+    }
+}
+
+/// Follows `elements` through a JSONB blob without decoding it, up to the end of the path or
+/// to its first wildcard or slice. Returns the encoded value reached along with the rest of the
+/// path, which starts at the wildcard or slice if there is one, or None if the path doesn't exist.
+fn jsonb_navigate<'b, 'p>(
+    blob: &'b [u8],
+    elements: &'p [PathElement],
+) -> Option<(Cow<'b, [u8]>, &'p [PathElement])> {
+    let mut current = Cow::Borrowed(blob);
+
+    for (i, element) in elements.iter().enumerate() {
+        let next = match element {
+            PathElement::Root() => {
+                current = Cow::Borrowed(blob);
+                continue;
+            }
+            PathElement::Wildcard | PathElement::Slice { .. } => {
+                return Some((current, &elements[i..]))
+            }
+            PathElement::Key(key) => jsonb::get_by_name(&current, key, false),
+            PathElement::ArrayLocator(_) | PathElement::ArrayLocatorFromEnd(_) => {
+                jsonb::array_length(&current)
+                    .and_then(|len| resolve_array_index(element, len))
+                    .and_then(|idx| jsonb::get_by_index(&current, idx))
+            }
+        };
+        current = Cow::Owned(next?);
+    }
+
+    Some((current, &[]))
+}
+
+/// A JSON path argument parsed once, so that it can be applied to many documents, eg. a constant
+/// path of json_extract over all the rows of a table.
+#[derive(Clone, Debug)]
+pub struct CompiledPath {
+    elements: Vec<PathElement>,
+}
+
+/// Writes the path in its canonical form. Paths compiled from a bare key or index, as given to
+/// the `->` and `->>` operators, are written as the full path they resolve to.
+impl std::fmt::Display for CompiledPath {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.elements.iter().try_for_each(|element| element.fmt(f))
+    }
+}
+
+impl CompiledPath {
+    /// Whether the path is just '$', which addresses the whole document.
+    fn is_root(&self) -> bool {
+        matches!(self.elements.as_slice(), [PathElement::Root()])
+    }
+
+    /// Parses a path argument. Returns None for a NULL path, which never resolves, and an error
+    /// for an invalid path.
+    ///
+    /// *strict* - if false, we will try to resolve the path even if it does not start with "$"
+    ///   in a way that's compatible with the `->` and `->>` operators. See examples in the docs:
+    ///   https://sqlite.org/json1.html#the_and_operators
+    pub fn new(path: &OwnedValue, strict: bool) -> crate::Result<Option<Self>> {
+        let json_path = if strict {
+            match path {
+                OwnedValue::Text(t) => json_path(t.value.as_str())?,
+                OwnedValue::Null => return Ok(None),
+                _ => crate::bail_constraint_error!("JSON path error near: {:?}", path.to_string()),
+            }
+        } else {
+            match path {
+                OwnedValue::Text(t) => {
+                    if t.value.starts_with("$") {
+                        json_path(t.value.as_str())?
+                    } else {
+                        JsonPath {
+                            elements: vec![
+                                PathElement::Root(),
+                                PathElement::Key(t.value.to_string()),
+                            ],
+                        }
+                    }
                 }
+                OwnedValue::Null => return Ok(None),
+                OwnedValue::Integer(i) => JsonPath {
+                    elements: vec![PathElement::Root(), PathElement::ArrayLocator(*i)],
+                },
+                OwnedValue::Float(f) => JsonPath {
+                    elements: vec![PathElement::Root(), PathElement::Key(f.to_string())],
+                },
+                _ => crate::bail_constraint_error!("JSON path error near: {:?}", path.to_string()),
             }
+        };
+
+        Ok(Some(Self {
+            elements: json_path.elements,
+        }))
+    }
+
+    /// Parses a path argument of json_query, which can also contain wildcards and slices.
+    fn with_extensions(path: &OwnedValue) -> crate::Result<Option<Self>> {
+        match path {
+            OwnedValue::Text(t) => Ok(Some(Self {
+                elements: json_path_with_extensions(t.value.as_str())?.elements,
+            })),
+            OwnedValue::Null => Ok(None),
+            _ => crate::bail_constraint_error!("JSON path error near: {:?}", path.to_string()),
+        }
+    }
+}
+
+/// Compiles the path arguments of json_extract. A NULL path makes the result NULL, so the paths
+/// after it are not parsed.
+pub fn compile_json_paths(paths: &[OwnedValue]) -> crate::Result<Vec<Option<CompiledPath>>> {
+    compile_paths(paths, |path| CompiledPath::new(path, true))
+}
+
+/// Compiles path arguments with `compile`, up to the first NULL path.
+fn compile_paths(
+    paths: &[OwnedValue],
+    compile: impl Fn(&OwnedValue) -> crate::Result<Option<CompiledPath>>,
+) -> crate::Result<Vec<Option<CompiledPath>>> {
+    let mut compiled = Vec::with_capacity(paths.len());
+    for path in paths {
+        let path = compile(path)?;
+        let is_null = path.is_none();
+        compiled.push(path);
+        if is_null {
+            break;
+        }
+    }
+    Ok(compiled)
+}
+
+/// Returns the value at the given JSON path. If the path does not exist, it returns None.
+/// If the path is an invalid path, returns an error. See `CompiledPath::new` for *strict*.
+fn json_extract_single<'a>(
+    json: &'a Val<'a>,
+    path: &OwnedValue,
+    strict: bool,
+) -> crate::Result<Option<Cow<'a, Val<'a>>>> {
+    Ok(CompiledPath::new(path, strict)?.and_then(|path| json_extract_single_compiled(json, &path)))
+}
+
+/// Returns the value at the given compiled path, or None if the path does not exist.
+pub fn json_extract_single_compiled<'a>(
+    json: &'a Val<'a>,
+    path: &CompiledPath,
+) -> Option<Cow<'a, Val<'a>>> {
+    resolve_path(json, &path.elements)
+}
+
+/// Walks `elements` down from `json`. The result is borrowed from `json`, unless the path contains
+/// a wildcard or a slice, which collect children of the current object or array into a new array.
+fn resolve_path<'a, 'v>(json: &'a Val<'v>, elements: &[PathElement]) -> Option<Cow<'a, Val<'v>>> {
+    let mut current = Cow::Borrowed(json);
+
+    for element in elements {
+        current = match element {
+            PathElement::Root() => Cow::Borrowed(json),
+            PathElement::Wildcard => match current.as_ref() {
+                Val::Array(array) => Cow::Owned(Val::Array(array.clone())),
+                Val::Object(map) => Cow::Owned(Val::Array(map.values().cloned().collect())),
+                _ => return None,
+            },
+            PathElement::Slice { start, end } => match current.as_ref() {
+                Val::Array(array) => {
+                    let range = resolve_slice(*start, *end, array.len());
+                    Cow::Owned(Val::Array(array[range].to_vec()))
+                }
+                _ => return None,
+            },
+            _ => match current {
+                Cow::Borrowed(value) => Cow::Borrowed(resolve_child(value, element)?),
+                Cow::Owned(value) => Cow::Owned(resolve_child(&value, element)?.clone()),
+            },
+        };
+    }
+
+    Some(current)
+}
+
+/// Returns the child of `value` that a key or array locator points to.
+fn resolve_child<'a, 'v>(value: &'a Val<'v>, element: &PathElement) -> Option<&'a Val<'v>> {
+    match (element, value) {
+        (PathElement::Key(key), Val::Object(map)) => map.get(key),
+        (PathElement::ArrayLocator(_) | PathElement::ArrayLocatorFromEnd(_), Val::Array(array)) => {
+            array.get(resolve_array_index(element, array.len())?)
+        }
+        _ => None,
+    }
+}
+
+/// Inserts values at the given paths, leaving values that already exist untouched.
+/// https://sqlite.org/json1.html#jins
+pub fn json_insert(json: &OwnedValue, args: &[OwnedValue]) -> crate::Result<OwnedValue> {
+    json_edit(json, args, JsonEdit::Insert, JsonRepr::of(json))
+}
+
+/// Overwrites values at the given paths, ignoring paths that don't resolve yet.
+/// https://sqlite.org/json1.html#jrepl
+pub fn json_replace(json: &OwnedValue, args: &[OwnedValue]) -> crate::Result<OwnedValue> {
+    json_edit(json, args, JsonEdit::Replace, JsonRepr::of(json))
+}
+
+/// Sets values at the given paths, creating them when missing and overwriting them otherwise.
+/// https://sqlite.org/json1.html#jset
+pub fn json_set(json: &OwnedValue, args: &[OwnedValue]) -> crate::Result<OwnedValue> {
+    json_edit(json, args, JsonEdit::Set, JsonRepr::of(json))
+}
+
+/// Same as json_insert, but always returns JSONB.
+/// https://sqlite.org/json1.html#jinsb
+pub fn jsonb_insert(json: &OwnedValue, args: &[OwnedValue]) -> crate::Result<OwnedValue> {
+    json_edit(json, args, JsonEdit::Insert, JsonRepr::Jsonb)
+}
+
+/// Same as json_replace, but always returns JSONB.
+/// https://sqlite.org/json1.html#jreplb
+pub fn jsonb_replace(json: &OwnedValue, args: &[OwnedValue]) -> crate::Result<OwnedValue> {
+    json_edit(json, args, JsonEdit::Replace, JsonRepr::Jsonb)
+}
+
+/// Same as json_set, but always returns JSONB.
+/// https://sqlite.org/json1.html#jsetb
+pub fn jsonb_set(json: &OwnedValue, args: &[OwnedValue]) -> crate::Result<OwnedValue> {
+    json_edit(json, args, JsonEdit::Set, JsonRepr::Jsonb)
+}
+
+/// Removes the values at the given paths. The paths are applied from left to right, each to the
+/// document left by the previous ones, so array indices of later paths refer to the elements
+/// after the shift, eg. removing `$[1]` and then `$[2]` from `["a","b","c","d"]` yields
+/// `["a","c"]`. Paths that don't resolve, including ones under an already removed value, are
+/// ignored. Passing a NULL path yields NULL, while removing the root is an error (SQLite yields
+/// NULL instead). Without paths, nothing is removed and the document is returned minified, like
+/// json() returns it, unlike json_extract, which returns NULL without paths, as SQLite does. The
+/// result is JSONB when the input is, text otherwise.
+/// https://sqlite.org/json1.html#jrm
+pub fn json_remove(json: &OwnedValue, paths: &[OwnedValue]) -> crate::Result<OwnedValue> {
+    remove_paths(json, paths, JsonRepr::of(json))
+}
+
+/// Same as json_remove, but always returns JSONB.
+/// https://sqlite.org/json1.html#jrmb
+pub fn jsonb_remove(json: &OwnedValue, paths: &[OwnedValue]) -> crate::Result<OwnedValue> {
+    remove_paths(json, paths, JsonRepr::Jsonb)
+}
+
+/// Removes `paths` from `json` like json_remove, returning the result as `repr`.
+fn remove_paths(
+    json: &OwnedValue,
+    paths: &[OwnedValue],
+    repr: JsonRepr,
+) -> crate::Result<OwnedValue> {
+    if let OwnedValue::Null = json {
+        return Ok(OwnedValue::Null);
+    }
+
+    let mut json = get_json_value(json)?;
+
+    for path in paths {
+        if let OwnedValue::Null = path {
+            return Ok(OwnedValue::Null);
+        }
+        let path = edit_path(path)?;
+
+        remove_json_path(&mut json, &path)?;
+    }
+
+    repr.encode(&json)
+}
+
+/// Applies `patch` to `target` following the RFC 7386 MergePatch algorithm.
+/// https://sqlite.org/json1.html#jpatch
+pub fn json_patch(target: &OwnedValue, patch: &OwnedValue) -> crate::Result<OwnedValue> {
+    if let (OwnedValue::Null, _) | (_, OwnedValue::Null) = (target, patch) {
+        return Ok(OwnedValue::Null);
+    }
+
+    let mut target = get_json_value(target)?;
+    let patch = get_json_value(patch)?;
+
+    merge_patch(&mut target, patch);
+
+    let json = val_to_string(&target)?;
+    Ok(OwnedValue::Text(LimboText::json(Rc::new(json))))
+}
+
+/// Shallow-merges two objects or concatenates two arrays; anything else is an error.
+pub fn json_concat(first: &OwnedValue, second: &OwnedValue) -> crate::Result<OwnedValue> {
+    if let (OwnedValue::Null, _) | (_, OwnedValue::Null) = (first, second) {
+        return Ok(OwnedValue::Null);
+    }
+
+    let first = get_json_value(first)?;
+    let second = get_json_value(second)?;
+    let json = match (first, second) {
+        (Val::Object(mut members), Val::Object(more_members)) => {
+            members.extend(more_members);
+            Val::Object(members)
+        }
+        (Val::Array(mut items), Val::Array(more_items)) => {
+            items.extend(more_items);
+            Val::Array(items)
+        }
+        (first, second) => crate::bail_constraint_error!(
+            "json_concat requires two objects or two arrays, got {} and {}",
+            first.type_name(),
+            second.type_name()
+        ),
+    };
+
+    let json = val_to_string(&json)?;
+    Ok(OwnedValue::Text(LimboText::json(Rc::new(json))))
+}
+
+/// Returns 1 if `doc` contains `candidate`, comparing arrays as sets, or 0 otherwise.
+pub fn json_contains(doc: &OwnedValue, candidate: &OwnedValue) -> crate::Result<OwnedValue> {
+    if let (OwnedValue::Null, _) | (_, OwnedValue::Null) = (doc, candidate) {
+        return Ok(OwnedValue::Null);
+    }
+
+    let doc = get_json_value(doc)?;
+    let candidate = get_json_value(candidate)?;
+
+    Ok(OwnedValue::Integer(contains(&doc, &candidate) as i64))
+}
+
+/// Returns 1 if the two values are equal regardless of member order, or 0 otherwise.
+pub fn json_equal(a: &OwnedValue, b: &OwnedValue) -> crate::Result<OwnedValue> {
+    if let (OwnedValue::Null, _) | (_, OwnedValue::Null) = (a, b) {
+        return Ok(OwnedValue::Null);
+    }
+
+    let a = get_json_value(a)?;
+    let b = get_json_value(b)?;
+
+    Ok(OwnedValue::Integer(vals_equal(&a, &b) as i64))
+}
+
+/// Whether `doc` contains `candidate`, see json_contains.
+fn contains(doc: &Val, candidate: &Val) -> bool {
+    match (doc, candidate) {
+        (Val::Object(doc), Val::Object(candidate)) => candidate
+            .iter()
+            .all(|(key, value)| doc.get(key).is_some_and(|member| contains(member, value))),
+        (Val::Array(doc), Val::Array(candidate)) => candidate
+            .iter()
+            .all(|value| doc.iter().any(|item| contains(item, value))),
+        (doc, candidate) => vals_equal(doc, candidate),
+    }
+}
+
+/// Merges `patch` into `target` in place. Object patches are merged member by member, with
+/// `null` members removing the key from the target; any other patch replaces the target.
+fn merge_patch<'a>(target: &mut Val<'a>, patch: Val<'a>) {
+    let Val::Object(patch) = patch else {
+        *target = patch;
+        return;
+    };
+
+    if !matches!(target, Val::Object(_)) {
+        *target = Val::Object(IndexMap::new());
+    }
+    let Val::Object(map) = target else {
+        unreachable!()
+    };
+
+    for (key, value) in patch {
+        if let Val::Null = value {
+            map.shift_remove(&key);
+        } else {
+            merge_patch(map.entry(key).or_insert(Val::Null), value);
+        }
+    }
+}
+
+/// Applies the operations of a JSON Patch (RFC 6902) to `target`, in order.
+/// A failed `test` operation returns NULL, or an error when `strict` is true.
+pub fn json_patch_ops(
+    target: &OwnedValue,
+    ops: &OwnedValue,
+    strict: Option<&OwnedValue>,
+) -> crate::Result<OwnedValue> {
+    let strict = match strict {
+        None | Some(OwnedValue::Null) => false,
+        Some(OwnedValue::Integer(i)) => *i != 0,
+        Some(OwnedValue::Float(f)) => *f != 0.0,
+        Some(_) => {
+            crate::bail_constraint_error!("STRICT parameter to json_patch_ops() must be an integer")
+        }
+    };
+    if let (OwnedValue::Null, _) | (_, OwnedValue::Null) = (target, ops) {
+        return Ok(OwnedValue::Null);
+    }
+
+    let repr = JsonRepr::of(target);
+    let mut json = get_json_value(target)?;
+    let Val::Array(ops) = get_json_value(ops)? else {
+        crate::bail_constraint_error!("JSON patch must be an array of operations");
+    };
+
+    for op in &ops {
+        if !apply_patch_op(&mut json, op)? {
+            if strict {
+                crate::bail_constraint_error!("JSON patch test failed: {}", val_to_string(op)?);
+            }
+            return Ok(OwnedValue::Null);
+        }
+    }
+
+    repr.encode(&json)
+}
+
+/// Applies a single JSON Patch operation to `json`. Returns false for a `test` operation that
+/// doesn't match.
+fn apply_patch_op<'a>(json: &mut Val<'a>, op: &Val) -> crate::Result<bool> {
+    let Val::Object(op) = op else {
+        crate::bail_constraint_error!("JSON patch operation must be an object");
+    };
+    let value = || match op.get("value") {
+        Some(value) => Ok(value.clone().into_owned()),
+        None => crate::bail_constraint_error!("JSON patch operation is missing \"value\""),
+    };
+
+    match patch_op_member(op, "op")? {
+        "add" => {
+            let path = patch_op_path(json, op, "path", false)?;
+            add_json_path(json, &path, value()?)?;
+        }
+        "remove" => {
+            let path = patch_op_path(json, op, "path", true)?;
+            remove_json_path(json, &path)?;
+        }
+        "replace" => {
+            let path = patch_op_path(json, op, "path", true)?;
+            edit_json_path(json, &path, value()?, JsonEdit::Replace)?;
+        }
+        "move" => {
+            let (from, to) = (patch_op_member(op, "from")?, patch_op_member(op, "path")?);
+            if from == to {
+                patch_op_path(json, op, "from", true)?;
+                return Ok(true);
+            }
+            if to.starts_with(from) && to[from.len()..].starts_with('/') {
+                crate::bail_constraint_error!(
+                    "JSON patch error: cannot move {:?} into its own child {:?}",
+                    from,
+                    to
+                );
+            }
+            let from = patch_op_path(json, op, "from", true)?;
+            let value = resolve_path(json, &from.elements).map(Cow::into_owned);
+            remove_json_path(json, &from)?;
+            let path = patch_op_path(json, op, "path", false)?;
+            add_json_path(json, &path, value.unwrap_or(Val::Null))?;
+        }
+        "copy" => {
+            let from = patch_op_path(json, op, "from", true)?;
+            let value = resolve_path(json, &from.elements).map(Cow::into_owned);
+            let path = patch_op_path(json, op, "path", false)?;
+            add_json_path(json, &path, value.unwrap_or(Val::Null))?;
+        }
+        "test" => {
+            let tokens = json_pointer(patch_op_member(op, "path")?)?;
+            let expected = value()?;
+            let actual = pointer_path(json, &tokens)
+                .and_then(|path| resolve_path(json, &path.elements).map(Cow::into_owned));
+            return Ok(actual.is_some_and(|actual| vals_equal(&actual, &expected)));
+        }
+        name => crate::bail_constraint_error!("JSON patch error: unknown operation {:?}", name),
+    }
+
+    Ok(true)
+}
+
+/// Returns a string member of a JSON Patch operation, eg. its `op` or `path`.
+fn patch_op_member<'o>(op: &'o IndexMap<String, Val>, name: &str) -> crate::Result<&'o str> {
+    match op.get(name) {
+        Some(Val::String(s)) => Ok(s),
+        Some(_) => crate::bail_constraint_error!("JSON patch error: {:?} must be a string", name),
+        None => crate::bail_constraint_error!("JSON patch operation is missing {:?}", name),
+    }
+}
+
+/// Resolves the JSON Pointer in the `name` member of a JSON Patch operation into a path. The
+/// parent of what it addresses must exist, and so must the addressed value itself when
+/// `must_exist` is true.
+fn patch_op_path(
+    json: &Val,
+    op: &IndexMap<String, Val>,
+    name: &str,
+    must_exist: bool,
+) -> crate::Result<JsonPath> {
+    let pointer = patch_op_member(op, name)?;
+    match pointer_path(json, &json_pointer(pointer)?) {
+        Some(path) if !must_exist || resolve_path(json, &path.elements).is_some() => Ok(path),
+        _ => crate::bail_constraint_error!("JSON patch error: no such path: {:?}", pointer),
+    }
+}
+
+/// Resolves the reference tokens of a JSON Pointer against `json` into a path. A token is an
+/// array index under an array, `-` being the slot after the last element, and a key under an
+/// object. Returns `None` when a token before the last doesn't resolve, or when the last one
+/// isn't a valid index for its parent array.
+fn pointer_path(json: &Val, tokens: &[String]) -> Option<JsonPath> {
+    let mut elements = vec![PathElement::Root()];
+    let mut current = json;
+
+    for (i, token) in tokens.iter().enumerate() {
+        let element = match current {
+            Val::Object(_) => PathElement::Key(token.clone()),
+            Val::Array(array) => {
+                let idx = match token.as_str() {
+                    "-" => array.len(),
+                    "0" => 0,
+                    token if token.starts_with('0') => return None,
+                    token if token.bytes().all(|b| b.is_ascii_digit()) => token.parse().ok()?,
+                    _ => return None,
+                };
+                if idx > array.len() {
+                    return None;
+                }
+                PathElement::ArrayLocator(i64::try_from(idx).ok()?)
+            }
+            _ => return None,
+        };
+        if i + 1 < tokens.len() {
+            current = resolve_child(current, &element)?;
+        }
+        elements.push(element);
+    }
+
+    Some(JsonPath { elements })
+}
+
+/// Adds `value` at `path` the way the JSON Patch `add` operation does: into an array, the value
+/// is inserted before the element at the index instead of replacing it.
+fn add_json_path<'a>(json: &mut Val<'a>, path: &JsonPath, value: Val<'a>) -> crate::Result<()> {
+    if let Some((PathElement::ArrayLocator(idx), parents)) = path.elements.split_last() {
+        if let Some(Val::Array(mut array)) = resolve_path(json, parents).map(Cow::into_owned) {
+            array.insert(*idx as usize, value);
+            let parent = JsonPath {
+                elements: parents.to_vec(),
+            };
+            return edit_json_path(json, &parent, Val::Array(array), JsonEdit::Replace);
+        }
+    }
+    edit_json_path(json, path, value, JsonEdit::Set)
+}
+
+/// Whether two values are equal the way the JSON Patch `test` operation compares them: numbers
+/// by value, whatever their representation, and objects regardless of the order of members.
+fn vals_equal(a: &Val, b: &Val) -> bool {
+    match (a, b) {
+        (Val::Null, Val::Null) => true,
+        (Val::Bool(a), Val::Bool(b)) => a == b,
+        (Val::Integer(a), Val::Integer(b)) => a == b,
+        (Val::String(a), Val::String(b)) => a == b,
+        (Val::Array(a), Val::Array(b)) => {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| vals_equal(a, b))
+        }
+        (Val::Object(a), Val::Object(b)) => {
+            a.len() == b.len()
+                && a.iter()
+                    .all(|(key, a)| b.get(key).is_some_and(|b| vals_equal(a, b)))
+        }
+        (a, b) => match (a.as_f64(), b.as_f64()) {
+            (Some(a), Some(b)) => a == b,
+            _ => false,
         },
-        OwnedValue::Blob(b) => match jsonb::from_slice(b) {
-            Ok(_) => Ok(OwnedValue::Integer(0)),
-            Err(JsonbError::Syntax(_, pos)) => Ok(OwnedValue::Integer(pos as i64)),
-            _ => Err(crate::error::LimboError::InternalError(
-                "failed to determine json error position".into(),
-            )),
-        },
-        OwnedValue::Null => Ok(OwnedValue::Null),
-        _ => Ok(OwnedValue::Integer(0)),
     }
-}
+}
+
+/// Describes what happens to the element a path points at when editing a document.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum JsonEdit {
+    /// Adds values only where the path doesn't resolve yet (json_insert).
+    Insert,
+    /// Overwrites values only where the path already resolves (json_replace).
+    Replace,
+    /// Creates or overwrites values regardless of whether the path resolves (json_set).
+    Set,
+}
+
+impl JsonEdit {
+    /// Whether the edit adds new members/elements, including missing intermediate containers.
+    fn creates(self) -> bool {
+        matches!(self, JsonEdit::Insert | JsonEdit::Set)
+    }
+
+    /// Whether the edit overwrites elements that already exist.
+    fn overwrites(self) -> bool {
+        matches!(self, JsonEdit::Replace | JsonEdit::Set)
+    }
+}
+
+/// How a document is represented as a SQL value. Text is the default, like for the json_*
+/// functions, with the jsonb_* functions returning JSONB.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum JsonRepr {
+    #[default]
+    Text,
+    Jsonb,
+}
+
+impl JsonRepr {
+    /// The representation of an input document, which the functions that modify documents keep
+    /// for their result. JSON text stored in a blob counts as text.
+    pub fn of(value: &OwnedValue) -> Self {
+        match value {
+            OwnedValue::Blob(b) if is_jsonb(b) => Self::Jsonb,
+            _ => Self::Text,
+        }
+    }
+
+    /// Returns `json` in this representation.
+    fn encode(self, json: &Val) -> crate::Result<OwnedValue> {
+        match self {
+            Self::Text => {
+                let json = val_to_string(json)?;
+                Ok(OwnedValue::Text(LimboText::json(Rc::new(json))))
+            }
+            Self::Jsonb => Ok(OwnedValue::Blob(Rc::new(val_to_jsonb(json)?.to_vec()))),
+        }
+    }
+}
+
+/// Applies `edit` to `json` for each (path, value) pair in `args`, from left to right, and
+/// returns the result as `repr`. Like in SQLite, pairs with a NULL path are skipped.
+fn json_edit(
+    json: &OwnedValue,
+    args: &[OwnedValue],
+    edit: JsonEdit,
+    repr: JsonRepr,
+) -> crate::Result<OwnedValue> {
+    if let OwnedValue::Null = json {
+        return Ok(OwnedValue::Null);
+    }
+
+    let mut json = get_json_value(json)?;
+
+    for pair in args.chunks_exact(2) {
+        if let OwnedValue::Null = pair[0] {
+            continue;
+        }
+        let path = edit_path(&pair[0])?;
+        let value = owned_value_to_val(&pair[1])?;
+
+        edit_json_path(&mut json, &path, value, edit)?;
+    }
+
+    repr.encode(&json)
+}
+
+/// Parses a path argument of the functions that edit documents.
+fn edit_path(path: &OwnedValue) -> crate::Result<JsonPath> {
+    match path {
+        OwnedValue::Text(t) => json_path(t.value.as_str()),
+        path => crate::bail_constraint_error!("JSON path error near: {:?}", path.to_string()),
+    }
+}
+
+/// Applies `edit` with `value` to the element of `json` addressed by `path`.
+/// When the edit creates values, missing intermediate objects and arrays are created on the way,
+/// as long as the rest of the path can be created in them too. Otherwise, or when the path runs
+/// into a value of the wrong type, the document is left as is.
+/// Like in SQLite, an array index equal to the array length addresses the slot after the last
+/// element, so creating edits append to the array.
+fn edit_json_path<'a>(
+    json: &mut Val<'a>,
+    path: &JsonPath,
+    value: Val<'a>,
+    edit: JsonEdit,
+) -> crate::Result<()> {
+    if path.is_root() {
+        if edit.overwrites() {
+            *json = value;
+        }
+        return Ok(());
+    }
+    let Some((target, parents)) = path.elements.split_last() else {
+        return Ok(());
+    };
+
+    let mut current = json;
+    for (i, element) in parents.iter().enumerate() {
+        let next_element = &path.elements[i + 1];
+
+        expand_raw_json(current)?;
+        current = match (element, current) {
+            (PathElement::Root(), current) => current,
+            (PathElement::Key(key), Val::Object(map)) => {
+                if !map.contains_key(key) {
+                    if !edit.creates() || !creatable_in_empty(&path.elements[i + 1..]) {
+                        return Ok(());
+                    }
+                    map.insert(key.clone(), empty_container_for(next_element));
+                }
+                let Some(child) = map.get_mut(key) else {
+                    return Ok(());
+                };
+                child
+            }
+            (
+                element @ (PathElement::ArrayLocator(_) | PathElement::ArrayLocatorFromEnd(_)),
+                Val::Array(array),
+            ) => {
+                let Some(idx) = resolve_array_index(element, array.len()) else {
+                    return Ok(());
+                };
+                if idx == array.len() && edit.creates() {
+                    if !creatable_in_empty(&path.elements[i + 1..]) {
+                        return Ok(());
+                    }
+                    array.push(empty_container_for(next_element));
+                }
+                let Some(child) = array.get_mut(idx) else {
+                    return Ok(());
+                };
+                child
+            }
+            _ => return Ok(()),
+        };
+    }
+
+    expand_raw_json(current)?;
+    match (target, current) {
+        (PathElement::Key(key), Val::Object(map)) => match map.get_mut(key) {
+            Some(existing) if edit.overwrites() => *existing = value,
+            None if edit.creates() => {
+                map.insert(key.clone(), value);
+            }
+            _ => {}
+        },
+        (
+            element @ (PathElement::ArrayLocator(_) | PathElement::ArrayLocatorFromEnd(_)),
+            Val::Array(array),
+        ) => {
+            let Some(idx) = resolve_array_index(element, array.len()) else {
+                return Ok(());
+            };
+            if idx < array.len() {
+                if edit.overwrites() {
+                    array[idx] = value;
+                }
+            } else if idx == array.len() && edit.creates() {
+                array.push(value);
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Parses JSON text embedded by an earlier edit of the same call, so that a path can go into it.
+fn expand_raw_json(json: &mut Val<'_>) -> crate::Result<()> {
+    if let Val::RawJson(text) = json {
+        *json = from_str::<Val>(text).map_err(malformed_json)?.into_owned();
+    }
+    Ok(())
+}
+
+/// Removes the element of `json` addressed by `path`, shifting later array elements down.
+/// Nothing happens when the path doesn't resolve. The root can't be removed.
+fn remove_json_path(json: &mut Val, path: &JsonPath) -> crate::Result<()> {
+    if path.is_root() {
+        crate::bail_constraint_error!("JSON path error near: \"$\"");
+    }
+    let Some((target, parents)) = path.elements.split_last() else {
+        return Ok(());
+    };
+
+    let mut current = json;
+    for element in parents {
+        let child = match (element, current) {
+            (PathElement::Root(), current) => Some(current),
+            (PathElement::Key(key), Val::Object(map)) => map.get_mut(key),
+            (
+                element @ (PathElement::ArrayLocator(_) | PathElement::ArrayLocatorFromEnd(_)),
+                Val::Array(array),
+            ) => resolve_array_index(element, array.len()).and_then(|idx| array.get_mut(idx)),
+            _ => None,
+        };
+        let Some(child) = child else {
+            return Ok(());
+        };
+        current = child;
+    }
+
+    match (target, current) {
+        (PathElement::Key(key), Val::Object(map)) => {
+            map.shift_remove(key);
+        }
+        (
+            element @ (PathElement::ArrayLocator(_) | PathElement::ArrayLocatorFromEnd(_)),
+            Val::Array(array),
+        ) => {
+            if let Some(idx) = resolve_array_index(element, array.len()) {
+                if idx < array.len() {
+                    array.remove(idx);
+                }
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Returns an empty container of the type the given path element can step into.
+fn empty_container_for(element: &PathElement) -> Val<'static> {
+    match element {
+        PathElement::Key(_) => Val::Object(IndexMap::new()),
+        PathElement::ArrayLocator(_) | PathElement::ArrayLocatorFromEnd(_) => Val::Array(vec![]),
+        PathElement::Root() | PathElement::Wildcard | PathElement::Slice { .. } => Val::Null,
+    }
+}
+
+/// Whether the rest of a path can be created inside a newly created, empty container: keys
+/// always can, array locators only when they address the slot after the last element.
+fn creatable_in_empty(elements: &[PathElement]) -> bool {
+    elements.iter().all(|element| match element {
+        PathElement::Key(_) => true,
+        PathElement::ArrayLocator(_) | PathElement::ArrayLocatorFromEnd(_) => {
+            resolve_array_index(element, 0) == Some(0)
+        }
+        _ => false,
+    })
+}
+
+/// Turns an array locator into an absolute index. Locators relative to the end of the array
+/// yield `None` when they point before its start.
+fn resolve_array_index(element: &PathElement, len: usize) -> Option<usize> {
+    match element {
+        PathElement::ArrayLocator(idx) if *idx >= 0 => usize::try_from(*idx).ok(),
+        PathElement::ArrayLocator(idx) => {
+            len.checked_sub(usize::try_from(idx.unsigned_abs()).ok()?)
+        }
+        PathElement::ArrayLocatorFromEnd(offset) => len.checked_sub(usize::try_from(*offset).ok()?),
+        _ => None,
+    }
+}
+
+/// Returns the range of the elements of an array of `len` elements that a slice covers. Negative
+/// bounds count back from the end, and bounds out of range are clamped, so the range may be empty.
+fn resolve_slice(start: Option<i64>, end: Option<i64>, len: usize) -> std::ops::Range<usize> {
+    let resolve = |bound: i64| {
+        let magnitude = usize::try_from(bound.unsigned_abs()).unwrap_or(usize::MAX);
+        if bound >= 0 {
+            magnitude.min(len)
+        } else {
+            len.saturating_sub(magnitude)
+        }
+    };
+    let start = start.map_or(0, resolve);
+    let end = end.map_or(len, resolve);
+    start..end.max(start)
+}
+
+/// Returns the one-based character offset of the first syntax error in the input, or 0 if it is
+/// well-formed. Values that aren't text or blobs always have a valid JSON representation.
+/// https://sqlite.org/json1.html#jerr
+pub fn json_error_position(json: &OwnedValue) -> crate::Result<OwnedValue> {
+    match json {
+        OwnedValue::Text(t) => match from_str::<Val>(&t.value) {
+            Ok(_) => Ok(OwnedValue::Integer(0)),
+            Err(err) => match err.location() {
+                Some(loc) => Ok(OwnedValue::Integer(loc.offset as i64)),
+                None => Err(crate::error::LimboError::InternalError(
+                    "failed to determine json error position".into(),
+                )),
+            },
+        },
+        OwnedValue::Blob(b) => match jsonb::from_slice(b) {
+            Ok(_) => Ok(OwnedValue::Integer(0)),
+            Err(JsonbError::Syntax(_, pos)) => Ok(OwnedValue::Integer(pos as i64)),
+            _ => Err(crate::error::LimboError::InternalError(
+                "failed to determine json error position".into(),
+            )),
+        },
+        OwnedValue::Null => Ok(OwnedValue::Null),
+        _ => Ok(OwnedValue::Integer(0)),
+    }
+}
+
+/// Returns 1 if the value is well-formed JSON according to `flags`, or 0 otherwise.
+/// Without flags only RFC 8259 JSON text is accepted, matching SQLite's default.
+/// https://sqlite.org/json1.html#jvalid
+pub fn json_valid(value: &OwnedValue, flags: Option<&OwnedValue>) -> crate::Result<OwnedValue> {
+    let flags = json_valid_flags(flags, "json_valid")?;
+    if let OwnedValue::Null = value {
+        return Ok(OwnedValue::Null);
+    }
+
+    let is_valid = valid_json_kind(value, flags).is_some();
+    Ok(OwnedValue::Integer(is_valid as i64))
+}
+
+/// Like json_valid, but only returns 1 for documents whose top-level value is an array or object.
+pub fn json_valid_document(
+    value: &OwnedValue,
+    flags: Option<&OwnedValue>,
+) -> crate::Result<OwnedValue> {
+    let flags = json_valid_flags(flags, "json_valid_document")?;
+    if let OwnedValue::Null = value {
+        return Ok(OwnedValue::Null);
+    }
+
+    let is_document = valid_json_kind(value, flags).is_some_and(JsonKind::is_container);
+    Ok(OwnedValue::Integer(is_document as i64))
+}
+
+/// Reads the FLAGS argument of `func`, which defaults to RFC 8259 JSON text.
+fn json_valid_flags(flags: Option<&OwnedValue>, func: &str) -> crate::Result<i64> {
+    let flags = match flags {
+        None => JSON_VALID_RFC_8259,
+        Some(OwnedValue::Integer(i)) => *i,
+        Some(OwnedValue::Float(f)) => *f as i64,
+        Some(_) => 0,
+    };
+    if !(1..=15).contains(&flags) {
+        crate::bail_constraint_error!("FLAGS parameter to {}() must be between 1 and 15", func);
+    }
+    Ok(flags)
+}
+
+/// Returns the type of the top-level value of `value` if it's well-formed JSON according to
+/// `flags`, or None otherwise.
+fn valid_json_kind(value: &OwnedValue, flags: i64) -> Option<JsonKind> {
+    let text = match value {
+        OwnedValue::Text(t) => t.value.as_str(),
+        OwnedValue::Blob(b)
+            if flags & (JSON_VALID_JSONB_SUPERFICIAL | JSON_VALID_JSONB) != 0 && is_jsonb(b) =>
+        {
+            let kind = match jsonb::from_slice(b).ok()? {
+                jsonb::Value::Null => JsonKind::Null,
+                jsonb::Value::Bool(_) => JsonKind::Bool,
+                jsonb::Value::Number(_) => JsonKind::Number,
+                jsonb::Value::String(_) => JsonKind::String,
+                jsonb::Value::Array(_) => JsonKind::Array,
+                jsonb::Value::Object(_) => JsonKind::Object,
+            };
+            return Some(kind);
+        }
+        OwnedValue::Blob(b) => std::str::from_utf8(b).ok()?,
+        // numbers always have a valid JSON text representation
+        OwnedValue::Integer(_) | OwnedValue::Float(_) => {
+            let is_text_accepted = flags & (JSON_VALID_RFC_8259 | JSON_VALID_JSON5) != 0;
+            return is_text_accepted.then_some(JsonKind::Number);
+        }
+        _ => return None,
+    };
+
+    if flags & JSON_VALID_JSON5 != 0 {
+        validate(text).ok()
+    } else if flags & JSON_VALID_RFC_8259 != 0 {
+        strict_json_kind(text)
+    } else {
+        None
+    }
+}
+
+/// Whether the blob is JSONB. `jsonb::from_slice` also accepts JSON text, which doesn't count.
+fn is_jsonb(b: &[u8]) -> bool {
+    jsonb::from_slice(b).is_ok() && !is_json_text_blob(b)
+}
+
+/// Whether the blob holds JSON text rather than JSONB.
+fn is_json_text_blob(b: &[u8]) -> bool {
+    std::str::from_utf8(b).is_ok_and(|s| validate(s).is_ok())
+}
+
+/// Constructs a JSON object from a list of values that represent key-value pairs.
+/// The number of values must be even, and the first value of each pair (which represents the map key)
+/// must be a TEXT value. The second value of each pair can be any JSON value (which represents the map value)
+pub fn json_object(values: &[OwnedValue]) -> crate::Result<OwnedValue> {
+    let value_map = json_object_members(values)?;
+
+    let result = val_to_string(&Val::Object(value_map))?;
+    Ok(OwnedValue::Text(LimboText::json(Rc::new(result))))
+}
+
+/// Same as json_object, but returns the object encoded as JSONB.
+pub fn jsonb_object(values: &[OwnedValue]) -> crate::Result<OwnedValue> {
+    let value_map = json_object_members(values)?;
+    Ok(OwnedValue::Blob(Rc::new(
+        val_to_jsonb(&Val::Object(value_map))?.to_vec(),
+    )))
+}
+
+/// Converts the key-value pair arguments of json_object/jsonb_object into object members.
+fn json_object_members(values: &[OwnedValue]) -> crate::Result<IndexMap<String, Val<'_>>> {
+    let mut members = IndexMap::new();
+    for chunk in values.chunks(2) {
+        let [key, value] = chunk else {
+            crate::bail_constraint_error!("json_object requires an even number of values");
+        };
+        let key = match key {
+            OwnedValue::Text(t) => t.value.to_string(),
+            _ => crate::bail_constraint_error!("labels must be TEXT"),
+        };
+        let json_val = owned_value_to_val(value)?;
+
+        // Duplicate labels are handled like when parsing: the last one wins, in its position.
+        members.shift_remove(&key);
+        members.insert(key, json_val);
+    }
+    Ok(members)
+}
+
+/// State of the json_group_array() aggregate. Each value is written out as JSON text when it is
+/// stepped, so that only the text of the array grows with the number of rows, rather than a
+/// `Val` being kept for every row.
+/// https://sqlite.org/json1.html#jgrouparray
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct JsonGroupArray {
+    /// The elements stepped so far, separated by commas, without the brackets.
+    elements: String,
+}
+
+impl JsonGroupArray {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends the value of the current row. Values with the JSON subtype are embedded as JSON,
+    /// any other text is added as a JSON string.
+    pub fn step(&mut self, value: &OwnedValue) -> crate::Result<()> {
+        let value = owned_value_to_val(value)?;
+        // The text of an element is never empty, so there's an element before this one if
+        // anything was written.
+        if !self.elements.is_empty() {
+            self.elements.push(',');
+        }
+        write_val(&mut self.elements, &value)?;
+        Ok(())
+    }
+
+    /// Returns the array of all the stepped values, `[]` if there were none.
+    pub fn finalize(&self) -> crate::Result<OwnedValue> {
+        let mut json = String::with_capacity(self.elements.len() + 2);
+        json.push('[');
+        json.push_str(&self.elements);
+        json.push(']');
+        Ok(OwnedValue::Text(LimboText::json(Rc::new(json))))
+    }
+}
+
+/// State of the json_group_object() aggregate.
+/// https://sqlite.org/json1.html#jgroupobject
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct JsonGroupObject {
+    members: IndexMap<String, Val<'static>>,
+}
+
+impl JsonGroupObject {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the member of the current row. A key that was already added keeps its position,
+    /// but its value is overwritten.
+    pub fn step(&mut self, key: &OwnedValue, value: &OwnedValue) -> crate::Result<()> {
+        let key = match key {
+            OwnedValue::Text(t) => t.value.to_string(),
+            _ => crate::bail_constraint_error!("labels must be TEXT"),
+        };
+        self.members
+            .insert(key, owned_value_to_val(value)?.into_owned());
+        Ok(())
+    }
+
+    /// Returns the object of all the stepped members, `{}` if there were none.
+    pub fn finalize(&self) -> crate::Result<OwnedValue> {
+        let mut json = String::new();
+        write_object(&mut json, &self.members)?;
+        Ok(OwnedValue::Text(LimboText::json(Rc::new(json))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json::ser::serialize_into;
+    use crate::types::OwnedValue;
+
+    #[test]
+    fn test_get_json_valid_json5() {
+        let input = OwnedValue::build_text(Rc::new("{ key: 'value' }".to_string()));
+        let result = get_json(&input).unwrap();
+        if let OwnedValue::Text(result_str) = result {
+            assert!(result_str.value.contains("\"key\":\"value\""));
+            assert_eq!(result_str.subtype, TextSubtype::Json);
+        } else {
+            panic!("Expected OwnedValue::Text");
+        }
+    }
+
+    #[test]
+    fn test_get_json_valid_json5_double_single_quotes() {
+        let input = OwnedValue::build_text(Rc::new("{ key: ''value'' }".to_string()));
+        let result = get_json(&input).unwrap();
+        if let OwnedValue::Text(result_str) = result {
+            assert!(result_str.value.contains("\"key\":\"value\""));
+            assert_eq!(result_str.subtype, TextSubtype::Json);
+        } else {
+            panic!("Expected OwnedValue::Text");
+        }
+    }
+
+    #[test]
+    fn test_get_json_valid_json5_infinity() {
+        let input = OwnedValue::build_text(Rc::new("{ \"key\": Infinity }".to_string()));
+        let result = get_json(&input).unwrap();
+        if let OwnedValue::Text(result_str) = result {
+            assert!(result_str.value.contains("{\"key\":9e999}"));
+            assert_eq!(result_str.subtype, TextSubtype::Json);
+        } else {
+            panic!("Expected OwnedValue::Text");
+        }
+    }
+
+    #[test]
+    fn test_get_json_valid_json5_negative_infinity() {
+        let input = OwnedValue::build_text(Rc::new("{ \"key\": -Infinity }".to_string()));
+        let result = get_json(&input).unwrap();
+        if let OwnedValue::Text(result_str) = result {
+            assert!(result_str.value.contains("{\"key\":-9e999}"));
+            assert_eq!(result_str.subtype, TextSubtype::Json);
+        } else {
+            panic!("Expected OwnedValue::Text");
+        }
+    }
+
+    #[test]
+    fn test_get_json_valid_json5_nan() {
+        let input = OwnedValue::build_text(Rc::new("{ \"key\": NaN }".to_string()));
+        let result = get_json(&input).unwrap();
+        if let OwnedValue::Text(result_str) = result {
+            assert!(result_str.value.contains("{\"key\":null}"));
+            assert_eq!(result_str.subtype, TextSubtype::Json);
+        } else {
+            panic!("Expected OwnedValue::Text");
+        }
+    }
+
+    #[test]
+    fn test_get_json_preserves_number_text() {
+        let input = OwnedValue::build_text(Rc::new(
+            "{\"x\":1.0,\"y\":[1.50,1E5,-0.0,12.5e-1]}".to_string(),
+        ));
+        let result = get_json(&input).unwrap();
+        if let OwnedValue::Text(result_str) = result {
+            assert_eq!(
+                result_str.value.as_str(),
+                "{\"x\":1.0,\"y\":[1.50,1E5,-0.0,12.5e-1]}"
+            );
+        } else {
+            panic!("Expected OwnedValue::Text");
+        }
+    }
+
+    #[test]
+    fn test_number_exponents_and_negative_zero() {
+        // Exponents of either case and with or without a sign are read, and JSON text keeps them
+        // as written.
+        let json = "[1e10,1E10,1E+10,1e-10,-0.0,0.0]";
+        let input = OwnedValue::build_text(Rc::new(json.to_string()));
+        assert_eq!(
+            get_json(&input).unwrap(),
+            OwnedValue::Text(LimboText::json(Rc::new(json.to_string())))
+        );
+        for (index, expected) in [(0, 1e10), (1, 1e10), (2, 1e10), (3, 1e-10)] {
+            let path = OwnedValue::build_text(Rc::new(format!("$[{}]", index)));
+            assert_eq!(
+                json_extract(&input, &[path]).unwrap(),
+                OwnedValue::Float(expected)
+            );
+        }
+        let path = OwnedValue::build_text(Rc::new("$[4]".to_string()));
+        let OwnedValue::Float(zero) = json_extract(&input, &[path]).unwrap() else {
+            panic!("Expected OwnedValue::Float");
+        };
+        assert!(zero == 0.0 && zero.is_sign_negative());
+
+        // Floats are formatted like SQLite does, with a lowercase exponent that is always signed,
+        // and without the sign of a zero.
+        let floats = [-0.0, 0.0, 1e10, 1e15, 1e-10]
+            .map(OwnedValue::Float)
+            .to_vec();
+        assert_eq!(
+            json_array(&floats).unwrap(),
+            OwnedValue::Text(LimboText::json(Rc::new(
+                "[0.0,0.0,10000000000.0,1.0e+15,1.0e-10]".to_string()
+            )))
+        );
+
+        for json in ["1e", "1E+", "1e-", "1e+-1", "1.e"] {
+            let input = OwnedValue::build_text(Rc::new(json.to_string()));
+            assert!(get_json(&input).is_err(), "{}", json);
+        }
+    }
+
+    #[test]
+    fn test_integer_negative_zero() {
+        // `-0` keeps its sign in JSON text and through JSONB, but is extracted as the integer 0.
+        for json in ["-0", "[-0]", r#"{"a":-0}"#] {
+            let input = OwnedValue::build_text(Rc::new(json.to_string()));
+            assert_eq!(get_json(&input).unwrap().to_string(), json);
+            let blob = get_jsonb(&input).unwrap();
+            assert_eq!(get_json(&blob).unwrap().to_string(), json);
+        }
+        let input = OwnedValue::build_text(Rc::new("[-0]".to_string()));
+        let path = OwnedValue::build_text(Rc::new("$[0]".to_string()));
+        assert_eq!(
+            json_extract(&input, std::slice::from_ref(&path)).unwrap(),
+            OwnedValue::Integer(0)
+        );
+        assert_eq!(
+            json_type(&input, Some(&path)).unwrap(),
+            OwnedValue::build_text(Rc::new("integer".to_string()))
+        );
+
+        // A negative zero real is still written as `0.0` once stored as JSONB.
+        let input = json_array(&[OwnedValue::Float(-0.0)]).unwrap();
+        let blob = get_jsonb(&input).unwrap();
+        assert_eq!(get_json(&blob).unwrap().to_string(), "[0.0]");
+    }
+
+    #[test]
+    fn test_get_json_canonicalizes_json5_numbers() {
+        let input = OwnedValue::build_text(Rc::new("[.5,5.,+1.5]".to_string()));
+        let result = get_json(&input).unwrap();
+        if let OwnedValue::Text(result_str) = result {
+            assert_eq!(result_str.value.as_str(), "[0.5,5.0,1.5]");
+        } else {
+            panic!("Expected OwnedValue::Text");
+        }
+    }
+
+    #[test]
+    fn test_get_json_duplicate_keys_last_wins() {
+        let input = OwnedValue::build_text(Rc::new("{\"a\":1,\"a\":2}".to_string()));
+        let result = get_json(&input).unwrap();
+        if let OwnedValue::Text(result_str) = result {
+            assert_eq!(result_str.value.as_str(), "{\"a\":2}");
+        } else {
+            panic!("Expected OwnedValue::Text");
+        }
+    }
+
+    #[test]
+    fn test_get_json_duplicate_keys_take_last_position() {
+        let input = OwnedValue::build_text(Rc::new("{a:1,b:3,\"a\":2}".to_string()));
+        let result = get_json(&input).unwrap();
+        if let OwnedValue::Text(result_str) = result {
+            assert_eq!(result_str.value.as_str(), "{\"b\":3,\"a\":2}");
+        } else {
+            panic!("Expected OwnedValue::Text");
+        }
+    }
+
+    #[test]
+    fn test_json_extract_duplicate_keys_last_wins() {
+        // SQLite reads the first member instead.
+        let input = OwnedValue::build_text(Rc::new(r#"{"a":1,"a":2}"#.to_string()));
+        let path = OwnedValue::build_text(Rc::new("$.a".to_string()));
+        assert_eq!(
+            json_extract(&input, &[path]).unwrap(),
+            OwnedValue::Integer(2)
+        );
+    }
+
+    #[test]
+    fn test_get_json_valid_json5_hex() {
+        let input = OwnedValue::build_text(Rc::new("{ \"key\": 0xFF }".to_string()));
+        let result = get_json(&input).unwrap();
+        if let OwnedValue::Text(result_str) = result {
+            assert!(result_str.value.contains("{\"key\":255}"));
+            assert_eq!(result_str.subtype, TextSubtype::Json);
+        } else {
+            panic!("Expected OwnedValue::Text");
+        }
+    }
+
+    #[test]
+    fn test_get_json_valid_json5_negative_hex() {
+        let input = OwnedValue::build_text(Rc::new("{ \"key\": -0XfF }".to_string()));
+        let result = get_json(&input).unwrap();
+        if let OwnedValue::Text(result_str) = result {
+            assert!(result_str.value.contains("{\"key\":-255}"));
+            assert_eq!(result_str.subtype, TextSubtype::Json);
+        } else {
+            panic!("Expected OwnedValue::Text");
+        }
+    }
+
+    #[test]
+    fn test_get_json_valid_json5_large_hex() {
+        let input = OwnedValue::build_text(Rc::new("{ \"key\": 0xFFFFFFFF }".to_string()));
+        let result = get_json(&input).unwrap();
+        if let OwnedValue::Text(result_str) = result {
+            assert!(result_str.value.contains("{\"key\":4294967295}"));
+            assert_eq!(result_str.subtype, TextSubtype::Json);
+        } else {
+            panic!("Expected OwnedValue::Text");
+        }
+    }
+
+    #[test]
+    fn test_get_json_valid_json5_leading_plus() {
+        let input = OwnedValue::build_text(Rc::new("{ \"key\": +5 }".to_string()));
+        let result = get_json(&input).unwrap();
+        if let OwnedValue::Text(result_str) = result {
+            assert!(result_str.value.contains("{\"key\":5}"));
+            assert_eq!(result_str.subtype, TextSubtype::Json);
+        } else {
+            panic!("Expected OwnedValue::Text");
+        }
+    }
+
+    #[test]
+    fn test_get_json_valid_json5_leading_decimal_point() {
+        let input = OwnedValue::build_text(Rc::new("{ \"key\": +.5 }".to_string()));
+        let result = get_json(&input).unwrap();
+        if let OwnedValue::Text(result_str) = result {
+            assert!(result_str.value.contains("{\"key\":0.5}"));
+            assert_eq!(result_str.subtype, TextSubtype::Json);
+        } else {
+            panic!("Expected OwnedValue::Text");
+        }
+    }
+
+    #[test]
+    fn test_get_json_valid_json5_trailing_decimal_point() {
+        let input = OwnedValue::build_text(Rc::new("{ \"key\": -5.e1 }".to_string()));
+        let result = get_json(&input).unwrap();
+        if let OwnedValue::Text(result_str) = result {
+            assert!(result_str.value.contains("{\"key\":-5.0e1}"));
+            assert_eq!(result_str.subtype, TextSubtype::Json);
+        } else {
+            panic!("Expected OwnedValue::Text");
+        }
+    }
+
+    #[test]
+    fn test_get_json_json5_comments() {
+        let cases = [
+            ("{ /* comment */ key: 1 }", r#"{"key":1}"#),
+            (
+                "[1, // to the end of line\n 2 /* a */ /* b */ , 3]",
+                "[1,2,3]",
+            ),
+            (
+                "{\"a\" /* x */ : /* y */ \"/* not a comment */\", // trailing\n}",
+                r#"{"a":"/* not a comment */"}"#,
+            ),
+            (
+                "['// not a comment either']",
+                r#"["// not a comment either"]"#,
+            ),
+        ];
+        for (input, expected) in cases {
+            let input = OwnedValue::build_text(Rc::new(input.to_string()));
+            let OwnedValue::Text(result) = get_json(&input).unwrap() else {
+                panic!("Expected OwnedValue::Text");
+            };
+            assert_eq!(result.value.as_str(), expected);
+        }
+    }
+
+    #[test]
+    fn test_get_json_json5_unterminated_comment() {
+        for input in ["[1 /* ]", "{a: 1} /* trailing"] {
+            let input = OwnedValue::build_text(Rc::new(input.to_string()));
+            match get_json(&input) {
+                Ok(_) => panic!("Expected error for malformed JSON"),
+                Err(e) => assert!(e.to_string().contains("malformed JSON")),
+            }
+        }
+    }
+
+    #[test]
+    fn test_trailing_content() {
+        for input in [
+            " {\"a\":1} ",
+            "\n\t[1]\r\n",
+            "1 // comment",
+            " /* a */ 2 /* b */ ",
+        ] {
+            assert!(from_str::<Val>(input).is_ok(), "{:?}", input);
+            let input = OwnedValue::build_text(Rc::new(input.to_string()));
+            assert!(get_json(&input).is_ok(), "{:?}", input);
+        }
+
+        for input in [
+            "{\"a\":1} x",
+            "{\"a\":1}garbage",
+            "1 2",
+            "[1]]",
+            "{\"a\":1}}",
+            "null,",
+        ] {
+            assert!(from_str::<Val>(input).is_err(), "{:?}", input);
+            let input = OwnedValue::build_text(Rc::new(input.to_string()));
+            assert_eq!(json_valid(&input, None).unwrap(), OwnedValue::Integer(0));
+            let path = OwnedValue::build_text(Rc::new("$.a".to_string()));
+            assert!(json_extract(&input, &[path]).is_err(), "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn test_get_json_with_format() {
+        let input = OwnedValue::build_text(Rc::new("{a: [1, 2.5, 'x'],}".to_string()));
+
+        let text = get_json_with_format(&input, JsonRepr::Text).unwrap();
+        let OwnedValue::Text(ref t) = text else {
+            panic!("Expected OwnedValue::Text");
+        };
+        assert_eq!(t.value.as_str(), r#"{"a":[1,2.5,"x"]}"#);
+        assert_eq!(t.subtype, TextSubtype::Json);
+        assert_eq!(text, get_json(&input).unwrap());
+
+        let blob = get_json_with_format(&input, JsonRepr::Jsonb).unwrap();
+        let OwnedValue::Blob(ref b) = blob else {
+            panic!("Expected OwnedValue::Blob");
+        };
+        assert!(is_jsonb(b));
+        assert_eq!(blob, get_jsonb(&input).unwrap());
+
+        // Either way, the value is the same, and converting back gives the other result.
+        assert_eq!(get_json_with_format(&blob, JsonRepr::Text).unwrap(), text);
+        assert_eq!(get_json_with_format(&text, JsonRepr::Jsonb).unwrap(), blob);
+        assert_eq!(
+            get_json_with_format(&blob, JsonRepr::of(&blob)).unwrap(),
+            blob
+        );
+        assert_eq!(
+            get_json_with_format(&text, JsonRepr::default()).unwrap(),
+            text
+        );
+        assert_eq!(
+            get_json_with_format(&OwnedValue::Null, JsonRepr::Jsonb).unwrap(),
+            OwnedValue::Null
+        );
+    }
+
+    #[test]
+    fn test_byte_order_mark() {
+        // A BOM is JSON5 whitespace: skipped before the value and between tokens.
+        for input in ["\u{feff}{\"a\":1}", "{\u{feff}\"a\"\u{feff}:1}\u{feff}"] {
+            assert_eq!(
+                from_str::<Val>(input).unwrap(),
+                from_str::<Val>(r#"{"a":1}"#).unwrap()
+            );
+            let input = OwnedValue::build_text(Rc::new(input.to_string()));
+            let OwnedValue::Text(result) = get_json(&input).unwrap() else {
+                panic!("Expected OwnedValue::Text");
+            };
+            assert_eq!(result.value.as_str(), r#"{"a":1}"#);
+            // It isn't RFC 8259 whitespace, though.
+            assert_eq!(json_valid(&input, None).unwrap(), OwnedValue::Integer(0));
+            assert_eq!(
+                json_valid(&input, Some(&OwnedValue::Integer(2))).unwrap(),
+                OwnedValue::Integer(1)
+            );
+            let path = OwnedValue::build_text(Rc::new("$.a".to_string()));
+            assert_eq!(
+                json_extract(&input, &[path]).unwrap(),
+                OwnedValue::Integer(1)
+            );
+        }
+
+        // Inside a string, it's part of the string.
+        let input = OwnedValue::build_text(Rc::new("{\"a\u{feff}\":1}".to_string()));
+        let OwnedValue::Text(result) = get_json(&input).unwrap() else {
+            panic!("Expected OwnedValue::Text");
+        };
+        assert_eq!(result.value.as_str(), "{\"a\u{feff}\":1}");
+
+        // Blobs are only read as text when they are UTF-8, so a BOM in UTF-8 is skipped too,
+        // while other encodings are an error.
+        let input = OwnedValue::Blob(Rc::new(b"\xef\xbb\xbf{\"a\":1}".to_vec()));
+        let OwnedValue::Text(result) = get_json(&input).unwrap() else {
+            panic!("Expected OwnedValue::Text");
+        };
+        assert_eq!(result.value.as_str(), r#"{"a":1}"#);
+        let utf16 = "\u{feff}{\"a\":1}"
+            .encode_utf16()
+            .flat_map(u16::to_le_bytes)
+            .collect();
+        assert!(get_json(&OwnedValue::Blob(Rc::new(utf16))).is_err());
+    }
+
+    #[test]
+    fn test_get_json_reuses_input_text() {
+        let text = Rc::new(r#"{"a":[1,2]}"#.to_string());
+        for input in [
+            OwnedValue::Text(LimboText::json(text.clone())),
+            OwnedValue::build_text(text.clone()),
+        ] {
+            let OwnedValue::Text(result) = get_json(&input).unwrap() else {
+                panic!("Expected OwnedValue::Text");
+            };
+            assert!(Rc::ptr_eq(&result.value, &text));
+            assert_eq!(result.subtype, TextSubtype::Json);
+        }
+
+        let input = OwnedValue::build_text(Rc::new(r#"{ "a": [1, 2] }"#.to_string()));
+        let OwnedValue::Text(result) = get_json(&input).unwrap() else {
+            panic!("Expected OwnedValue::Text");
+        };
+        assert_eq!(result.value.as_str(), r#"{"a":[1,2]}"#);
+    }
+
+    #[test]
+    fn test_get_json_non_bmp_characters() {
+        let cases = [
+            ("\"\u{1F600}\"", "\"\u{1F600}\""),
+            (r#""\uD83D\uDE00""#, "\"\u{1F600}\""),
+            (
+                r#"["a\ud83d\ude00b", "\u00e9"]"#,
+                "[\"a\u{1F600}b\",\"\u{e9}\"]",
+            ),
+        ];
+        for (input, expected) in cases {
+            let input = OwnedValue::build_text(Rc::new(input.to_string()));
+            let OwnedValue::Text(result) = get_json(&input).unwrap() else {
+                panic!("Expected OwnedValue::Text");
+            };
+            assert_eq!(result.value.as_str(), expected);
+        }
+    }
+
+    #[test]
+    fn test_get_json_lone_surrogate() {
+        for input in [
+            r#""\uD83D""#,
+            r#""\uDE00""#,
+            r#""\uD83Dx""#,
+            r#""\uD83D\u0041""#,
+            r#""\uDE00\uD83D""#,
+        ] {
+            let input = OwnedValue::build_text(Rc::new(input.to_string()));
+            match get_json(&input) {
+                Ok(_) => panic!("Expected error for malformed JSON"),
+                Err(e) => assert!(e.to_string().contains("malformed JSON")),
+            }
+        }
+    }
+
+    #[test]
+    fn test_get_json_invalid_json5() {
+        let input = OwnedValue::build_text(Rc::new("{ key: value }".to_string()));
+        let result = get_json(&input);
+        match result {
+            Ok(_) => panic!("Expected error for malformed JSON"),
+            Err(e) => assert!(e.to_string().contains("malformed JSON")),
+        }
+    }
+
+    #[test]
+    fn test_get_json_valid_jsonb() {
+        let input = OwnedValue::build_text(Rc::new("{\"key\":\"value\"}".to_string()));
+        let result = get_json(&input).unwrap();
+        if let OwnedValue::Text(result_str) = result {
+            assert!(result_str.value.contains("\"key\":\"value\""));
+            assert_eq!(result_str.subtype, TextSubtype::Json);
+        } else {
+            panic!("Expected OwnedValue::Text");
+        }
+    }
+
+    #[test]
+    fn test_get_json_invalid_jsonb() {
+        let input = OwnedValue::build_text(Rc::new("{key:\"value\"".to_string()));
+        let result = get_json(&input);
+        match result {
+            Ok(_) => panic!("Expected error for malformed JSON"),
+            Err(e) => assert!(e.to_string().contains("malformed JSON")),
+        }
+    }
+
+    #[test]
+    fn test_get_json_blob_valid_jsonb() {
+        let binary_json = b"\x40\0\0\x01\x10\0\0\x03\x10\0\0\x03\x61\x73\x64\x61\x64\x66".to_vec();
+        let input = OwnedValue::Blob(Rc::new(binary_json));
+        let result = get_json(&input).unwrap();
+        if let OwnedValue::Text(result_str) = result {
+            assert!(result_str.value.contains("\"asd\":\"adf\""));
+            assert_eq!(result_str.subtype, TextSubtype::Json);
+        } else {
+            panic!("Expected OwnedValue::Text");
+        }
+    }
+
+    #[test]
+    fn test_get_json_value_from_jsonb() {
+        let binary_json = b"\x40\0\0\x01\x10\0\0\x03\x10\0\0\x03\x61\x73\x64\x61\x64\x66".to_vec();
+        let input = OwnedValue::Blob(Rc::new(binary_json));
+        assert_eq!(
+            get_json_value(&input).unwrap(),
+            Val::object().insert("asd", "adf")
+        );
+
+        let input = jsonb_blob(r#"[null,true,-3,18446744073709551615,1.5,"x",{"a":[]}]"#);
+        assert_eq!(
+            get_json_value(&input).unwrap(),
+            Val::array()
+                .push(Val::Null)
+                .push(true)
+                .push(-3)
+                .push(Val::RawNumber("18446744073709551615".to_string()))
+                .push(1.5)
+                .push("x")
+                .push(Val::object().insert("a", Val::array()))
+        );
+    }
+
+    #[test]
+    fn test_json_functions_on_jsonb() {
+        let input = jsonb_blob(r#"{"a":[1,2,3],"b":{"c":"d"}}"#);
+        let path = |p: &str| OwnedValue::build_text(Rc::new(p.to_string()));
+
+        assert_eq!(
+            json_array_length(&input, Some(&path("$.a"))).unwrap(),
+            OwnedValue::Integer(3)
+        );
+        assert_eq!(
+            json_type(&input, Some(&path("$.b"))).unwrap(),
+            OwnedValue::build_text(Rc::new("object".to_string()))
+        );
+        assert_eq!(
+            json_arrow_shift_extract(&input, &path("$.b.c")).unwrap(),
+            OwnedValue::build_text(Rc::new("d".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_jsonb_round_trip() {
+        let input = OwnedValue::build_text(Rc::new("{a: [1, 2.5, 'x'], b: null}".to_string()));
+        let OwnedValue::Blob(blob) = get_jsonb(&input).unwrap() else {
+            panic!("Expected OwnedValue::Blob");
+        };
+
+        let OwnedValue::Text(text) = get_json(&OwnedValue::Blob(blob.clone())).unwrap() else {
+            panic!("Expected OwnedValue::Text");
+        };
+        assert_eq!(text.value.as_str(), r#"{"a":[1,2.5,"x"],"b":null}"#);
+
+        // Valid JSONB is passed through untouched
+        assert_eq!(
+            get_jsonb(&OwnedValue::Blob(blob.clone())).unwrap(),
+            OwnedValue::Blob(blob)
+        );
+    }
+
+    #[test]
+    fn test_jsonb_round_trip_large_integers() {
+        let round_trip = |json: &str| {
+            let blob = get_jsonb(&OwnedValue::build_text(Rc::new(json.to_string()))).unwrap();
+            get_json(&blob).unwrap().to_string()
+        };
+
+        for json in ["18446744073709551615", "[9223372036854775808,-1]"] {
+            assert_eq!(round_trip(json), json);
+        }
+        // JSONB numbers are at most 64-bit integers, so larger ones are stored as floats.
+        assert_eq!(
+            round_trip("123456789012345678901234567890"),
+            "1.23456789012346e+29"
+        );
+    }
+
+    #[test]
+    fn test_get_json_jsonb_same_as_text() {
+        let text = OwnedValue::build_text(Rc::new(
+            // JSONB sorts object keys, so they are sorted here already.
+            r#"{"b":[true,{}],"f":[0.1,-2.5,1.0e+20,3.0e-05,1.0],"i":[0,-7],"n":null,"s":"a\"\n"}"#
+                .to_string(),
+        ));
+        let blob = get_jsonb(&text).unwrap();
+        assert!(matches!(blob, OwnedValue::Blob(_)));
+
+        let from_text = get_json(&text).unwrap();
+        let from_blob = get_json(&blob).unwrap();
+        assert_eq!(from_blob, from_text);
+        assert_eq!(from_blob.to_string(), from_text.to_string());
+    }
+
+    #[test]
+    fn test_get_jsonb_passes_jsonb_through() {
+        let text = OwnedValue::build_text(Rc::new(r#"{"a":[1,2.5,"x"]}"#.to_string()));
+        let OwnedValue::Blob(blob) = get_jsonb(&text).unwrap() else {
+            panic!("Expected OwnedValue::Blob");
+        };
+        let OwnedValue::Blob(passed_through) = get_jsonb(&OwnedValue::Blob(blob.clone())).unwrap()
+        else {
+            panic!("Expected OwnedValue::Blob");
+        };
+        assert!(Rc::ptr_eq(&blob, &passed_through));
+
+        // JSON text stored as a blob isn't JSONB, so it gets encoded.
+        let text_blob = OwnedValue::Blob(Rc::new(br#"{"a":[1,2.5,"x"]}"#.to_vec()));
+        assert_eq!(get_jsonb(&text_blob).unwrap(), OwnedValue::Blob(blob));
+    }
+
+    #[test]
+    fn test_jsonb_invalid_input() {
+        let input = OwnedValue::build_text(Rc::new("{a:".to_string()));
+        assert!(get_jsonb(&input).is_err());
+
+        let input = OwnedValue::Blob(Rc::new(vec![0xA2, 0x62, 0x6B, 0x31, 0x62, 0x76]));
+        assert!(get_jsonb(&input).is_err());
+
+        assert_eq!(get_jsonb(&OwnedValue::Null).unwrap(), OwnedValue::Null);
+    }
+
+    #[test]
+    fn test_jsonb_array_and_object() {
+        let values = [
+            OwnedValue::Integer(1),
+            OwnedValue::build_text(Rc::new("x".to_string())),
+            OwnedValue::Text(LimboText::json(Rc::new("[2,3]".to_string()))),
+            OwnedValue::Null,
+        ];
+        let OwnedValue::Blob(blob) = jsonb_array(&values).unwrap() else {
+            panic!("Expected OwnedValue::Blob");
+        };
+        let OwnedValue::Text(text) = get_json(&OwnedValue::Blob(blob)).unwrap() else {
+            panic!("Expected OwnedValue::Text");
+        };
+        assert_eq!(text.value.as_str(), r#"[1,"x",[2,3],null]"#);
+
+        let members = [
+            OwnedValue::build_text(Rc::new("a".to_string())),
+            OwnedValue::Text(LimboText::json(Rc::new("[2,3]".to_string()))),
+            OwnedValue::build_text(Rc::new("b".to_string())),
+            OwnedValue::Null,
+        ];
+        let OwnedValue::Blob(blob) = jsonb_object(&members).unwrap() else {
+            panic!("Expected OwnedValue::Blob");
+        };
+        let OwnedValue::Text(text) = get_json(&OwnedValue::Blob(blob)).unwrap() else {
+            panic!("Expected OwnedValue::Text");
+        };
+        assert_eq!(text.value.as_str(), r#"{"a":[2,3],"b":null}"#);
+    }
+
+    #[test]
+    fn test_blob_in_json_error_kind() {
+        let blob = OwnedValue::Blob(Rc::new(vec![1, 2]));
+        let text = |s: &str| OwnedValue::build_text(Rc::new(s.to_string()));
+        for result in [
+            json_array(std::slice::from_ref(&blob)),
+            jsonb_array(std::slice::from_ref(&blob)),
+            json_object(&[text("a"), blob.clone()]),
+            json_quote(&blob),
+            json_escape(&blob),
+            json_unescape(&blob),
+            json_set(&text("{}"), &[text("$.a"), blob.clone()]),
+        ] {
+            let err = result.unwrap_err();
+            assert!(matches!(err, crate::LimboError::BlobInJson), "{:?}", err);
+            assert_eq!(
+                err.to_string(),
+                "Runtime error: JSON cannot hold BLOB values"
+            );
+        }
+
+        // Parse and path errors are told apart from it.
+        assert!(matches!(
+            get_json(&text("[1,")).unwrap_err(),
+            crate::LimboError::ParseError(_)
+        ));
+        assert!(matches!(
+            json_extract(&text("[1]"), &[text("x")]).unwrap_err(),
+            crate::LimboError::Constraint(_)
+        ));
+    }
+
+    #[test]
+    fn test_jsonb_array_and_object_validation() {
+        let blob = OwnedValue::Blob(Rc::new(vec![1, 2]));
+        match jsonb_array(&[blob]) {
+            Ok(_) => panic!("Expected error for BLOB value"),
+            Err(e) => assert!(e.to_string().contains("JSON cannot hold BLOB values")),
+        }
+        match jsonb_object(&[OwnedValue::Integer(1), OwnedValue::Integer(2)]) {
+            Ok(_) => panic!("Expected error for non-TEXT label"),
+            Err(e) => assert!(e.to_string().contains("labels must be TEXT")),
+        }
+    }
+
+    #[test]
+    fn test_get_json_blob_invalid_jsonb() {
+        let binary_json: Vec<u8> = vec![0xA2, 0x62, 0x6B, 0x31, 0x62, 0x76]; // Incomplete binary JSON
+        let input = OwnedValue::Blob(Rc::new(binary_json));
+        let result = get_json(&input);
+        match result {
+            Ok(_) => panic!("Expected error for malformed JSON"),
+            Err(e) => assert!(e.to_string().contains("malformed JSON")),
+        }
+    }
+
+    #[test]
+    fn test_get_json_non_text() {
+        let input = OwnedValue::Null;
+        let result = get_json(&input).unwrap();
+        if let OwnedValue::Null = result {
+            // Test passed
+        } else {
+            panic!("Expected OwnedValue::Null");
+        }
+    }
+
+    #[test]
+    fn test_json_canonicalize() {
+        let canonical = |json: &str| {
+            let input = OwnedValue::build_text(Rc::new(json.to_string()));
+            match json_canonicalize(&input).unwrap() {
+                OwnedValue::Text(t) => {
+                    assert_eq!(t.subtype, TextSubtype::Json);
+                    t.value.to_string()
+                }
+                other => panic!("Expected OwnedValue::Text, got {:?}", other),
+            }
+        };
+
+        assert_eq!(
+            canonical(r#"{"b": [3, {"z": 1, "a": 2}], "a": {"d": null, "c": true}}"#),
+            r#"{"a":{"c":true,"d":null},"b":[3,{"a":2,"z":1}]}"#
+        );
+        assert_eq!(
+            canonical(r#"{"b":[2.0,{"d":1,"c":1e21}],"a":null}"#),
+            r#"{"a":null,"b":[2,{"c":1e+21,"d":1}]}"#
+        );
+        // Keys are compared by UTF-16 code units: U+1F600 is a surrogate pair, which sorts
+        // before U+FB33 even though its UTF-8 encoding sorts after.
+        assert_eq!(
+            canonical(r#"{"\ufb33":1,"😀":2,"é":3,"10":4,"1":5}"#),
+            r#"{"1":5,"10":4,"é":3,"😀":2,"דּ":1}"#
+        );
+        assert_eq!(
+            canonical(
+                "[1.0, -0.0, 1e21, 1e20, 1.5e-7, 0.000001, 123.456e2, 0x10, 18446744073709551615]"
+            ),
+            "[1,0,1e+21,100000000000000000000,1.5e-7,0.000001,12345.6,16,18446744073709552000]"
+        );
+        assert_eq!(
+            canonical(r#"{'a':"\u0041\u001f\/"}"#),
+            r#"{"a":"A\u001f/"}"#
+        );
+
+        // The same value written two ways canonicalizes to the same text, JSONB included.
+        let jsonb = get_jsonb(&OwnedValue::build_text(Rc::new(
+            r#"{"y":[1,2],"x":0.5}"#.to_string(),
+        )))
+        .unwrap();
+        assert_eq!(
+            json_canonicalize(&jsonb).unwrap(),
+            json_canonicalize(&OwnedValue::build_text(Rc::new(
+                r#"{ "x": 5e-1, "y": [1, 2.0] }"#.to_string()
+            )))
+            .unwrap()
+        );
+        assert_eq!(
+            json_canonicalize(&OwnedValue::Null).unwrap(),
+            OwnedValue::Null
+        );
+    }
+
+    #[test]
+    fn test_json_canonicalize_rejects_non_finite_numbers() {
+        for json in ["NaN", "[Infinity]", r#"{"a":-Infinity}"#, "1e999"] {
+            let input = OwnedValue::build_text(Rc::new(json.to_string()));
+            assert!(json_canonicalize(&input).is_err(), "{}", json);
+        }
+    }
+
+    #[test]
+    fn test_json_array_simple() {
+        let text = OwnedValue::build_text(Rc::new("value1".to_string()));
+        let json = OwnedValue::Text(LimboText::json(Rc::new("\"value2\"".to_string())));
+        let input = vec![text, json, OwnedValue::Integer(1), OwnedValue::Float(1.1)];
+
+        let result = json_array(&input).unwrap();
+        if let OwnedValue::Text(res) = result {
+            assert_eq!(res.value.as_str(), "[\"value1\",\"value2\",1,1.1]");
+            assert_eq!(res.subtype, TextSubtype::Json);
+        } else {
+            panic!("Expected OwnedValue::Text");
+        }
+    }
+
+    #[test]
+    fn test_json_array_empty() {
+        let input = vec![];
+
+        let result = json_array(&input).unwrap();
+        if let OwnedValue::Text(res) = result {
+            assert_eq!(res.value.as_str(), "[]");
+            assert_eq!(res.subtype, TextSubtype::Json);
+        } else {
+            panic!("Expected OwnedValue::Text");
+        }
+    }
+
+    #[test]
+    fn test_json_array_single_element() {
+        let inputs = [
+            (OwnedValue::Integer(1), "[1]"),
+            (OwnedValue::build_text(Rc::new("a".to_string())), "[\"a\"]"),
+            (
+                OwnedValue::Text(LimboText::json(Rc::new("[2]".to_string()))),
+                "[[2]]",
+            ),
+        ];
+        for (input, expected) in inputs {
+            let result = json_array(&[input]).unwrap();
+            if let OwnedValue::Text(res) = result {
+                assert_eq!(res.value.as_str(), expected);
+                assert_eq!(res.subtype, TextSubtype::Json);
+            } else {
+                panic!("Expected OwnedValue::Text");
+            }
+        }
+    }
+
+    #[test]
+    fn test_json_array_compacts_embedded_json() {
+        let pretty = OwnedValue::Text(LimboText::json(Rc::new(
+            "{\n  \"a b\": [1, 2],\n  \"c\": \"d e\"\n}".to_string(),
+        )));
+        let compact = OwnedValue::Text(LimboText::json(Rc::new(r#"{"x y":" z "}"#.to_string())));
+        let result = json_array(&[pretty.clone(), compact.clone()]).unwrap();
+        assert_eq!(
+            result,
+            OwnedValue::Text(LimboText::json(Rc::new(
+                r#"[{"a b":[1,2],"c":"d e"},{"x y":" z "}]"#.to_string()
+            )))
+        );
+
+        let key = OwnedValue::build_text(Rc::new("k".to_string()));
+        assert_eq!(
+            json_object(&[key, pretty.clone()]).unwrap(),
+            OwnedValue::Text(LimboText::json(Rc::new(
+                r#"{"k":{"a b":[1,2],"c":"d e"}}"#.to_string()
+            )))
+        );
+        for func in [get_json, json_quote] {
+            assert_eq!(
+                func(&pretty).unwrap(),
+                OwnedValue::Text(LimboText::json(Rc::new(
+                    r#"{"a b":[1,2],"c":"d e"}"#.to_string()
+                )))
+            );
+            assert_eq!(func(&compact).unwrap(), compact);
+        }
+    }
+
+    #[test]
+    fn test_json_array_blob_invalid() {
+        let blob = OwnedValue::Blob(Rc::new("1".as_bytes().to_vec()));
+
+        let input = vec![blob];
+
+        let result = json_array(&input);
+
+        match result {
+            Ok(_) => panic!("Expected error for blob input"),
+            Err(e) => assert!(e.to_string().contains("JSON cannot hold BLOB values")),
+        }
+    }
+
+    #[test]
+    fn test_json_quote_scalars() {
+        let cases = [
+            (OwnedValue::Integer(3), "3"),
+            (OwnedValue::Float(3.5), "3.5"),
+            (OwnedValue::Null, "null"),
+            (
+                OwnedValue::build_text(Rc::new("verdant".to_string())),
+                "\"verdant\"",
+            ),
+        ];
+        for (input, expected) in cases {
+            let OwnedValue::Text(result) = json_quote(&input).unwrap() else {
+                panic!("Expected OwnedValue::Text");
+            };
+            assert_eq!(result.value.as_str(), expected);
+            assert_eq!(result.subtype, TextSubtype::Json);
+        }
+    }
+
+    #[test]
+    fn test_json_quote_float_formatting() {
+        // Expected values are SQLite's outputs
+        let cases = [
+            (0.1, "0.1"),
+            (0.1 + 0.2, "0.3"),
+            (123.456, "123.456"),
+            (-1.5, "-1.5"),
+            (1.0, "1.0"),
+            (-0.0, "0.0"),
+            (1e-4, "0.0001"),
+            (3.0e-5, "3.0e-05"),
+            (1.5e-10, "1.5e-10"),
+            (1e14, "100000000000000.0"),
+            (99999999999999.9, "99999999999999.9"),
+            (999999999999999.9, "1.0e+15"),
+            (1e20, "1.0e+20"),
+            (123456789012345678.0, "1.23456789012346e+17"),
+            (2.5e300, "2.5e+300"),
+            (f64::MAX, "1.79769313486232e+308"),
+            (5e-324, "4.94065645841247e-324"),
+        ];
+        for (input, expected) in cases {
+            let OwnedValue::Text(result) = json_quote(&OwnedValue::Float(input)).unwrap() else {
+                panic!("Expected OwnedValue::Text");
+            };
+            assert_eq!(result.value.as_str(), expected, "input: {}", input);
+        }
+    }
+
+    #[test]
+    fn test_json_quote_does_not_parse_text() {
+        let input = OwnedValue::build_text(Rc::new(r#"{"a":1}"#.to_string()));
+        let OwnedValue::Text(result) = json_quote(&input).unwrap() else {
+            panic!("Expected OwnedValue::Text");
+        };
+        assert_eq!(result.value.as_str(), r#""{\"a\":1}""#);
+    }
+
+    #[test]
+    fn test_json_quote_json_subtype_is_unchanged() {
+        let input = OwnedValue::Text(LimboText::json(Rc::new(r#"{"a":1}"#.to_string())));
+        assert_eq!(json_quote(&input).unwrap(), input);
+    }
+
+    #[test]
+    fn test_serialize_into_appends_to_buffer() {
+        let mut buf = String::from("[");
+        serialize_into(&mut buf, &Val::String("a\"b".into())).unwrap();
+        buf.push(',');
+        serialize_into(
+            &mut buf,
+            &Val::Array(vec![Val::Integer(1), Val::RawNumber("1.50".to_string())]),
+        )
+        .unwrap();
+        buf.push(']');
+        assert_eq!(buf, r#"["a\"b",[1,1.50]]"#);
+        assert_eq!(to_string(&Val::Null).unwrap(), "null");
+    }
+
+    #[test]
+    fn test_json_quote_escapes_control_characters() {
+        let cases = [
+            ("\n", r#""\n""#),
+            ("\0", r#""\u0000""#),
+            ("\u{8}\u{c}", r#""\b\f""#),
+            ("\u{b}\u{1f}", r#""\u000b\u001f""#),
+            ("\u{7f}", "\"\u{7f}\""),
+        ];
+        for (input, expected) in cases {
+            let input = OwnedValue::build_text(Rc::new(input.to_string()));
+            let OwnedValue::Text(result) = json_quote(&input).unwrap() else {
+                panic!("Expected OwnedValue::Text");
+            };
+            assert_eq!(result.value.as_str(), expected);
+        }
+    }
+
+    #[test]
+    fn test_json_quote_blob_invalid() {
+        let input = OwnedValue::Blob(Rc::new(b"abc".to_vec()));
+        match json_quote(&input) {
+            Ok(_) => panic!("Expected error for blob input"),
+            Err(e) => assert!(e.to_string().contains("JSON cannot hold BLOB values")),
+        }
+    }
+
+    #[test]
+    fn test_json_escape() {
+        let cases = [
+            ("plain", "plain"),
+            (r#"say "hi""#, r#"say \"hi\""#),
+            (r"C:\dir\", r"C:\\dir\\"),
+            ("a\nb\tc\r", r"a\nb\tc\r"),
+            ("\u{0}\u{8}\u{c}\u{1f}\u{7f}", "\\u0000\\b\\f\\u001f\u{7f}"),
+            ("é😀", "é😀"),
+        ];
+        for (input, escaped) in cases {
+            let input = OwnedValue::build_text(Rc::new(input.to_string()));
+            let OwnedValue::Text(result) = json_escape(&input).unwrap() else {
+                panic!("Expected OwnedValue::Text");
+            };
+            assert_eq!(result.value.as_str(), escaped);
+            assert_eq!(result.subtype, TextSubtype::Text);
+            assert_eq!(json_unescape(&OwnedValue::Text(result)).unwrap(), input);
+        }
+
+        assert_eq!(
+            json_escape(&OwnedValue::Integer(-12)).unwrap(),
+            OwnedValue::build_text(Rc::new("-12".to_string()))
+        );
+        assert_eq!(json_escape(&OwnedValue::Null).unwrap(), OwnedValue::Null);
+        assert!(json_escape(&OwnedValue::Blob(Rc::new(vec![1]))).is_err());
+    }
+
+    #[test]
+    fn test_json_unescape() {
+        let cases = [
+            (r"\u00e9\ud83d\ude00", "é😀"),
+            (r"\/\'\v\0", "/'\u{b}\u{0}"),
+            ("line\\\ncontinued", "linecontinued"),
+        ];
+        for (input, unescaped) in cases {
+            let input = OwnedValue::build_text(Rc::new(input.to_string()));
+            assert_eq!(
+                json_unescape(&input).unwrap(),
+                OwnedValue::build_text(Rc::new(unescaped.to_string()))
+            );
+        }
+
+        for input in [r#"a"b"#, r#"a" // "#, r"\", r"\ud800", "a\nb"] {
+            let input = OwnedValue::build_text(Rc::new(input.to_string()));
+            assert!(json_unescape(&input).is_err(), "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn test_json_array_length() {
+        let input = OwnedValue::build_text(Rc::new("[1,2,3,4]".to_string()));
+        let result = json_array_length(&input, None).unwrap();
+        if let OwnedValue::Integer(res) = result {
+            assert_eq!(res, 4);
+        } else {
+            panic!("Expected OwnedValue::Integer");
+        }
+    }
+
+    #[test]
+    fn test_json_array_length_empty() {
+        let input = OwnedValue::build_text(Rc::new("[]".to_string()));
+        let result = json_array_length(&input, None).unwrap();
+        if let OwnedValue::Integer(res) = result {
+            assert_eq!(res, 0);
+        } else {
+            panic!("Expected OwnedValue::Integer");
+        }
+    }
+
+    #[test]
+    fn test_json_array_length_root() {
+        let input = OwnedValue::build_text(Rc::new("[1,2,3,4]".to_string()));
+        let result = json_array_length(
+            &input,
+            Some(&OwnedValue::build_text(Rc::new("$".to_string()))),
+        )
+        .unwrap();
+        if let OwnedValue::Integer(res) = result {
+            assert_eq!(res, 4);
+        } else {
+            panic!("Expected OwnedValue::Integer");
+        }
+    }
+
+    #[test]
+    fn test_json_array_length_not_array() {
+        let input = OwnedValue::build_text(Rc::new("{one: [1,2,3,4]}".to_string()));
+        let result = json_array_length(&input, None).unwrap();
+        if let OwnedValue::Integer(res) = result {
+            assert_eq!(res, 0);
+        } else {
+            panic!("Expected OwnedValue::Integer");
+        }
+    }
+
+    #[test]
+    fn test_json_array_length_via_prop() {
+        let input = OwnedValue::build_text(Rc::new("{one: [1,2,3,4]}".to_string()));
+        let result = json_array_length(
+            &input,
+            Some(&OwnedValue::build_text(Rc::new("$.one".to_string()))),
+        )
+        .unwrap();
+        if let OwnedValue::Integer(res) = result {
+            assert_eq!(res, 4);
+        } else {
+            panic!("Expected OwnedValue::Integer");
+        }
+    }
+
+    #[test]
+    fn test_json_array_length_via_index() {
+        let input = OwnedValue::build_text(Rc::new("[[1,2,3,4]]".to_string()));
+        let result = json_array_length(
+            &input,
+            Some(&OwnedValue::build_text(Rc::new("$[0]".to_string()))),
+        )
+        .unwrap();
+        if let OwnedValue::Integer(res) = result {
+            assert_eq!(res, 4);
+        } else {
+            panic!("Expected OwnedValue::Integer");
+        }
+    }
+
+    #[test]
+    fn test_json_array_length_via_index_not_array() {
+        let input = OwnedValue::build_text(Rc::new("[1,2,3,4]".to_string()));
+        let result = json_array_length(
+            &input,
+            Some(&OwnedValue::build_text(Rc::new("$[2]".to_string()))),
+        )
+        .unwrap();
+        if let OwnedValue::Integer(res) = result {
+            assert_eq!(res, 0);
+        } else {
+            panic!("Expected OwnedValue::Integer");
+        }
+    }
+
+    #[test]
+    fn test_json_array_length_via_index_bad_prop() {
+        let input = OwnedValue::build_text(Rc::new("{one: [1,2,3,4]}".to_string()));
+        let result = json_array_length(
+            &input,
+            Some(&OwnedValue::build_text(Rc::new("$.two".to_string()))),
+        )
+        .unwrap();
+        assert_eq!(OwnedValue::Null, result);
+    }
+
+    #[test]
+    fn test_json_array_length_scalar_at_path() {
+        // SQLite counts a non-array as 0 elements; only a path that doesn't resolve gives NULL.
+        let input = OwnedValue::build_text(Rc::new(r#"{"a":1,"b":null}"#.to_string()));
+
+        for path in ["$.a", "$.b"] {
+            let path = OwnedValue::build_text(Rc::new(path.to_string()));
+            let result = json_array_length(&input, Some(&path)).unwrap();
+            assert_eq!(result, OwnedValue::Integer(0));
+        }
+    }
+
+    #[test]
+    fn test_json_array_length_object_at_path() {
+        let input = OwnedValue::build_text(Rc::new(r#"{"a":{"b":[1,2]}}"#.to_string()));
+        let path = OwnedValue::build_text(Rc::new("$.a".to_string()));
+
+        let result = json_array_length(&input, Some(&path)).unwrap();
+        assert_eq!(result, OwnedValue::Integer(0));
+    }
+
+    #[test]
+    fn test_json_array_length_from_end() {
+        let input = OwnedValue::build_text(Rc::new("[1,2,[3,4]]".to_string()));
+        let path = OwnedValue::build_text(Rc::new("$[#-1]".to_string()));
+
+        let result = json_array_length(&input, Some(&path)).unwrap();
+        assert_eq!(result, OwnedValue::Integer(2));
+    }
+
+    #[test]
+    fn test_json_array_length_null() {
+        let result = json_array_length(&OwnedValue::Null, None).unwrap();
+        assert_eq!(result, OwnedValue::Null);
+
+        let input = OwnedValue::build_text(Rc::new("[1]".to_string()));
+        let result = json_array_length(&input, Some(&OwnedValue::Null)).unwrap();
+        assert_eq!(result, OwnedValue::Null);
+    }
+
+    #[test]
+    fn test_json_array_length_non_text_path() {
+        let input = OwnedValue::build_text(Rc::new("[1,2,3]".to_string()));
+        for path in [OwnedValue::Integer(2), OwnedValue::Float(2.5)] {
+            match json_array_length(&input, Some(&path)) {
+                Ok(result) => panic!("Expected JSON path error, got: {:?}", result),
+                Err(e) => assert!(e.to_string().contains("JSON path error")),
+            }
+        }
+    }
+
+    #[test]
+    fn test_json_array_length_simple_json_subtype() {
+        let input = OwnedValue::build_text(Rc::new("[1,2,3]".to_string()));
+        let wrapped = get_json(&input).unwrap();
+        let result = json_array_length(&wrapped, None).unwrap();
+
+        if let OwnedValue::Integer(res) = result {
+            assert_eq!(res, 3);
+        } else {
+            panic!("Expected OwnedValue::Integer");
+        }
+    }
+
+    #[test]
+    fn test_json_array_length_jsonb() {
+        let text = |s: &str| OwnedValue::build_text(Rc::new(s.to_string()));
+        let fixture = get_jsonb(&text(r#"[1,[2,3],{"a":[4,5,6],"s":"x"},[]]"#)).unwrap();
+        assert!(matches!(fixture, OwnedValue::Blob(_)));
+
+        let cases = [
+            (None, OwnedValue::Integer(4)),
+            (Some("$"), OwnedValue::Integer(4)),
+            (Some("$[1]"), OwnedValue::Integer(2)),
+            (Some("$[#-1]"), OwnedValue::Integer(0)),
+            (Some("$[2].a"), OwnedValue::Integer(3)),
+            // Paths that don't lead to an array go through the general path.
+            (Some("$[0]"), OwnedValue::Integer(0)),
+            (Some("$[2]"), OwnedValue::Integer(0)),
+            (Some("$[2].s"), OwnedValue::Integer(0)),
+            (Some("$[9]"), OwnedValue::Null),
+            (Some("$[2].b"), OwnedValue::Null),
+        ];
+        for (path, expected) in cases {
+            let path = path.map(text);
+            assert_eq!(
+                json_array_length(&fixture, path.as_ref()).unwrap(),
+                expected,
+                "{:?}",
+                path
+            );
+        }
+        assert!(json_array_length(&fixture, Some(&text("$["))).is_err());
+    }
+
+    #[test]
+    fn test_json_array_length_jsonb_large() {
+        let items = (0..10_000).map(|i| i.to_string()).collect::<Vec<_>>();
+        let input = OwnedValue::build_text(Rc::new(format!("[{}]", items.join(","))));
+        let OwnedValue::Blob(blob) = get_jsonb(&input).unwrap() else {
+            panic!("Expected OwnedValue::Blob");
+        };
+        assert_eq!(jsonb_array_length(&blob, None).unwrap(), Some(10_000));
+        assert_eq!(
+            json_array_length(&OwnedValue::Blob(blob), None).unwrap(),
+            OwnedValue::Integer(10_000)
+        );
+    }
+
+    #[test]
+    fn test_val_accessors() {
+        let json =
+            r#"{"s":"x","i":-3,"big":9223372036854775808,"f":1.5e0,"b":true,"n":null,"a":[1,[2]]}"#;
+        let val = from_str::<Val>(json).unwrap();
+        let members = val.as_object().unwrap();
+        assert_eq!(members.len(), 7);
+
+        assert_eq!(members["s"].as_str(), Some("x"));
+        assert_eq!(members["i"].as_i64(), Some(-3));
+        assert_eq!(members["i"].as_f64(), Some(-3.0));
+        assert_eq!(members["big"].as_i64(), None);
+        assert_eq!(members["big"].as_f64(), Some(9223372036854775808.0));
+        assert_eq!(members["f"].as_i64(), None);
+        assert_eq!(members["f"].as_f64(), Some(1.5));
+        assert_eq!(members["b"].as_bool(), Some(true));
+        assert!(members["n"].is_null());
+        let items = members["a"].as_array().unwrap();
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[1].as_array().map(<[Val]>::len), Some(1));
+
+        // Each accessor only answers for its own type.
+        let s = &members["s"];
+        assert_eq!(s.as_i64(), None);
+        assert_eq!(s.as_f64(), None);
+        assert_eq!(s.as_bool(), None);
+        assert!(s.as_array().is_none());
+        assert!(s.as_object().is_none());
+        assert!(!s.is_null());
+        assert_eq!(members["i"].as_str(), None);
+        assert!(val.as_array().is_none());
+        assert_eq!(Val::RawJson("\"x\"".into()).as_str(), None);
+    }
+
+    #[test]
+    fn test_array_cursor() {
+        let collect = |json| ArrayCursor::new(json).collect::<Vec<_>>();
+
+        let items = collect(r#" [1, "a\"]", [2, {"b": [3]}], {}, null ] "#);
+        let expected = from_str::<Val>(r#"[1,"a\"]",[2,{"b":[3]}],{},null]"#).unwrap();
+        let items: Vec<_> = items.into_iter().map(Result::unwrap).collect();
+        assert_eq!(Val::Array(items), expected);
+        assert!(collect("[]").is_empty());
+        assert!(collect(" [ ]\n").is_empty());
+
+        // Elements before a malformed one are yielded, then the error parsing the whole text
+        // gives, then nothing.
+        for (json, yielded) in [
+            ("[1, [2], [3 4], 5]", 2),
+            ("[1, 2, x, 4]", 2),
+            (r#"[1, "\uD800", 2]"#, 1),
+            ("[1, 2] 3", 1),
+            ("[1, 2", 1),
+        ] {
+            let mut cursor = ArrayCursor::new(json);
+            for _ in 0..yielded {
+                assert!(matches!(cursor.next(), Some(Ok(_))), "{}", json);
+            }
+            let expected = from_str::<Val>(json).unwrap_err();
+            assert_eq!(cursor.next(), Some(Err(expected)), "{}", json);
+            assert!(cursor.next().is_none(), "{}", json);
+        }
+
+        // JSON5 is parsed whole when the scan reaches it, and the elements left come from that.
+        let items: Vec<_> = collect("[1, 2, {a: 3}, 'x', [4,],]")
+            .into_iter()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(
+            Val::Array(items),
+            from_str::<Val>(r#"[1,2,{"a":3},"x",[4]]"#).unwrap()
+        );
+
+        for json in ["{}", "1", "", "x"] {
+            assert!(matches!(collect(json).as_slice(), [Err(_)]), "{}", json);
+        }
+    }
+
+    #[test]
+    fn test_json_array_length_scanned() {
+        let cases = [
+            ("[]", 0),
+            (" [ ] ", 0),
+            ("[1]", 1),
+            (r#"[1,"a",null,true,-2.5e3]"#, 5),
+            (r#"[[1,2,[3]],{"a":[4,5],"b":{"c":6}},[]]"#, 3),
+            (r#"["[1,2]","{\"a\":[",",\\",{"]":"["}]"#, 4),
+            ("[\n  [1, 2],\n  {\"a\": 3}\n]\n", 2),
+        ];
+        for (json, expected) in cases {
+            assert_eq!(strict_array_length(json), Some(expected), "{}", json);
+            let input = OwnedValue::build_text(Rc::new(json.to_string()));
+            assert_eq!(
+                json_array_length(&input, None).unwrap(),
+                OwnedValue::Integer(expected as i64)
+            );
+        }
+
+        // JSON5 and anything else that the scanner doesn't accept takes the general path.
+        for (json, expected) in [("[1,2,]", 2), ("[0x1, /* 2 */ 3]", 2), (r#"{"a":[1]}"#, 0)] {
+            assert_eq!(strict_array_length(json), None, "{}", json);
+            let input = OwnedValue::build_text(Rc::new(json.to_string()));
+            assert_eq!(
+                json_array_length(&input, None).unwrap(),
+                OwnedValue::Integer(expected)
+            );
+        }
+        for json in ["[1,2", "[1,2]]", "[1 2]", "[[1,2],", r#"["a]"#, "[1e999]"] {
+            assert_eq!(strict_array_length(json), None, "{}", json);
+            let input = OwnedValue::build_text(Rc::new(json.to_string()));
+            assert!(json_array_length(&input, None).is_err(), "{}", json);
+        }
+    }
+
+    #[test]
+    fn test_json_array_length_blob_with_json_text() {
+        let blob = OwnedValue::Blob(Rc::new(b"[1,2]".to_vec()));
+        assert_eq!(
+            json_array_length(&blob, None).unwrap(),
+            OwnedValue::Integer(2)
+        );
+    }
+
+    #[test]
+    fn test_json_keys() {
+        let input = OwnedValue::build_text(Rc::new(
+            r#"{"z":1,"a":{"y":[1],"b":null,"x":{}}}"#.to_string(),
+        ));
+
+        let OwnedValue::Text(result) = json_keys(&input, None).unwrap() else {
+            panic!("Expected OwnedValue::Text");
+        };
+        assert_eq!(result.value.as_str(), r#"["z","a"]"#);
+        assert_eq!(result.subtype, TextSubtype::Json);
+
+        let path = OwnedValue::build_text(Rc::new("$.a".to_string()));
+        let OwnedValue::Text(result) = json_keys(&input, Some(&path)).unwrap() else {
+            panic!("Expected OwnedValue::Text");
+        };
+        assert_eq!(result.value.as_str(), r#"["y","b","x"]"#);
+
+        let path = OwnedValue::build_text(Rc::new("$.a.x".to_string()));
+        let OwnedValue::Text(result) = json_keys(&input, Some(&path)).unwrap() else {
+            panic!("Expected OwnedValue::Text");
+        };
+        assert_eq!(result.value.as_str(), "[]");
+    }
+
+    #[test]
+    fn test_json_keys_not_object() {
+        let input = OwnedValue::build_text(Rc::new(r#"{"a":{"b":[1,2],"c":"d"}}"#.to_string()));
+        for path in ["$.a.b", "$.a.c", "$.a.missing"] {
+            let path = OwnedValue::build_text(Rc::new(path.to_string()));
+            assert_eq!(json_keys(&input, Some(&path)).unwrap(), OwnedValue::Null);
+        }
+
+        let input = OwnedValue::build_text(Rc::new("[1,2]".to_string()));
+        assert_eq!(json_keys(&input, None).unwrap(), OwnedValue::Null);
+        assert_eq!(
+            json_keys(&OwnedValue::Null, None).unwrap(),
+            OwnedValue::Null
+        );
+    }
+
+    #[test]
+    fn test_json_values() {
+        let input = OwnedValue::build_text(Rc::new(
+            r#"{"z":1,"a":{"y":[1,"s"],"b":null,"x":{"k":1.5}}}"#.to_string(),
+        ));
+
+        let OwnedValue::Text(result) = json_values(&input, None).unwrap() else {
+            panic!("Expected OwnedValue::Text");
+        };
+        assert_eq!(
+            result.value.as_str(),
+            r#"[1,{"y":[1,"s"],"b":null,"x":{"k":1.5}}]"#
+        );
+        assert_eq!(result.subtype, TextSubtype::Json);
+
+        let path = OwnedValue::build_text(Rc::new("$.a".to_string()));
+        let OwnedValue::Text(result) = json_values(&input, Some(&path)).unwrap() else {
+            panic!("Expected OwnedValue::Text");
+        };
+        assert_eq!(result.value.as_str(), r#"[[1,"s"],null,{"k":1.5}]"#);
+    }
+
+    #[test]
+    fn test_json_values_array() {
+        let input = OwnedValue::build_text(Rc::new(r#"{"a":[1,"s",[true],{}]}"#.to_string()));
+        let path = OwnedValue::build_text(Rc::new("$.a".to_string()));
+        let OwnedValue::Text(result) = json_values(&input, Some(&path)).unwrap() else {
+            panic!("Expected OwnedValue::Text");
+        };
+        assert_eq!(result.value.as_str(), r#"[1,"s",[true],{}]"#);
+
+        let array = OwnedValue::build_text(Rc::new(r#"[1,{"a":2}]"#.to_string()));
+        let OwnedValue::Text(result) = json_values(&array, None).unwrap() else {
+            panic!("Expected OwnedValue::Text");
+        };
+        assert_eq!(result.value.as_str(), r#"[1,{"a":2}]"#);
+
+        for path in ["$.a[0]", "$.a[1]", "$.missing"] {
+            let path = OwnedValue::build_text(Rc::new(path.to_string()));
+            assert_eq!(json_values(&input, Some(&path)).unwrap(), OwnedValue::Null);
+        }
+    }
+
+    #[test]
+    fn test_get_json_blob_with_json_text() {
+        let cases = [
+            (r#"{"a":1}"#, r#"{"a":1}"#),
+            ("{a: [1, 2,],}", r#"{"a":[1,2]}"#),
+        ];
+        for (input, expected) in cases {
+            let input = OwnedValue::Blob(Rc::new(input.as_bytes().to_vec()));
+            let OwnedValue::Text(result) = get_json(&input).unwrap() else {
+                panic!("Expected OwnedValue::Text");
+            };
+            assert_eq!(result.value.as_str(), expected);
+        }
+
+        let input = OwnedValue::Blob(Rc::new(b"{a: 1}".to_vec()));
+        let path = OwnedValue::build_text(Rc::new("$.a".to_string()));
+        assert_eq!(
+            json_type(&input, Some(&path)).unwrap(),
+            OwnedValue::build_text(Rc::new("integer".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_get_json_blob_corrupt() {
+        for input in [vec![0xff, 0xfe, 0x00], b"{a: }".to_vec(), vec![]] {
+            let input = OwnedValue::Blob(Rc::new(input));
+            match get_json(&input) {
+                Ok(_) => panic!("Expected error for malformed JSON"),
+                Err(e) => assert!(e.to_string().contains("malformed JSON")),
+            }
+        }
+    }
+
+    #[test]
+    fn test_get_json_value_cached_reuses_parsed_value() {
+        let doc = OwnedValue::build_text(Rc::new("{\"a\":1,\"b\":[2,3]}".to_string()));
+        let same_text = OwnedValue::build_text(Rc::new("{\"a\":1,\"b\":[2,3]}".to_string()));
+
+        let first = get_json_value_cached(&doc).unwrap();
+        let second = get_json_value_cached(&same_text).unwrap();
+        assert!(Rc::ptr_eq(&first, &second));
+
+        let result = json_extract(
+            &doc,
+            &[OwnedValue::build_text(Rc::new("$.b[1]".to_string()))],
+        );
+        assert_eq!(result.unwrap(), OwnedValue::Integer(3));
+    }
+
+    #[test]
+    fn test_json_extract_malformed_json_subtype() {
+        let doc = OwnedValue::Text(LimboText::json(Rc::new("{\"a\":".to_string())));
+        let path = OwnedValue::build_text(Rc::new("$.a".to_string()));
+        for _ in 0..2 {
+            match json_extract(&doc, std::slice::from_ref(&path)) {
+                Ok(_) => panic!("Expected error for malformed JSON"),
+                Err(e) => assert!(e.to_string().contains("malformed JSON")),
+            }
+        }
+    }
+
+    fn jsonb_blob(json: &str) -> OwnedValue {
+        OwnedValue::Blob(Rc::new(
+            jsonb::parse_value(json.as_bytes()).unwrap().to_vec(),
+        ))
+    }
+
+    #[test]
+    fn test_jsonb_extract_single_path() {
+        let blob = jsonb_blob(r#"{"a":{"b":[1,2,"x"]},"c":1.5}"#);
+        let path = |p: &str| OwnedValue::build_text(Rc::new(p.to_string()));
+
+        assert_eq!(
+            jsonb_extract(&blob, &[path("$.a.b[1]")]).unwrap(),
+            OwnedValue::Integer(2)
+        );
+        assert_eq!(
+            jsonb_extract(&blob, &[path("$.a.b[#-1]")]).unwrap(),
+            OwnedValue::build_text(Rc::new("x".to_string()))
+        );
+        assert_eq!(
+            jsonb_extract(&blob, &[path("$.c")]).unwrap(),
+            OwnedValue::Float(1.5)
+        );
+        assert_eq!(
+            jsonb_extract(&blob, &[path("$.missing")]).unwrap(),
+            OwnedValue::Null
+        );
+        assert_eq!(
+            jsonb_extract(&blob, &[path("$.a.b[3]")]).unwrap(),
+            OwnedValue::Null
+        );
+
+        assert_eq!(
+            json_extract(&blob, &[path("$.a")]).unwrap(),
+            jsonb_blob(r#"{"b":[1,2,"x"]}"#)
+        );
+    }
+
+    #[test]
+    fn test_json_extract_jsonb_container() {
+        let doc = r#"{"a":{"b":[1,{"c":null}],"d":"e"},"f":[]}"#;
+        let blob = jsonb_blob(doc);
+        let path = |p: &str| OwnedValue::build_text(Rc::new(p.to_string()));
+        let extract_jsonb = |p: &str| match json_extract(&blob, &[path(p)]).unwrap() {
+            OwnedValue::Blob(b) => {
+                assert!(is_jsonb(&b), "{}", p);
+                b
+            }
+            other => panic!("Expected OwnedValue::Blob for {}, got {:?}", p, other),
+        };
+        let as_text = |b: Rc<Vec<u8>>| get_json(&OwnedValue::Blob(b)).unwrap().to_string();
+
+        assert_eq!(
+            as_text(extract_jsonb("$.a")),
+            r#"{"b":[1,{"c":null}],"d":"e"}"#
+        );
+        assert_eq!(as_text(extract_jsonb("$.a.b")), r#"[1,{"c":null}]"#);
+        assert_eq!(as_text(extract_jsonb("$.a.b[#-1]")), r#"{"c":null}"#);
+        assert_eq!(as_text(extract_jsonb("$.f")), "[]");
+        // The root is the document itself, which isn't copied.
+        let OwnedValue::Blob(input) = &blob else {
+            unreachable!()
+        };
+        assert!(Rc::ptr_eq(&extract_jsonb("$"), input));
+
+        // Scalars are still SQL values, and several paths still give a text array.
+        assert_eq!(
+            json_extract(&blob, &[path("$.a.d")]).unwrap(),
+            OwnedValue::build_text(Rc::new("e".to_string()))
+        );
+        assert_eq!(
+            json_extract(&blob, &[path("$.a.b[1].c")]).unwrap(),
+            OwnedValue::Null
+        );
+        let OwnedValue::Text(text) = json_extract(&blob, &[path("$.f"), path("$.a.d")]).unwrap()
+        else {
+            panic!("Expected OwnedValue::Text");
+        };
+        assert_eq!(text.value.as_str(), r#"[[],"e"]"#);
+
+        // JSON text stored in a blob gives text, like JSON text.
+        let text_blob = OwnedValue::Blob(Rc::new(doc.as_bytes().to_vec()));
+        let OwnedValue::Text(text) = json_extract(&text_blob, &[path("$.a.b")]).unwrap() else {
+            panic!("Expected OwnedValue::Text");
+        };
+        assert_eq!(text.value.as_str(), r#"[1,{"c":null}]"#);
+        assert_eq!(text.subtype, TextSubtype::Json);
+    }
+
+    #[test]
+    fn test_jsonb_extract_multiple_paths() {
+        let blob = jsonb_blob(r#"{"a":[1,2],"b":"x"}"#);
+        let paths = [
+            OwnedValue::build_text(Rc::new("$.a".to_string())),
+            OwnedValue::build_text(Rc::new("$.b".to_string())),
+            OwnedValue::build_text(Rc::new("$.c".to_string())),
+        ];
+
+        let OwnedValue::Text(text) = json_extract(&blob, &paths).unwrap() else {
+            panic!("Expected OwnedValue::Text");
+        };
+        assert_eq!(text.value.as_str(), r#"[[1,2],"x",null]"#);
+    }
+
+    #[test]
+    fn test_json_query_wildcard() {
+        let json = OwnedValue::build_text(Rc::new(r#"{"a":1,"b":[2,{"c":3}]}"#.to_string()));
+        let path = |p: &str| OwnedValue::build_text(Rc::new(p.to_string()));
+
+        let OwnedValue::Text(text) = json_query(&json, &[path("$.*")]).unwrap() else {
+            panic!("Expected OwnedValue::Text");
+        };
+        assert_eq!(text.value.as_str(), r#"[1,[2,{"c":3}]]"#);
+
+        let OwnedValue::Text(text) = json_query(&json, &[path("$.b[*]")]).unwrap() else {
+            panic!("Expected OwnedValue::Text");
+        };
+        assert_eq!(text.value.as_str(), r#"[2,{"c":3}]"#);
+
+        assert_eq!(
+            json_query(&json, &[path("$.*[1][1].c")]).unwrap(),
+            OwnedValue::Integer(3)
+        );
+        assert_eq!(
+            json_query(&json, &[path("$.a.*")]).unwrap(),
+            OwnedValue::Null
+        );
+        let OwnedValue::Text(text) = json_query(&json, &[path("$.a"), path("$.b[*]")]).unwrap()
+        else {
+            panic!("Expected OwnedValue::Text");
+        };
+        assert_eq!(text.value.as_str(), r#"[1,[2,{"c":3}]]"#);
+
+        // Like in SQLite, json_extract rejects wildcards.
+        for p in ["$.*", "$.b[*]"] {
+            assert!(json_extract(&json, &[path(p)]).is_err(), "{}", p);
+        }
+    }
+
+    #[test]
+    fn test_json_query_jsonb_wildcard() {
+        let blob = jsonb_blob(r#"{"a":{"x":1,"y":2},"f":[]}"#);
+        let path = |p: &str| OwnedValue::build_text(Rc::new(p.to_string()));
+
+        assert_eq!(
+            json_query(&blob, &[path("$.a.*")]).unwrap(),
+            jsonb_blob("[1,2]")
+        );
+        assert_eq!(
+            json_query(&blob, &[path("$.*")]).unwrap(),
+            jsonb_blob(r#"[{"x":1,"y":2},[]]"#)
+        );
+        assert!(jsonb_extract(&blob, &[path("$.a.*")]).is_err());
+    }
+
+    #[test]
+    fn test_json_set_rejects_wildcard() {
+        let json = OwnedValue::build_text(Rc::new(r#"{"a":1}"#.to_string()));
+        let args = [
+            OwnedValue::build_text(Rc::new("$.*".to_string())),
+            OwnedValue::Integer(2),
+        ];
+        match json_set(&json, &args) {
+            Ok(_) => panic!("Expected error for wildcard path"),
+            Err(e) => assert!(e.to_string().contains("JSON path error")),
+        }
+    }
+
+    #[test]
+    fn test_array_index_overflow() {
+        let json = OwnedValue::build_text(Rc::new("[1,2,3]".to_string()));
+        let path = |p: &str| OwnedValue::build_text(Rc::new(p.to_string()));
+        for p in [
+            "$[9223372036854775807]",
+            "$[99999999999999999999]",
+            "$[#-9223372036854775807]",
+            "$[#-99999999999999999999]",
+            "$[9223372036854775808]",
+            // SQLite truncates these to 32 bits, reading an element of the array instead.
+            "$[4294967296]",
+            "$[4294967297]",
+            "$[#-4294967297]",
+            "$[#-4294967298]",
+        ] {
+            assert_eq!(
+                json_extract(&json, &[path(p)]).unwrap(),
+                OwnedValue::Null,
+                "{}",
+                p
+            );
+        }
+        // Integer paths of the -> operators, which used to be truncated to 32 bits.
+        for idx in [i64::MIN, i64::MAX, 1 << 32, -(1 << 32)] {
+            assert_eq!(
+                json_arrow_shift_extract(&json, &OwnedValue::Integer(idx)).unwrap(),
+                OwnedValue::Null,
+                "{}",
+                idx
+            );
+        }
+        assert_eq!(
+            json_arrow_shift_extract(&json, &OwnedValue::Integer(-1)).unwrap(),
+            OwnedValue::Integer(3)
+        );
+
+        let len = usize::MAX;
+        let index = |element| resolve_array_index(&element, len);
+        assert_eq!(
+            index(PathElement::ArrayLocator(i64::MIN)),
+            Some(len - (1 << 63))
+        );
+        assert_eq!(
+            index(PathElement::ArrayLocator(i64::MAX)),
+            Some(i64::MAX as usize)
+        );
+        assert_eq!(index(PathElement::ArrayLocator(-1)), Some(len - 1));
+        assert_eq!(
+            index(PathElement::ArrayLocatorFromEnd(i64::MAX)),
+            Some(len - i64::MAX as usize)
+        );
+        assert_eq!(
+            resolve_array_index(&PathElement::ArrayLocator(i64::MIN), 3),
+            None
+        );
+        assert_eq!(
+            resolve_array_index(&PathElement::ArrayLocatorFromEnd(i64::MAX), 3),
+            None
+        );
+
+        assert_eq!(resolve_slice(Some(i64::MIN), Some(i64::MAX), 3), 0..3);
+        assert_eq!(resolve_slice(Some(i64::MAX), None, 3), 3..3);
+        assert_eq!(
+            resolve_slice(Some(i64::MIN), Some(-1), len),
+            len - (1 << 63)..len - 1
+        );
+        assert_eq!(
+            resolve_slice(Some(1), Some(i64::MAX), len),
+            1..i64::MAX as usize
+        );
+        assert_eq!(resolve_slice(Some(-2), None, len), len - 2..len);
+    }
+
+    #[test]
+    fn test_json_query_slice() {
+        let json = OwnedValue::build_text(Rc::new(r#"{"a":[1,2,3,4],"b":{"c":5}}"#.to_string()));
+        let path = |p: &str| OwnedValue::build_text(Rc::new(p.to_string()));
+        let extract = |p: &str| match json_query(&json, &[path(p)]).unwrap() {
+            OwnedValue::Text(text) => text.value.to_string(),
+            other => panic!("Expected OwnedValue::Text for {}, got {:?}", p, other),
+        };
+
+        assert_eq!(extract("$.a[0:2]"), "[1,2]");
+        assert_eq!(extract("$.a[1:]"), "[2,3,4]");
+        assert_eq!(extract("$.a[:-1]"), "[1,2,3]");
+        assert_eq!(extract("$.a[-2:]"), "[3,4]");
+        assert_eq!(extract("$.a[:]"), "[1,2,3,4]");
+        // Out of range bounds are clamped rather than an error.
+        assert_eq!(extract("$.a[2:100]"), "[3,4]");
+        assert_eq!(extract("$.a[-100:1]"), "[1]");
+        assert_eq!(extract("$.a[10:]"), "[]");
+        assert_eq!(extract("$.a[3:1]"), "[]");
+
+        assert_eq!(
+            json_query(&json, &[path("$.a[1:][0]")]).unwrap(),
+            OwnedValue::Integer(2)
+        );
+        assert_eq!(
+            json_query(&json, &[path("$.b[0:1]")]).unwrap(),
+            OwnedValue::Null
+        );
+
+        let blob = jsonb_blob(r#"{"a":[1,2,3,4]}"#);
+        assert_eq!(
+            json_query(&blob, &[path("$.a[1:3]")]).unwrap(),
+            jsonb_blob("[2,3]")
+        );
+
+        // Like in SQLite, the other functions reject slices.
+        assert!(json_extract(&json, &[path("$.a[0:2]")]).is_err());
+        assert!(jsonb_extract(&blob, &[path("$.a[1:3]")]).is_err());
+        match json_set(&json, &[path("$.a[0:1]"), OwnedValue::Integer(2)]) {
+            Ok(_) => panic!("Expected error for slice path"),
+            Err(e) => assert!(e.to_string().contains("JSON path error")),
+        }
+    }
+
+    #[test]
+    fn test_json_extract_multiple_paths_shape() {
+        let json = OwnedValue::build_text(Rc::new(r#"{"a":[],"b":"","c":{}}"#.to_string()));
+        let paths =
+            ["$.a", "$.b", "$.c", "$.d"].map(|p| OwnedValue::build_text(Rc::new(p.to_string())));
+
+        let OwnedValue::Text(text) = json_extract(&json, &paths).unwrap() else {
+            panic!("Expected OwnedValue::Text");
+        };
+        assert_eq!(text.value.as_str(), r#"[[],"",{},null]"#);
+        assert_eq!(text.subtype, TextSubtype::Json);
+    }
+
+    #[test]
+    fn test_json_extract_multiple_paths_with_missing_path() {
+        let json = OwnedValue::build_text(Rc::new(r#"{"a":"x,]","c":[1,[2]]}"#.to_string()));
+        let path = |p: &str| OwnedValue::build_text(Rc::new(p.to_string()));
+
+        let cases = [
+            (vec!["$.a", "$.b", "$.c"], r#"["x,]",null,[1,[2]]]"#),
+            (vec!["$.b", "$.a"], r#"[null,"x,]"]"#),
+            (vec!["$.a", "$.b"], r#"["x,]",null]"#),
+            (vec!["$.b", "$.d"], "[null,null]"),
+        ];
+        for (paths, expected) in cases {
+            let paths: Vec<_> = paths.into_iter().map(path).collect();
+            let OwnedValue::Text(text) = json_extract(&json, &paths).unwrap() else {
+                panic!("Expected OwnedValue::Text");
+            };
+            assert_eq!(text.value.as_str(), expected);
+        }
+    }
+
+    #[test]
+    fn test_json_extract_missing_path() {
+        let result = json_extract(
+            &OwnedValue::build_text(Rc::new("{\"a\":2}".to_string())),
+            &[OwnedValue::build_text(Rc::new("$.x".to_string()))],
+        );
+
+        match result {
+            Ok(OwnedValue::Null) => (),
+            _ => panic!("Expected null result, got: {:?}", result),
+        }
+    }
+    #[test]
+    fn test_json_extract_non_finite_numbers() {
+        let text = |s: &str| OwnedValue::build_text(Rc::new(s.to_string()));
+        let source = text(r#"{"a":Infinity,"b":-Infinity,"c":NaN,"d":[NaN]}"#);
+        let written = get_json(&source).unwrap();
+        assert_eq!(
+            written.to_string(),
+            r#"{"a":9e999,"b":-9e999,"c":null,"d":[null]}"#
+        );
+
+        // The same values come out of the JSON5 source, what get_json wrote and JSONB.
+        for json in [source.clone(), written.clone(), get_jsonb(&source).unwrap()] {
+            let extract = |p: &str| json_extract(&json, &[text(p)]).unwrap();
+            assert_eq!(extract("$.a"), OwnedValue::Float(f64::INFINITY));
+            assert_eq!(extract("$.b"), OwnedValue::Float(f64::NEG_INFINITY));
+            assert_eq!(extract("$.c"), OwnedValue::Null);
+            assert_eq!(extract("$.d[0]"), OwnedValue::Null);
+            assert_eq!(
+                json_arrow_shift_extract(&json, &text("a")).unwrap(),
+                OwnedValue::Float(f64::INFINITY)
+            );
+            assert_eq!(json_type(&json, Some(&text("$.b"))).unwrap(), text("real"));
+        }
+        assert_eq!(get_json(&written).unwrap().to_string(), written.to_string());
+        assert_eq!(
+            json_extract(&text("[1, -9e999]"), &[text("$[1]")]).unwrap(),
+            OwnedValue::Float(f64::NEG_INFINITY)
+        );
+
+        // Other reals too large for a double are still an error.
+        for json in ["[1e999]", "[9.0e999]", "[9E999]", "[-1e9999]"] {
+            assert!(get_json(&text(json)).is_err(), "{}", json);
+        }
+    }
+
+    #[test]
+    fn test_json_extract_consecutive_array_indices() {
+        let text = |s: &str| OwnedValue::build_text(Rc::new(s.to_string()));
+        let matrix = text("[[1,2],[3,4]]");
+        let doc = text(r#"{"a":[{"b":[[5,6],[7,[8,9]]]}]}"#);
+        for (json, path, expected) in [
+            (&matrix, "$[1][0]", OwnedValue::Integer(3)),
+            (&matrix, "$[0][#-1]", OwnedValue::Integer(2)),
+            (&matrix, "$[1][2]", OwnedValue::Null),
+            (&matrix, "$[0][0][0]", OwnedValue::Null),
+            (&doc, "$.a[0].b[1][1][0]", OwnedValue::Integer(8)),
+            (&doc, "$.a[0].b[#-1][#-1][#-1]", OwnedValue::Integer(9)),
+            (&doc, "$.a[0].b[0].c", OwnedValue::Null),
+        ] {
+            for json in [json.clone(), get_jsonb(json).unwrap()] {
+                assert_eq!(
+                    json_extract(&json, &[text(path)]).unwrap(),
+                    expected,
+                    "{}",
+                    path
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_json_extract_numeric_key() {
+        let text = |s: &str| OwnedValue::build_text(Rc::new(s.to_string()));
+        let extract = |json: &str, path: &str| json_extract(&text(json), &[text(path)]).unwrap();
+
+        assert_eq!(extract(r#"{"123":"x"}"#, "$.123"), text("x"));
+        assert_eq!(
+            extract(r#"{"0abc":"y","1":{"2":"z"}}"#, "$.0abc"),
+            text("y")
+        );
+        assert_eq!(extract(r#"{"0abc":"y","1":{"2":"z"}}"#, "$.1.2"), text("z"));
+        // A key is never an array index, nor the other way around.
+        assert_eq!(extract(r#"["a","b"]"#, "$.1"), OwnedValue::Null);
+        assert_eq!(extract(r#"{"1":"z"}"#, "$[1]"), OwnedValue::Null);
+        assert_eq!(extract(r#"["a","b"]"#, "$[1]"), text("b"));
+    }
+
+    #[test]
+    fn test_json_extract_through_missing_intermediates() {
+        let json = r#"{"a":{"b":null,"n":5,"s":"x","arr":[1,{"c":2}],"obj":{"0":1}}}"#;
+        let paths = [
+            // Through null, a scalar and a missing key
+            "$.a.b.c",
+            "$.a.b[0]",
+            "$.a.n.c",
+            "$.a.s.c",
+            "$.a.s[0]",
+            "$.a.x.y",
+            "$.x.y.z",
+            // An object key on an array, and an array index on an object
+            "$.a.arr.c",
+            "$.a.arr[1].c.d",
+            "$.a.obj[0]",
+            "$.a[0].b",
+        ];
+        let documents = [
+            OwnedValue::build_text(Rc::new(json.to_string())),
+            get_jsonb(&OwnedValue::build_text(Rc::new(json.to_string()))).unwrap(),
+        ];
+        for document in &documents {
+            for path in paths {
+                let path = OwnedValue::build_text(Rc::new(path.to_string()));
+                assert_eq!(
+                    json_extract(document, std::slice::from_ref(&path)).unwrap(),
+                    OwnedValue::Null,
+                    "{:?} in {:?}",
+                    path,
+                    document
+                );
+                assert_eq!(json_type(document, Some(&path)).unwrap(), OwnedValue::Null);
+                assert_eq!(
+                    json_arrow_shift_extract(document, &path).unwrap(),
+                    OwnedValue::Null
+                );
+            }
+        }
+
+        // The paths that do resolve, for comparison.
+        let json = OwnedValue::build_text(Rc::new(json.to_string()));
+        let path = OwnedValue::build_text(Rc::new("$.a.b".to_string()));
+        assert_eq!(
+            json_type(&json, Some(&path)).unwrap(),
+            OwnedValue::build_text(Rc::new("null".to_string()))
+        );
+        let path = OwnedValue::build_text(Rc::new("$.a.arr[1].c".to_string()));
+        assert_eq!(
+            json_extract(&json, &[path]).unwrap(),
+            OwnedValue::Integer(2)
+        );
+    }
+
+    #[test]
+    fn test_json_extract_single_path_sql_types() {
+        let json = OwnedValue::build_text(Rc::new(
+            r#"{"i":1,"f":1.5,"s":"x\"y","t":true,"n":false,"a":[1],"o":{"b":2}}"#.to_string(),
+        ));
+        let extract = |path: &str| {
+            json_extract(&json, &[OwnedValue::build_text(Rc::new(path.to_string()))]).unwrap()
+        };
+
+        assert_eq!(extract("$.i"), OwnedValue::Integer(1));
+        assert_eq!(extract("$.f"), OwnedValue::Float(1.5));
+        assert_eq!(extract("$.t"), OwnedValue::Integer(1));
+        assert_eq!(extract("$.n"), OwnedValue::Integer(0));
+        for (path, expected, subtype) in [
+            ("$.s", "x\"y", TextSubtype::Text),
+            ("$.a", "[1]", TextSubtype::Json),
+            ("$.o", r#"{"b":2}"#, TextSubtype::Json),
+        ] {
+            let OwnedValue::Text(t) = extract(path) else {
+                panic!("Expected OwnedValue::Text for {}", path);
+            };
+            assert_eq!(t.value.as_str(), expected);
+            assert_eq!(t.subtype, subtype);
+        }
+    }
+
+    #[test]
+    fn test_json_extract_top_level_key() {
+        let extract = |json: &str, path: &str| {
+            json_extract(
+                &OwnedValue::build_text(Rc::new(json.to_string())),
+                &[OwnedValue::build_text(Rc::new(path.to_string()))],
+            )
+        };
+
+        let json = r#" { "a" : [1, 2] , "b":{"c" : "x"}, "d":1.50 } "#;
+        assert_eq!(extract(json, "$.a").unwrap().to_string(), "[1,2]");
+        assert_eq!(extract(json, "$.b").unwrap().to_string(), r#"{"c":"x"}"#);
+        assert_eq!(extract(json, "$.d").unwrap(), OwnedValue::Float(1.5));
+        assert_eq!(extract(json, "$.e").unwrap(), OwnedValue::Null);
+        assert_eq!(
+            extract(r#"{"a":1,"b":2,"a":3}"#, "$.a").unwrap(),
+            OwnedValue::Integer(3)
+        );
+        assert_eq!(
+            extract(r#"{"a\"":1,"a":2}"#, "$.\"a\\\"\"").unwrap(),
+            OwnedValue::Integer(1)
+        );
+        assert_eq!(
+            extract(r#"{"\u0061":1}"#, "$.a").unwrap(),
+            OwnedValue::Integer(1)
+        );
+        assert_eq!(extract("[1]", "$.a").unwrap(), OwnedValue::Null);
+        assert_eq!(extract("{}", "$.a").unwrap(), OwnedValue::Null);
+        // JSON5 isn't scanned, but parsed as usual.
+        assert_eq!(extract("{a:1,}", "$.a").unwrap(), OwnedValue::Integer(1));
+        // The whole document has to be well-formed, not just the extracted member.
+        assert!(extract(r#"{"a":1,"b":x}"#, "$.a").is_err());
+        assert!(extract(r#"{"a":1}}"#, "$.a").is_err());
+        assert!(extract(r#"{"a":1,"b":[1}"#, "$.a").is_err());
+    }
+
+    #[test]
+    fn test_json_extract_bare_key_path() {
+        let json = OwnedValue::build_text(Rc::new("{\"a\":1}".to_string()));
+        for path in ["a", "$a", "0"] {
+            let path = OwnedValue::build_text(Rc::new(path.to_string()));
+            match json_extract(&json, &[path]) {
+                Ok(result) => panic!("Expected JSON path error, got: {:?}", result),
+                Err(e) => assert!(e.to_string().contains("JSON path error")),
+            }
+        }
+
+        // Unlike json_extract, the -> and ->> operators accept a bare key.
+        let key = OwnedValue::build_text(Rc::new("a".to_string()));
+        assert_eq!(json_arrow_extract(&json, &key).unwrap().to_string(), "1");
+        assert_eq!(
+            json_arrow_shift_extract(&json, &key).unwrap(),
+            OwnedValue::Integer(1)
+        );
+    }
+
+    #[test]
+    fn test_json_extract_null_path() {
+        let result = json_extract(
+            &OwnedValue::build_text(Rc::new("{\"a\":2}".to_string())),
+            &[OwnedValue::Null],
+        );
+
+        match result {
+            Ok(OwnedValue::Null) => (),
+            _ => panic!("Expected null result, got: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_json_extract_null_path_before_invalid_path() {
+        let result = json_extract(
+            &OwnedValue::build_text(Rc::new("[1]".to_string())),
+            &[
+                OwnedValue::Null,
+                OwnedValue::build_text(Rc::new("x".to_string())),
+            ],
+        );
+        assert_eq!(result.unwrap(), OwnedValue::Null);
+    }
+
+    #[test]
+    fn test_compiled_path_display() {
+        let text = |s: &str| OwnedValue::build_text(Rc::new(s.to_string()));
+        for (path, strict, expected) in [
+            (text("$.a[#-1]"), true, "$.a[#-1]"),
+            (text("a b"), false, r#"$."a b""#),
+            (OwnedValue::Integer(2), false, "$[2]"),
+        ] {
+            let compiled = CompiledPath::new(&path, strict).unwrap().unwrap();
+            assert_eq!(compiled.to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn test_json_extract_compiled_reused_across_documents() {
+        let paths =
+            compile_json_paths(&[OwnedValue::build_text(Rc::new("$.a[1]".to_string()))]).unwrap();
+        for (input, expected) in [
+            (r#"{"a":[1,2]}"#, OwnedValue::Integer(2)),
+            (
+                r#"{"a":[1,"x"]}"#,
+                OwnedValue::build_text(Rc::new("x".to_string())),
+            ),
+            (r#"{"b":1}"#, OwnedValue::Null),
+        ] {
+            let input = OwnedValue::build_text(Rc::new(input.to_string()));
+            assert_eq!(json_extract_compiled(&input, &paths).unwrap(), expected);
+        }
+
+        let blob = get_jsonb(&OwnedValue::build_text(Rc::new(
+            r#"{"a":[1,2]}"#.to_string(),
+        )))
+        .unwrap();
+        assert_eq!(
+            json_extract_compiled(&blob, &paths).unwrap(),
+            OwnedValue::Integer(2)
+        );
+    }
+
+    #[test]
+    fn test_compile_json_paths() {
+        let paths = [
+            OwnedValue::build_text(Rc::new("$.a".to_string())),
+            OwnedValue::Null,
+            OwnedValue::build_text(Rc::new("x".to_string())),
+        ];
+        let compiled = compile_json_paths(&paths).unwrap();
+        assert_eq!(compiled.len(), 2);
+        assert!(compiled[0].is_some());
+        assert!(compiled[1].is_none());
+
+        assert!(compile_json_paths(&paths[2..]).is_err());
+        assert!(CompiledPath::new(&paths[2], false).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_json_arrow_extract_scalar_is_json() {
+        let result = json_arrow_extract(
+            &OwnedValue::build_text(Rc::new(r#"{"a":1}"#.to_string())),
+            &OwnedValue::build_text(Rc::new("$.a".to_string())),
+        )
+        .unwrap();
+
+        let OwnedValue::Text(result) = result else {
+            panic!("Expected OwnedValue::Text");
+        };
+        assert_eq!(result.value.as_str(), "1");
+        assert_eq!(result.subtype, TextSubtype::Json);
+    }
+
+    #[test]
+    fn test_json_arrow_extract_shorthand_paths() {
+        let json = OwnedValue::build_text(Rc::new(r#"{"a":"x","b":[1,2]}"#.to_string()));
+
+        let result = json_arrow_extract(&json, &OwnedValue::build_text(Rc::new("a".to_string())));
+        let Ok(OwnedValue::Text(result)) = result else {
+            panic!("Expected OwnedValue::Text");
+        };
+        assert_eq!(result.value.as_str(), r#""x""#);
+
+        let array =
+            json_arrow_extract(&json, &OwnedValue::build_text(Rc::new("b".to_string()))).unwrap();
+        let result = json_arrow_extract(&array, &OwnedValue::Integer(1));
+        let Ok(OwnedValue::Text(result)) = result else {
+            panic!("Expected OwnedValue::Text");
+        };
+        assert_eq!(result.value.as_str(), "2");
+    }
+
+    #[test]
+    fn test_json_arrow_extract_missing_path_is_json_null() {
+        // SQLite returns SQL NULL here instead.
+        for path in ["$.b", "x"] {
+            let result = json_arrow_extract(
+                &OwnedValue::build_text(Rc::new(r#"{"a":1}"#.to_string())),
+                &OwnedValue::build_text(Rc::new(path.to_string())),
+            )
+            .unwrap();
+
+            let OwnedValue::Text(result) = result else {
+                panic!("Expected OwnedValue::Text");
+            };
+            assert_eq!(result.value.as_str(), "null", "path: {}", path);
+            assert_eq!(result.subtype, TextSubtype::Json);
+        }
+    }
+
+    #[test]
+    fn test_json_arrow_shift_extract_unwraps_scalars() {
+        let json = OwnedValue::build_text(Rc::new(r#"{"a":"x","b":1,"c":[1,2]}"#.to_string()));
+
+        let result =
+            json_arrow_shift_extract(&json, &OwnedValue::build_text(Rc::new("$.a".to_string())));
+        let Ok(OwnedValue::Text(result)) = result else {
+            panic!("Expected OwnedValue::Text");
+        };
+        assert_eq!(result.value.as_str(), "x");
+        assert_eq!(result.subtype, TextSubtype::Text);
+
+        let result =
+            json_arrow_shift_extract(&json, &OwnedValue::build_text(Rc::new("$.b".to_string())));
+        assert_eq!(result.unwrap(), OwnedValue::Integer(1));
+
+        let result =
+            json_arrow_shift_extract(&json, &OwnedValue::build_text(Rc::new("$.c".to_string())));
+        let Ok(OwnedValue::Text(result)) = result else {
+            panic!("Expected OwnedValue::Text");
+        };
+        assert_eq!(result.value.as_str(), "[1,2]");
+    }
+
+    #[test]
+    fn test_json_arrow_shift_extract_shorthand_paths() {
+        let json = OwnedValue::build_text(Rc::new(r#"{"a":"x"}"#.to_string()));
+        let result =
+            json_arrow_shift_extract(&json, &OwnedValue::build_text(Rc::new("a".to_string())));
+        let Ok(OwnedValue::Text(result)) = result else {
+            panic!("Expected OwnedValue::Text");
+        };
+        assert_eq!(result.value.as_str(), "x");
+
+        let json = OwnedValue::build_text(Rc::new("[1,2,3]".to_string()));
+        let result = json_arrow_shift_extract(&json, &OwnedValue::Integer(2));
+        assert_eq!(result.unwrap(), OwnedValue::Integer(3));
+    }
+
+    #[test]
+    fn test_json_arrow_shift_extract_missing_path() {
+        let result = json_arrow_shift_extract(
+            &OwnedValue::build_text(Rc::new(r#"{"a":1}"#.to_string())),
+            &OwnedValue::build_text(Rc::new("$.b".to_string())),
+        );
+
+        assert_eq!(result.unwrap(), OwnedValue::Null);
+    }
+
+    #[test]
+    fn test_json_extract_quoted_key() {
+        let result = json_extract(
+            &OwnedValue::build_text(Rc::new(r#"{"a.b":1,"a":{"b":2}}"#.to_string())),
+            &[OwnedValue::build_text(Rc::new(r#"$."a.b""#.to_string()))],
+        );
+
+        assert_eq!(result.unwrap(), OwnedValue::Integer(1));
+    }
+
+    #[test]
+    fn test_json_extract_quoted_key_unicode_escape() {
+        let json = OwnedValue::build_text(Rc::new(r#"{"café":1}"#.to_string()));
+        let path = OwnedValue::build_text(Rc::new(r#"$."caf\u00e9""#.to_string()));
+        assert_eq!(
+            json_extract(&json, &[path]).unwrap(),
+            OwnedValue::Integer(1)
+        );
+    }
+
+    #[test]
+    fn test_json_extract_from_end_locator() {
+        let json = OwnedValue::build_text(Rc::new("[1,2,3]".to_string()));
+
+        let result = json_extract(
+            &json,
+            &[OwnedValue::build_text(Rc::new("$[#-1]".to_string()))],
+        );
+        assert_eq!(result.unwrap(), OwnedValue::Integer(3));
+
+        let result = json_extract(
+            &json,
+            &[OwnedValue::build_text(Rc::new("$[#-3]".to_string()))],
+        );
+        assert_eq!(result.unwrap(), OwnedValue::Integer(1));
+
+        let result = json_extract(
+            &json,
+            &[OwnedValue::build_text(Rc::new("$[#-4]".to_string()))],
+        );
+        assert_eq!(result.unwrap(), OwnedValue::Null);
+
+        let result = json_extract(
+            &json,
+            &[OwnedValue::build_text(Rc::new("$[#]".to_string()))],
+        );
+        assert_eq!(result.unwrap(), OwnedValue::Null);
+    }
+
+    #[test]
+    fn test_json_path_invalid() {
+        let result = json_extract(
+            &OwnedValue::build_text(Rc::new("{\"a\":2}".to_string())),
+            &[OwnedValue::Float(1.1)],
+        );
+
+        match result {
+            Ok(_) => panic!("expected error"),
+            Err(e) => assert!(e.to_string().contains("JSON path error")),
+        }
+    }
+
+    #[test]
+    fn test_json_type_bool_values() {
+        let input = OwnedValue::build_text(Rc::new("[true,false]".to_string()));
+
+        let result = json_type(
+            &input,
+            Some(&OwnedValue::build_text(Rc::new("$[0]".to_string()))),
+        )
+        .unwrap();
+        assert_eq!(result, OwnedValue::build_text(Rc::new("true".to_string())));
+
+        let result = json_type(
+            &input,
+            Some(&OwnedValue::build_text(Rc::new("$[1]".to_string()))),
+        )
+        .unwrap();
+        assert_eq!(result, OwnedValue::build_text(Rc::new("false".to_string())));
+    }
+
+    #[test]
+    fn test_json_type_is_plain_text() {
+        let input = OwnedValue::build_text(Rc::new("{\"a\":1.5}".to_string()));
+        let result = json_type(&input, None).unwrap();
+        let OwnedValue::Text(result) = result else {
+            panic!("Expected OwnedValue::Text");
+        };
+        assert_eq!(result.value.as_str(), "object");
+        assert_eq!(result.subtype, TextSubtype::Text);
+    }
+
+    #[test]
+    fn test_json_type_missing_path() {
+        let input = OwnedValue::build_text(Rc::new("{\"a\":1}".to_string()));
+        let result = json_type(
+            &input,
+            Some(&OwnedValue::build_text(Rc::new("$.b".to_string()))),
+        )
+        .unwrap();
+        assert_eq!(result, OwnedValue::Null);
+    }
+
+    #[test]
+    fn test_json_type_invalid_path() {
+        let text = OwnedValue::build_text(Rc::new(r#"{"a":[1]}"#.to_string()));
+        let path = |p: &str| OwnedValue::build_text(Rc::new(p.to_string()));
+        for input in [text.clone(), get_jsonb(&text).unwrap()] {
+            for p in ["bad", "a", "$.", "$[", "$.a[x]", "$$"] {
+                match json_type(&input, Some(&path(p))) {
+                    Err(e) => assert!(e.to_string().contains("JSON path error"), "{}: {}", p, e),
+                    Ok(result) => panic!("Expected error for path {:?}, got {:?}", p, result),
+                }
+            }
+            assert!(json_type(&input, Some(&OwnedValue::Integer(1))).is_err());
+
+            for p in ["$.x", "$.a[1]", "$.a.b", "$[0]", r#"$."a b""#] {
+                assert_eq!(
+                    json_type(&input, Some(&path(p))).unwrap(),
+                    OwnedValue::Null,
+                    "{}",
+                    p
+                );
+            }
+            assert_eq!(
+                json_type(&input, Some(&OwnedValue::Null)).unwrap(),
+                OwnedValue::Null
+            );
+        }
+    }
+
+    #[test]
+    fn test_json_type_malformed_json() {
+        let input = OwnedValue::build_text(Rc::new("{\"a\":".to_string()));
+        match json_type(&input, None) {
+            Ok(_) => panic!("Expected error for malformed JSON"),
+            Err(e) => assert!(e.to_string().contains("malformed JSON")),
+        }
+    }
+
+    #[test]
+    fn test_json_type_without_path_matches_parse() {
+        let cases = [
+            "null",
+            " true",
+            "false\n",
+            "0",
+            "-0",
+            "42",
+            "-17",
+            "9223372036854775808",
+            "-18446744073709551616",
+            "1.5",
+            "-0.0",
+            "1e5",
+            "1E-5",
+            "2.5e+3",
+            r#""text""#,
+            r#""1.5""#,
+            "[]",
+            "[1.5, 2]",
+            r#"{"a": 1.5}"#,
+            "\t\r\n {}",
+        ];
+        for json in cases {
+            let expected = from_str::<Val>(json).unwrap().type_name();
+            assert_eq!(top_level_type(json), Some(expected), "{}", json);
+
+            let input = OwnedValue::build_text(Rc::new(json.to_string()));
+            assert_eq!(
+                json_type(&input, None).unwrap(),
+                OwnedValue::build_text(Rc::new(expected.to_string())),
+                "{}",
+                json
+            );
+        }
+
+        // Values that only JSON5 can start with are parsed to tell their type.
+        for json in [
+            "0x10",
+            "-0X1E",
+            ".5",
+            "+5",
+            "-Infinity",
+            "NaN",
+            "'x'",
+            "/* c */ 1",
+        ] {
+            assert_eq!(top_level_type(json), None, "{}", json);
+        }
+        for (json, expected) in [
+            ("0x10", "integer"),
+            ("-0X1E", "integer"),
+            (".5", "real"),
+            ("-Infinity", "real"),
+            ("'x'", "text"),
+            ("/* c */ 1", "integer"),
+            ("[1,]", "array"),
+        ] {
+            let input = OwnedValue::build_text(Rc::new(json.to_string()));
+            assert_eq!(
+                json_type(&input, None).unwrap(),
+                OwnedValue::build_text(Rc::new(expected.to_string())),
+                "{}",
+                json
+            );
+        }
+        // Malformed text is still an error, even when it starts like a valid value.
+        for json in ["[1", "1.5x", r#"{"a":}"#, "[1e999]", r#"{"a":"\uD83D"}"#] {
+            let input = OwnedValue::build_text(Rc::new(json.to_string()));
+            assert!(json_type(&input, None).is_err(), "{}", json);
+        }
+    }
+
+    #[test]
+    fn test_json_integers_beyond_i64() {
+        let cases = [
+            ("9223372036854775807", Val::Integer(i64::MAX)),
+            (
+                "9223372036854775808",
+                Val::RawNumber("9223372036854775808".to_string()),
+            ),
+            ("-9223372036854775808", Val::Integer(i64::MIN)),
+            (
+                "-9223372036854775809",
+                Val::RawNumber("-9223372036854775809".to_string()),
+            ),
+            (
+                "0xFFFFFFFFFFFFFFFF",
+                Val::RawNumber("18446744073709551615".to_string()),
+            ),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(from_str::<Val>(input).unwrap(), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_json_large_integer_round_trips() {
+        let text = |s: &str| OwnedValue::build_text(Rc::new(s.to_string()));
+        for input in [
+            r#"{"x":99999999999999999999}"#,
+            "[9223372036854775807,9223372036854775808,-9223372036854775809]",
+        ] {
+            let OwnedValue::Text(result) = get_json(&text(input)).unwrap() else {
+                panic!("Expected OwnedValue::Text");
+            };
+            assert_eq!(result.value.as_str(), input);
+        }
+
+        let input = text("[9223372036854775807,9223372036854775808]");
+        for (path, sql_value, json_type_name) in [
+            ("$[0]", OwnedValue::Integer(i64::MAX), "integer"),
+            ("$[1]", OwnedValue::Float(9223372036854775808.0), "integer"),
+        ] {
+            let path = text(path);
+            assert_eq!(
+                json_extract(&input, std::slice::from_ref(&path)).unwrap(),
+                sql_value
+            );
+            assert_eq!(
+                json_type(&input, Some(&path)).unwrap(),
+                text(json_type_name)
+            );
+        }
+    }
+
+    #[test]
+    fn test_json_insert_does_not_overwrite() {
+        let input = OwnedValue::build_text(Rc::new(r#"{"a":1}"#.to_string()));
+        let args = [
+            OwnedValue::build_text(Rc::new("$.a".to_string())),
+            OwnedValue::Integer(99),
+            OwnedValue::build_text(Rc::new("$.b".to_string())),
+            OwnedValue::Integer(2),
+        ];
+
+        let OwnedValue::Text(result) = json_insert(&input, &args).unwrap() else {
+            panic!("Expected OwnedValue::Text");
+        };
+        assert_eq!(result.value.as_str(), r#"{"a":1,"b":2}"#);
+        assert_eq!(result.subtype, TextSubtype::Json);
+    }
+
+    #[test]
+    fn test_json_insert_creates_intermediate_containers() {
+        let input = OwnedValue::build_text(Rc::new(r#"{"a":1}"#.to_string()));
+        let args = [
+            OwnedValue::build_text(Rc::new("$.b.c".to_string())),
+            OwnedValue::Integer(2),
+            OwnedValue::build_text(Rc::new("$.d[0]".to_string())),
+            OwnedValue::Integer(3),
+        ];
+
+        let OwnedValue::Text(result) = json_insert(&input, &args).unwrap() else {
+            panic!("Expected OwnedValue::Text");
+        };
+        assert_eq!(result.value.as_str(), r#"{"a":1,"b":{"c":2},"d":[3]}"#);
+    }
+
+    #[test]
+    fn test_json_insert_array() {
+        let input = OwnedValue::build_text(Rc::new("[1,2]".to_string()));
+        let args = [
+            OwnedValue::build_text(Rc::new("$[0]".to_string())),
+            OwnedValue::Integer(9),
+            OwnedValue::build_text(Rc::new("$[2]".to_string())),
+            OwnedValue::Integer(3),
+            OwnedValue::build_text(Rc::new("$[5]".to_string())),
+            OwnedValue::Integer(4),
+        ];
+
+        let OwnedValue::Text(result) = json_insert(&input, &args).unwrap() else {
+            panic!("Expected OwnedValue::Text");
+        };
+        assert_eq!(result.value.as_str(), "[1,2,3]");
+    }
+
+    #[test]
+    fn test_json_insert_json_value() {
+        let input = OwnedValue::build_text(Rc::new("{}".to_string()));
+        let args = [
+            OwnedValue::build_text(Rc::new("$.a".to_string())),
+            OwnedValue::Text(LimboText::json(Rc::new("[1,2]".to_string()))),
+            OwnedValue::build_text(Rc::new("$.b".to_string())),
+            OwnedValue::build_text(Rc::new("[1,2]".to_string())),
+        ];
+
+        let OwnedValue::Text(result) = json_insert(&input, &args).unwrap() else {
+            panic!("Expected OwnedValue::Text");
+        };
+        assert_eq!(result.value.as_str(), r#"{"a":[1,2],"b":"[1,2]"}"#);
+    }
+
+    #[test]
+    fn test_json_insert_bad_path() {
+        let input = OwnedValue::build_text(Rc::new("{}".to_string()));
+        let args = [OwnedValue::Integer(1), OwnedValue::Integer(2)];
+
+        match json_insert(&input, &args) {
+            Ok(_) => panic!("Expected error for non-text path"),
+            Err(e) => assert!(e.to_string().contains("JSON path error")),
+        }
+    }
 
-/// Constructs a JSON object from a list of values that represent key-value pairs.
-/// The number of values must be even, and the first value of each pair (which represents the map key)
-/// must be a TEXT value. The second value of each pair can be any JSON value (which represents the map value)
-pub fn json_object(values: &[OwnedValue]) -> crate::Result<OwnedValue> {
-    let value_map = values
-        .chunks(2)
-        .map(|chunk| match chunk {
-            [key, value] => {
-                let key = match key {
-                    OwnedValue::Text(t) => t.value.to_string(),
-                    _ => crate::bail_constraint_error!("labels must be TEXT"),
-                };
-                let json_val = convert_db_type_to_json(value)?;
+    #[test]
+    fn test_json_edits_skip_null_path() {
+        let text = |s: &str| OwnedValue::build_text(Rc::new(s.to_string()));
+        let input = text(r#"{"a":1}"#);
 
-                Ok((key, json_val))
-            }
-            _ => crate::bail_constraint_error!("json_object requires an even number of values"),
-        })
-        .collect::<Result<IndexMap<String, Val>, _>>()?;
+        let args = [OwnedValue::Null, OwnedValue::Integer(2)];
+        for edit in [json_set, json_insert, json_replace] {
+            assert_eq!(edit(&input, &args).unwrap().to_string(), r#"{"a":1}"#);
+        }
 
-    let result = crate::json::to_string(&value_map).unwrap();
-    Ok(OwnedValue::Text(LimboText::json(Rc::new(result))))
-}
+        // The other pairs are still applied.
+        let args = [
+            OwnedValue::Null,
+            OwnedValue::Integer(2),
+            text("$.b"),
+            OwnedValue::Integer(3),
+        ];
+        assert_eq!(
+            json_set(&input, &args).unwrap().to_string(),
+            r#"{"a":1,"b":3}"#
+        );
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::types::OwnedValue;
+    #[test]
+    fn test_json_replace_only_existing() {
+        let input = OwnedValue::build_text(Rc::new(r#"{"a":1}"#.to_string()));
+        let args = [
+            OwnedValue::build_text(Rc::new("$.a".to_string())),
+            OwnedValue::Integer(2),
+            OwnedValue::build_text(Rc::new("$.c".to_string())),
+            OwnedValue::Integer(9),
+        ];
+
+        let OwnedValue::Text(result) = json_replace(&input, &args).unwrap() else {
+            panic!("Expected OwnedValue::Text");
+        };
+        assert_eq!(result.value.as_str(), r#"{"a":2}"#);
+        assert_eq!(result.subtype, TextSubtype::Json);
+    }
 
     #[test]
-    fn test_get_json_valid_json5() {
-        let input = OwnedValue::build_text(Rc::new("{ key: 'value' }".to_string()));
-        let result = get_json(&input).unwrap();
-        if let OwnedValue::Text(result_str) = result {
-            assert!(result_str.value.contains("\"key\":\"value\""));
-            assert_eq!(result_str.subtype, TextSubtype::Json);
-        } else {
+    fn test_json_replace_does_not_create_intermediate_containers() {
+        let input = OwnedValue::build_text(Rc::new(r#"{"a":1}"#.to_string()));
+        let args = [
+            OwnedValue::build_text(Rc::new("$.b.c".to_string())),
+            OwnedValue::Integer(2),
+        ];
+
+        let OwnedValue::Text(result) = json_replace(&input, &args).unwrap() else {
+            panic!("Expected OwnedValue::Text");
+        };
+        assert_eq!(result.value.as_str(), r#"{"a":1}"#);
+    }
+
+    #[test]
+    fn test_json_replace_array() {
+        let input = OwnedValue::build_text(Rc::new("[1,2,3]".to_string()));
+        let args = [
+            OwnedValue::build_text(Rc::new("$[1]".to_string())),
+            OwnedValue::Integer(9),
+            OwnedValue::build_text(Rc::new("$[#]".to_string())),
+            OwnedValue::Integer(4),
+            OwnedValue::build_text(Rc::new("$[#-1]".to_string())),
+            OwnedValue::Integer(7),
+        ];
+
+        let OwnedValue::Text(result) = json_replace(&input, &args).unwrap() else {
+            panic!("Expected OwnedValue::Text");
+        };
+        assert_eq!(result.value.as_str(), "[1,9,7]");
+    }
+
+    #[test]
+    fn test_json_replace_root() {
+        let input = OwnedValue::build_text(Rc::new(r#"{"a":1}"#.to_string()));
+        let args = [
+            OwnedValue::build_text(Rc::new("$".to_string())),
+            OwnedValue::Integer(1),
+        ];
+
+        let OwnedValue::Text(result) = json_replace(&input, &args).unwrap() else {
+            panic!("Expected OwnedValue::Text");
+        };
+        assert_eq!(result.value.as_str(), "1");
+    }
+
+    #[test]
+    fn test_json_set_creates_and_overwrites() {
+        let input = OwnedValue::build_text(Rc::new(r#"{"a":1}"#.to_string()));
+        let args = [
+            OwnedValue::build_text(Rc::new("$.a".to_string())),
+            OwnedValue::Integer(2),
+            OwnedValue::build_text(Rc::new("$.b.c".to_string())),
+            OwnedValue::Integer(3),
+        ];
+
+        let OwnedValue::Text(result) = json_set(&input, &args).unwrap() else {
+            panic!("Expected OwnedValue::Text");
+        };
+        assert_eq!(result.value.as_str(), r#"{"a":2,"b":{"c":3}}"#);
+        assert_eq!(result.subtype, TextSubtype::Json);
+    }
+
+    #[test]
+    fn test_json_set_array_append() {
+        let input = OwnedValue::build_text(Rc::new("[1,2]".to_string()));
+        let args = [
+            OwnedValue::build_text(Rc::new("$[#]".to_string())),
+            OwnedValue::Integer(3),
+            OwnedValue::build_text(Rc::new("$[0]".to_string())),
+            OwnedValue::Integer(0),
+        ];
+
+        let OwnedValue::Text(result) = json_set(&input, &args).unwrap() else {
             panic!("Expected OwnedValue::Text");
+        };
+        assert_eq!(result.value.as_str(), "[0,2,3]");
+    }
+
+    #[test]
+    fn test_json_edit_append_creates_containers() {
+        let cases = [
+            (r#"{"a":[1]}"#, "$.a[#].b", r#"{"a":[1,{"b":2}]}"#),
+            ("[]", "$[#][#]", "[[2]]"),
+            ("{}", "$.x[#]", r#"{"x":[2]}"#),
+        ];
+        for (input, path, expected) in cases {
+            let input = OwnedValue::build_text(Rc::new(input.to_string()));
+            let args = [
+                OwnedValue::build_text(Rc::new(path.to_string())),
+                OwnedValue::Integer(2),
+            ];
+            for result in [json_set(&input, &args), json_insert(&input, &args)] {
+                let OwnedValue::Text(result) = result.unwrap() else {
+                    panic!("Expected OwnedValue::Text");
+                };
+                assert_eq!(result.value.as_str(), expected, "path: {}", path);
+            }
         }
     }
 
     #[test]
-    fn test_get_json_valid_json5_double_single_quotes() {
-        let input = OwnedValue::build_text(Rc::new("{ key: ''value'' }".to_string()));
-        let result = get_json(&input).unwrap();
-        if let OwnedValue::Text(result_str) = result {
-            assert!(result_str.value.contains("\"key\":\"value\""));
-            assert_eq!(result_str.subtype, TextSubtype::Json);
-        } else {
+    fn test_json_edit_unreachable_path_creates_nothing() {
+        // Missing parents aren't created when the rest of the path can't be created in them.
+        let cases = [
+            ("{}", "$.a[5]"),
+            ("{}", "$.a.b[3].c"),
+            ("{}", "$.a[#-1]"),
+            ("[]", "$[0].a[1]"),
+            (r#"{"a":[]}"#, "$.a[0][2]"),
+        ];
+        for (input, path) in cases {
+            let json = OwnedValue::build_text(Rc::new(input.to_string()));
+            let args = [
+                OwnedValue::build_text(Rc::new(path.to_string())),
+                OwnedValue::Integer(1),
+            ];
+            for result in [json_set(&json, &args), json_insert(&json, &args)] {
+                let OwnedValue::Text(result) = result.unwrap() else {
+                    panic!("Expected OwnedValue::Text");
+                };
+                assert_eq!(result.value.as_str(), input, "path: {}", path);
+            }
+        }
+
+        let input = OwnedValue::build_text(Rc::new("{}".to_string()));
+        let args = [
+            OwnedValue::build_text(Rc::new("$.a[0].b[#].c".to_string())),
+            OwnedValue::Integer(1),
+        ];
+        let OwnedValue::Text(result) = json_set(&input, &args).unwrap() else {
+            panic!("Expected OwnedValue::Text");
+        };
+        assert_eq!(result.value.as_str(), r#"{"a":[{"b":[{"c":1}]}]}"#);
+    }
+
+    #[test]
+    fn test_json_insert_append_twice() {
+        let input = OwnedValue::build_text(Rc::new(r#"{"a":[]}"#.to_string()));
+        let args = [
+            OwnedValue::build_text(Rc::new("$.a[#]".to_string())),
+            OwnedValue::Integer(1),
+            OwnedValue::build_text(Rc::new("$.a[#]".to_string())),
+            OwnedValue::Integer(2),
+        ];
+
+        let OwnedValue::Text(result) = json_insert(&input, &args).unwrap() else {
             panic!("Expected OwnedValue::Text");
+        };
+        assert_eq!(result.value.as_str(), r#"{"a":[1,2]}"#);
+    }
+
+    #[test]
+    fn test_json_remove_left_to_right() {
+        let cases: [(&str, &[&str], &str); 7] = [
+            (r#"["a","b","c","d"]"#, &["$[1]", "$[2]"], r#"["a","c"]"#),
+            (
+                r#"{"a":[1,{"b":2,"c":3}],"d":4}"#,
+                &["$.a[1].b", "$.d"],
+                r#"{"a":[1,{"c":3}]}"#,
+            ),
+            (
+                r#"{"a":[1,{"b":2,"c":3}],"d":4}"#,
+                &["$.a[0]", "$.a[0].c"],
+                r#"{"a":[{"b":2}],"d":4}"#,
+            ),
+            ("[[1,2],[3]]", &["$[0]", "$[0][0]"], "[[]]"),
+            ("[1,2,3]", &["$[#-1]", "$[#]", "$[5]"], "[1,2]"),
+            // The parent of the second path is already gone
+            (
+                r#"{"a":{"b":{"c":1}}}"#,
+                &["$.a.b", "$.a.b.c"],
+                r#"{"a":{}}"#,
+            ),
+            (r#"{"a":1}"#, &["$.a", "$.a", "$.x.y"], "{}"),
+        ];
+        for (input, paths, expected) in cases {
+            let input = OwnedValue::build_text(Rc::new(input.to_string()));
+            let paths: Vec<_> = paths
+                .iter()
+                .map(|path| OwnedValue::build_text(Rc::new(path.to_string())))
+                .collect();
+            let OwnedValue::Text(result) = json_remove(&input, &paths).unwrap() else {
+                panic!("Expected OwnedValue::Text");
+            };
+            assert_eq!(result.value.as_str(), expected);
+            assert_eq!(result.subtype, TextSubtype::Json);
         }
     }
 
     #[test]
-    fn test_get_json_valid_json5_infinity() {
-        let input = OwnedValue::build_text(Rc::new("{ \"key\": Infinity }".to_string()));
-        let result = get_json(&input).unwrap();
-        if let OwnedValue::Text(result_str) = result {
-            assert!(result_str.value.contains("{\"key\":9e999}"));
-            assert_eq!(result_str.subtype, TextSubtype::Json);
-        } else {
+    fn test_json_remove_null() {
+        let input = OwnedValue::build_text(Rc::new("[1,2]".to_string()));
+        assert_eq!(
+            json_remove(&input, &[OwnedValue::Null]).unwrap(),
+            OwnedValue::Null
+        );
+        assert_eq!(
+            json_remove(&OwnedValue::Null, &[]).unwrap(),
+            OwnedValue::Null
+        );
+    }
+
+    #[test]
+    fn test_root_path_in_all_functions() {
+        let doc = || OwnedValue::build_text(Rc::new(r#"{"a":[1,2]}"#.to_string()));
+        let root = || OwnedValue::build_text(Rc::new("$".to_string()));
+        let text = |s: &str| OwnedValue::build_text(Rc::new(s.to_string()));
+        let json_text = |s: &str| OwnedValue::Text(LimboText::json(Rc::new(s.to_string())));
+
+        assert_eq!(
+            json_extract(&doc(), &[root()]).unwrap(),
+            json_text(r#"{"a":[1,2]}"#)
+        );
+        assert_eq!(
+            json_arrow_extract(&doc(), &root()).unwrap(),
+            json_text(r#"{"a":[1,2]}"#)
+        );
+        assert_eq!(
+            json_arrow_shift_extract(&doc(), &root()).unwrap(),
+            text(r#"{"a":[1,2]}"#)
+        );
+        assert_eq!(json_type(&doc(), Some(&root())).unwrap(), text("object"));
+        assert_eq!(
+            json_array_length(&text("[1,2]"), Some(&root())).unwrap(),
+            OwnedValue::Integer(2)
+        );
+
+        let args = [root(), OwnedValue::Integer(3)];
+        assert_eq!(json_set(&doc(), &args).unwrap(), json_text("3"));
+        assert_eq!(json_replace(&doc(), &args).unwrap(), json_text("3"));
+        assert_eq!(
+            json_insert(&doc(), &args).unwrap(),
+            json_text(r#"{"a":[1,2]}"#)
+        );
+
+        match json_remove(&doc(), &[root()]) {
+            Ok(result) => panic!("Expected error for removing the root, got: {:?}", result),
+            Err(e) => assert!(e.to_string().contains("JSON path error")),
+        }
+    }
+
+    #[test]
+    fn test_json_remove_without_paths_minifies() {
+        let input = OwnedValue::build_text(Rc::new(" [1, 2] ".to_string()));
+        let OwnedValue::Text(result) = json_remove(&input, &[]).unwrap() else {
             panic!("Expected OwnedValue::Text");
+        };
+        assert_eq!(result.value.as_str(), "[1,2]");
+        assert_eq!(result.subtype, TextSubtype::Json);
+    }
+
+    #[test]
+    fn test_json_remove_and_extract_without_paths_differ() {
+        // Both are what SQLite does: json_remove returns the document, json_extract NULL.
+        let input = OwnedValue::build_text(Rc::new(r#"{ "a" : 1 }"#.to_string()));
+        assert_eq!(
+            json_remove(&input, &[]).unwrap(),
+            OwnedValue::Text(LimboText::json(Rc::new(r#"{"a":1}"#.to_string())))
+        );
+        assert_eq!(json_extract(&input, &[]).unwrap(), OwnedValue::Null);
+
+        let jsonb = get_jsonb(&input).unwrap();
+        assert_eq!(json_remove(&jsonb, &[]).unwrap(), jsonb);
+        assert_eq!(json_extract(&jsonb, &[]).unwrap(), OwnedValue::Null);
+    }
+
+    #[test]
+    fn test_json_edits_keep_jsonb() {
+        let text = |s: &str| OwnedValue::build_text(Rc::new(s.to_string()));
+        let doc = get_jsonb(&text(r#"{"a":[1,2],"b":{"c":3}}"#)).unwrap();
+        let as_text = |result: OwnedValue| {
+            let OwnedValue::Blob(blob) = result else {
+                panic!("Expected OwnedValue::Blob, got {:?}", result);
+            };
+            get_json(&OwnedValue::Blob(blob)).unwrap().to_string()
+        };
+
+        assert_eq!(
+            as_text(json_set(&doc, &[text("$.b.c"), OwnedValue::Integer(4)]).unwrap()),
+            r#"{"a":[1,2],"b":{"c":4}}"#
+        );
+        assert_eq!(
+            as_text(json_insert(&doc, &[text("$.a[#]"), OwnedValue::Integer(3)]).unwrap()),
+            r#"{"a":[1,2,3],"b":{"c":3}}"#
+        );
+        assert_eq!(
+            as_text(json_replace(&doc, &[text("$.a"), text("x")]).unwrap()),
+            r#"{"a":"x","b":{"c":3}}"#
+        );
+        assert_eq!(
+            as_text(json_remove(&doc, &[text("$.a[0]")]).unwrap()),
+            r#"{"a":[2],"b":{"c":3}}"#
+        );
+    }
+
+    #[test]
+    fn test_jsonb_edits_round_trip() {
+        let text = |s: &str| OwnedValue::build_text(Rc::new(s.to_string()));
+        let round_trip = |result: OwnedValue| {
+            let OwnedValue::Blob(_) = result else {
+                panic!("Expected OwnedValue::Blob, got {:?}", result);
+            };
+            get_json(&result).unwrap().to_string()
+        };
+
+        for doc in [
+            text(r#"{"a":[1,2],"b":{"c":3}}"#),
+            get_jsonb(&text(r#"{"a":[1,2],"b":{"c":3}}"#)).unwrap(),
+        ] {
+            assert_eq!(
+                round_trip(jsonb_set(&doc, &[text("$.a[#]"), OwnedValue::Integer(3)]).unwrap()),
+                r#"{"a":[1,2,3],"b":{"c":3}}"#
+            );
+            assert_eq!(
+                round_trip(jsonb_set(&doc, &[text("$.b.d"), text("x")]).unwrap()),
+                r#"{"a":[1,2],"b":{"c":3,"d":"x"}}"#
+            );
+            assert_eq!(
+                round_trip(jsonb_insert(&doc, &[text("$.a[0]"), OwnedValue::Integer(9)]).unwrap()),
+                r#"{"a":[1,2],"b":{"c":3}}"#
+            );
+            assert_eq!(
+                round_trip(jsonb_insert(&doc, &[text("$.a[#]"), OwnedValue::Null]).unwrap()),
+                r#"{"a":[1,2,null],"b":{"c":3}}"#
+            );
+            assert_eq!(
+                round_trip(jsonb_replace(&doc, &[text("$.a[#]"), OwnedValue::Integer(9)]).unwrap()),
+                r#"{"a":[1,2],"b":{"c":3}}"#
+            );
+            assert_eq!(
+                round_trip(jsonb_replace(&doc, &[text("$.b.c"), OwnedValue::Integer(9)]).unwrap()),
+                r#"{"a":[1,2],"b":{"c":9}}"#
+            );
+            assert_eq!(
+                round_trip(jsonb_remove(&doc, &[text("$.a[#-1]"), text("$.b")]).unwrap()),
+                r#"{"a":[1]}"#
+            );
         }
     }
 
     #[test]
-    fn test_get_json_valid_json5_negative_infinity() {
-        let input = OwnedValue::build_text(Rc::new("{ \"key\": -Infinity }".to_string()));
-        let result = get_json(&input).unwrap();
-        if let OwnedValue::Text(result_str) = result {
-            assert!(result_str.value.contains("{\"key\":-9e999}"));
-            assert_eq!(result_str.subtype, TextSubtype::Json);
-        } else {
+    fn test_jsonb_edits_null() {
+        let path = OwnedValue::build_text(Rc::new("$.a".to_string()));
+        assert_eq!(
+            jsonb_set(&OwnedValue::Null, &[path.clone(), OwnedValue::Integer(1)]).unwrap(),
+            OwnedValue::Null
+        );
+        assert_eq!(
+            jsonb_remove(&OwnedValue::Null, &[path]).unwrap(),
+            OwnedValue::Null
+        );
+    }
+
+    #[test]
+    fn test_json_edits_of_text_stay_text() {
+        let text = |s: &str| OwnedValue::build_text(Rc::new(s.to_string()));
+        for doc in [
+            text(r#"{"a":1}"#),
+            OwnedValue::Blob(Rc::new(br#"{"a":1}"#.to_vec())),
+        ] {
+            assert_eq!(JsonRepr::of(&doc), JsonRepr::Text);
+            let OwnedValue::Text(result) =
+                json_set(&doc, &[text("$.a"), OwnedValue::Integer(2)]).unwrap()
+            else {
+                panic!("Expected OwnedValue::Text");
+            };
+            assert_eq!(result.value.as_str(), r#"{"a":2}"#);
+            assert_eq!(result.subtype, TextSubtype::Json);
+        }
+    }
+
+    #[test]
+    fn test_json_remove_invalid_path() {
+        let input = OwnedValue::build_text(Rc::new(r#"{"a":1}"#.to_string()));
+        for path in [
+            OwnedValue::build_text(Rc::new("x".to_string())),
+            OwnedValue::build_text(Rc::new("$.*".to_string())),
+            OwnedValue::Integer(1),
+        ] {
+            assert!(json_remove(&input, &[path]).is_err());
+        }
+    }
+
+    #[test]
+    fn test_json_edit_value_types() {
+        let cases = [
+            (OwnedValue::Integer(7), r#"{"a":7}"#),
+            (OwnedValue::Float(1.5), r#"{"a":1.5}"#),
+            (OwnedValue::Null, r#"{"a":null}"#),
+            (
+                OwnedValue::build_text(Rc::new("[1]".to_string())),
+                r#"{"a":"[1]"}"#,
+            ),
+            (
+                OwnedValue::Text(LimboText::json(Rc::new("[1]".to_string()))),
+                r#"{"a":[1]}"#,
+            ),
+        ];
+        for (value, expected) in cases {
+            let args = [OwnedValue::build_text(Rc::new("$.a".to_string())), value];
+            let input = OwnedValue::build_text(Rc::new(r#"{"a":0}"#.to_string()));
+            for result in [json_set(&input, &args), json_replace(&input, &args)] {
+                let OwnedValue::Text(result) = result.unwrap() else {
+                    panic!("Expected OwnedValue::Text");
+                };
+                assert_eq!(result.value.as_str(), expected);
+            }
+            let input = OwnedValue::build_text(Rc::new("{}".to_string()));
+            let OwnedValue::Text(result) = json_insert(&input, &args).unwrap() else {
+                panic!("Expected OwnedValue::Text");
+            };
+            assert_eq!(result.value.as_str(), expected);
+        }
+    }
+
+    #[test]
+    fn test_json_edit_blob_value() {
+        let input = OwnedValue::build_text(Rc::new(r#"{"a":0}"#.to_string()));
+        let args = [
+            OwnedValue::build_text(Rc::new("$.a".to_string())),
+            OwnedValue::Blob(Rc::new(b"abc".to_vec())),
+        ];
+        for result in [
+            json_set(&input, &args),
+            json_replace(&input, &args),
+            json_insert(&input, &args),
+        ] {
+            match result {
+                Ok(_) => panic!("Expected error for blob value"),
+                Err(e) => assert!(e.to_string().contains("JSON cannot hold BLOB values")),
+            }
+        }
+    }
+
+    #[test]
+    fn test_json_set_json_value_is_spliced() {
+        let input = OwnedValue::build_text(Rc::new(r#"{"a":1}"#.to_string()));
+        let args = [
+            OwnedValue::build_text(Rc::new("$.a".to_string())),
+            OwnedValue::Text(LimboText::json(Rc::new(r#"{"b":[1,2]}"#.to_string()))),
+            OwnedValue::build_text(Rc::new("$.c".to_string())),
+            OwnedValue::build_text(Rc::new(r#"{"b":[1,2]}"#.to_string())),
+        ];
+
+        let OwnedValue::Text(result) = json_set(&input, &args).unwrap() else {
+            panic!("Expected OwnedValue::Text");
+        };
+        assert_eq!(
+            result.value.as_str(),
+            r#"{"a":{"b":[1,2]},"c":"{\"b\":[1,2]}"}"#
+        );
+    }
+
+    #[test]
+    fn test_json_set_into_spliced_json_value() {
+        let input = OwnedValue::build_text(Rc::new("{}".to_string()));
+        let args = [
+            OwnedValue::build_text(Rc::new("$.a".to_string())),
+            OwnedValue::Text(LimboText::json(Rc::new(r#"{"b":[1,2]}"#.to_string()))),
+            OwnedValue::build_text(Rc::new("$.a.b[#]".to_string())),
+            OwnedValue::Integer(3),
+        ];
+
+        let OwnedValue::Text(result) = json_set(&input, &args).unwrap() else {
             panic!("Expected OwnedValue::Text");
+        };
+        assert_eq!(result.value.as_str(), r#"{"a":{"b":[1,2,3]}}"#);
+    }
+
+    /// Text with the JSON subtype that isn't actually valid JSON. It can't come out of the JSON
+    /// functions, but shows whether a function trusts the subtype instead of validating again.
+    fn malformed_json_subtype() -> OwnedValue {
+        OwnedValue::Text(LimboText::json(Rc::new("[1,".to_string())))
+    }
+
+    #[test]
+    fn test_json_subtype_is_embedded_verbatim() {
+        let text = |s: &str| OwnedValue::build_text(Rc::new(s.to_string()));
+        let result_text = |result: crate::Result<OwnedValue>| match result.unwrap() {
+            OwnedValue::Text(t) => {
+                assert_eq!(t.subtype, TextSubtype::Json);
+                t.value.to_string()
+            }
+            other => panic!("Expected OwnedValue::Text, got {:?}", other),
+        };
+
+        assert_eq!(
+            result_text(json_array(&[
+                OwnedValue::Integer(1),
+                malformed_json_subtype()
+            ])),
+            "[1,[1,]"
+        );
+        assert_eq!(
+            result_text(json_object(&[text("a"), malformed_json_subtype()])),
+            r#"{"a":[1,}"#
+        );
+        assert_eq!(
+            result_text(json_set(
+                &text("{}"),
+                &[text("$.a"), malformed_json_subtype()]
+            )),
+            r#"{"a":[1,}"#
+        );
+        assert_eq!(
+            result_text(json_extract(&malformed_json_subtype(), &[text("$")])),
+            "[1,"
+        );
+        assert_eq!(result_text(get_json(&malformed_json_subtype())), "[1,");
+        assert_eq!(
+            to_string(&Val::Array(vec![Val::RawJson("[1,".into())])).unwrap(),
+            "[[1,]"
+        );
+
+        let mut group = JsonGroupArray::new();
+        group.step(&malformed_json_subtype()).unwrap();
+        assert_eq!(result_text(group.finalize()), "[[1,]");
+    }
+
+    #[test]
+    fn test_malformed_raw_json_is_an_error_when_parsed() {
+        let text = |s: &str| OwnedValue::build_text(Rc::new(s.to_string()));
+
+        // JSONB has to encode the embedded text, and paths have to go into it, so it's parsed
+        // then, and being malformed is an error rather than a null.
+        assert!(jsonb_array(&[malformed_json_subtype()]).is_err());
+        assert!(json_set(
+            &text("{}"),
+            &[
+                text("$.a"),
+                malformed_json_subtype(),
+                text("$.a[0]"),
+                OwnedValue::Integer(2)
+            ]
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_raw_json_type_name() {
+        let cases = [
+            ("[1]", "array"),
+            (r#"{"a":1}"#, "object"),
+            (r#""s""#, "text"),
+            ("true", "true"),
+            ("false", "false"),
+            ("null", "null"),
+            ("-12", "integer"),
+            ("1.5", "real"),
+            ("9e999", "real"),
+        ];
+        for (json, expected) in cases {
+            assert_eq!(Val::RawJson(json.into()).type_name(), expected, "{}", json);
         }
     }
 
     #[test]
-    fn test_get_json_valid_json5_nan() {
-        let input = OwnedValue::build_text(Rc::new("{ \"key\": NaN }".to_string()));
-        let result = get_json(&input).unwrap();
-        if let OwnedValue::Text(result_str) = result {
-            assert!(result_str.value.contains("{\"key\":null}"));
-            assert_eq!(result_str.subtype, TextSubtype::Json);
-        } else {
+    fn test_json_subtype_is_parsed_when_navigated() {
+        let text = |s: &str| OwnedValue::build_text(Rc::new(s.to_string()));
+        // Extracting below the root has to look into the document.
+        assert!(json_extract(&malformed_json_subtype(), &[text("$[0]")]).is_err());
+
+        let OwnedValue::Blob(blob) = jsonb_array(&[OwnedValue::Text(LimboText::json(Rc::new(
+            "[1]".to_string(),
+        )))])
+        .unwrap() else {
+            panic!("Expected OwnedValue::Blob");
+        };
+        assert_eq!(
+            get_json(&OwnedValue::Blob(blob)).unwrap().to_string(),
+            "[[1]]"
+        );
+    }
+
+    #[test]
+    fn test_json_set_null_input() {
+        let args = [
+            OwnedValue::build_text(Rc::new("$.a".to_string())),
+            OwnedValue::Integer(1),
+        ];
+
+        assert_eq!(
+            json_set(&OwnedValue::Null, &args).unwrap(),
+            OwnedValue::Null
+        );
+    }
+
+    #[test]
+    fn test_json_patch_merges_objects() {
+        let target =
+            OwnedValue::build_text(Rc::new(r#"{"a":1,"b":{"c":2,"d":3},"e":4}"#.to_string()));
+        let patch = OwnedValue::build_text(Rc::new(
+            r#"{"b":{"c":null,"f":5},"e":null,"g":6}"#.to_string(),
+        ));
+
+        let OwnedValue::Text(result) = json_patch(&target, &patch).unwrap() else {
             panic!("Expected OwnedValue::Text");
-        }
+        };
+        assert_eq!(result.value.as_str(), r#"{"a":1,"b":{"d":3,"f":5},"g":6}"#);
+        assert_eq!(result.subtype, TextSubtype::Json);
     }
 
     #[test]
-    fn test_get_json_invalid_json5() {
-        let input = OwnedValue::build_text(Rc::new("{ key: value }".to_string()));
-        let result = get_json(&input);
-        match result {
-            Ok(_) => panic!("Expected error for malformed JSON"),
-            Err(e) => assert!(e.to_string().contains("malformed JSON")),
-        }
+    fn test_json_concat_objects() {
+        let first = OwnedValue::build_text(Rc::new(r#"{"a":1,"b":{"c":2},"d":3}"#.to_string()));
+        let second = OwnedValue::build_text(Rc::new(r#"{"b":{"e":4},"f":null,"a":5}"#.to_string()));
+
+        let OwnedValue::Text(result) = json_concat(&first, &second).unwrap() else {
+            panic!("Expected OwnedValue::Text");
+        };
+        // Unlike json_patch, nested objects are replaced rather than merged, and null is kept.
+        assert_eq!(
+            result.value.as_str(),
+            r#"{"a":5,"b":{"e":4},"d":3,"f":null}"#
+        );
+        assert_eq!(result.subtype, TextSubtype::Json);
     }
 
     #[test]
-    fn test_get_json_valid_jsonb() {
-        let input = OwnedValue::build_text(Rc::new("{\"key\":\"value\"}".to_string()));
-        let result = get_json(&input).unwrap();
-        if let OwnedValue::Text(result_str) = result {
-            assert!(result_str.value.contains("\"key\":\"value\""));
-            assert_eq!(result_str.subtype, TextSubtype::Json);
-        } else {
+    fn test_json_concat_arrays() {
+        let first = OwnedValue::build_text(Rc::new("[1,[2]]".to_string()));
+        let second = OwnedValue::build_text(Rc::new(r#"[[2],{"a":3}]"#.to_string()));
+
+        let OwnedValue::Text(result) = json_concat(&first, &second).unwrap() else {
             panic!("Expected OwnedValue::Text");
-        }
+        };
+        assert_eq!(result.value.as_str(), r#"[1,[2],[2],{"a":3}]"#);
+
+        let empty = OwnedValue::build_text(Rc::new("[]".to_string()));
+        assert_eq!(
+            json_concat(&empty, &empty).unwrap(),
+            get_json(&empty).unwrap()
+        );
+        assert_eq!(
+            json_concat(&OwnedValue::Null, &empty).unwrap(),
+            OwnedValue::Null
+        );
     }
 
     #[test]
-    fn test_get_json_invalid_jsonb() {
-        let input = OwnedValue::build_text(Rc::new("{key:\"value\"".to_string()));
-        let result = get_json(&input);
-        match result {
-            Ok(_) => panic!("Expected error for malformed JSON"),
-            Err(e) => assert!(e.to_string().contains("malformed JSON")),
+    fn test_json_contains() {
+        let cases = [
+            // Objects
+            (r#"{"a":1,"b":2}"#, r#"{"a":1}"#, 1),
+            (r#"{"a":1,"b":2}"#, r#"{"a":2}"#, 0),
+            (r#"{"a":1}"#, r#"{"a":1,"b":2}"#, 0),
+            (r#"{"a":1}"#, "{}", 1),
+            (r#"{"a":{"b":1,"c":2},"d":3}"#, r#"{"a":{"c":2}}"#, 1),
+            (r#"{"a":{"b":1,"c":2}}"#, r#"{"a":{"c":3}}"#, 0),
+            (r#"{"a":{"b":{"c":[1,2]}}}"#, r#"{"a":{"b":{"c":[2]}}}"#, 1),
+            // Arrays, regardless of order and repetitions
+            ("[1,2,3]", "[3,1]", 1),
+            ("[1,2,3]", "[1,1]", 1),
+            ("[1,2,3]", "[4]", 0),
+            ("[1,2,3]", "[]", 1),
+            ("[]", "[1]", 0),
+            (r#"[{"a":1,"b":2},{"c":3}]"#, r#"[{"c":3},{"a":1}]"#, 1),
+            (r#"[{"a":1,"b":2}]"#, r#"[{"a":1,"c":3}]"#, 0),
+            ("[[1,2],[3]]", "[[2]]", 1),
+            (r#"{"a":[1,{"b":[2,3]}]}"#, r#"{"a":[{"b":[3]}]}"#, 1),
+            (
+                r#"{"a":{"b":[1,2,3],"c":4},"d":5}"#,
+                r#"{"a":{"b":[3,1]}}"#,
+                1,
+            ),
+            (r#"{"a":{"b":[1,2,3]}}"#, r#"{"a":{"b":[4]}}"#, 0),
+            // Scalars, and containers of different types
+            ("1", "1.0", 1),
+            (r#""a""#, r#""a""#, 1),
+            ("null", "null", 1),
+            ("true", "1", 0),
+            ("[1]", "1", 0),
+            (r#"{"a":1}"#, "[1]", 0),
+            ("[1]", r#"{"a":1}"#, 0),
+        ];
+        for (doc, candidate, expected) in cases {
+            let doc = OwnedValue::build_text(Rc::new(doc.to_string()));
+            let candidate = OwnedValue::build_text(Rc::new(candidate.to_string()));
+            assert_eq!(
+                json_contains(&doc, &candidate).unwrap(),
+                OwnedValue::Integer(expected),
+                "{:?} contains {:?}",
+                doc,
+                candidate
+            );
         }
+
+        let doc = OwnedValue::build_text(Rc::new("[1]".to_string()));
+        assert_eq!(
+            json_contains(&doc, &OwnedValue::Null).unwrap(),
+            OwnedValue::Null
+        );
     }
 
     #[test]
-    fn test_get_json_blob_valid_jsonb() {
-        let binary_json = b"\x40\0\0\x01\x10\0\0\x03\x10\0\0\x03\x61\x73\x64\x61\x64\x66".to_vec();
-        let input = OwnedValue::Blob(Rc::new(binary_json));
-        let result = get_json(&input).unwrap();
-        if let OwnedValue::Text(result_str) = result {
-            assert!(result_str.value.contains("\"asd\":\"adf\""));
-            assert_eq!(result_str.subtype, TextSubtype::Json);
-        } else {
-            panic!("Expected OwnedValue::Text");
+    fn test_json_equal() {
+        let cases = [
+            (r#"{"a":1,"b":2}"#, r#"{"b":2,"a":1}"#, 1),
+            (r#" { "a" : [1, 2] } "#, r#"{"a":[1,2]}"#, 1),
+            (
+                r#"{"x":{"c":[{"e":1,"f":2}],"d":null},"y":"s"}"#,
+                r#"{"y":"s","x":{"d":null,"c":[{"f":2,"e":1}]}}"#,
+                1,
+            ),
+            (
+                r#"{"a":1,"b":[{"c":2,"d":3}]}"#,
+                r#" {"b": [{"d":3,"c":2}], "a":1.0}"#,
+                1,
+            ),
+            (r#"{"a":1,"b":2}"#, r#"{"a":1}"#, 0),
+            (r#"{"a":1}"#, r#"{"a":1,"b":2}"#, 0),
+            (r#"{"a":1}"#, r#"{"b":1}"#, 0),
+            // Array order matters
+            ("[1,2]", "[2,1]", 0),
+            ("[1,2]", "[1,2,2]", 0),
+            ("1", "1.0", 1),
+            ("1e2", "100", 1),
+            ("{a:1,}", r#"{"a":1}"#, 1),
+            (r#""1""#, "1", 0),
+            ("null", "false", 0),
+        ];
+        for (a, b, expected) in cases {
+            let a = OwnedValue::build_text(Rc::new(a.to_string()));
+            let b = OwnedValue::build_text(Rc::new(b.to_string()));
+            assert_eq!(
+                json_equal(&a, &b).unwrap(),
+                OwnedValue::Integer(expected),
+                "{:?} equals {:?}",
+                a,
+                b
+            );
         }
+
+        let a = OwnedValue::build_text(Rc::new("[1]".to_string()));
+        assert_eq!(json_equal(&OwnedValue::Null, &a).unwrap(), OwnedValue::Null);
     }
 
     #[test]
-    fn test_get_json_blob_invalid_jsonb() {
-        let binary_json: Vec<u8> = vec![0xA2, 0x62, 0x6B, 0x31, 0x62, 0x76]; // Incomplete binary JSON
-        let input = OwnedValue::Blob(Rc::new(binary_json));
-        let result = get_json(&input);
-        match result {
-            Ok(_) => panic!("Expected error for malformed JSON"),
-            Err(e) => assert!(e.to_string().contains("malformed JSON")),
+    fn test_json_concat_type_mismatch() {
+        let object = OwnedValue::build_text(Rc::new(r#"{"a":1}"#.to_string()));
+        let array = OwnedValue::build_text(Rc::new("[1]".to_string()));
+        let scalar = OwnedValue::Integer(1);
+
+        for (first, second, types) in [
+            (&object, &array, "object and array"),
+            (&array, &object, "array and object"),
+            (&array, &scalar, "array and integer"),
+        ] {
+            match json_concat(first, second) {
+                Ok(_) => panic!("Expected error for {}", types),
+                Err(e) => assert!(
+                    e.to_string().contains(&format!(
+                        "requires two objects or two arrays, got {}",
+                        types
+                    )),
+                    "{}",
+                    e
+                ),
+            }
         }
     }
 
     #[test]
-    fn test_get_json_non_text() {
-        let input = OwnedValue::Null;
-        let result = get_json(&input).unwrap();
-        if let OwnedValue::Null = result {
-            // Test passed
-        } else {
-            panic!("Expected OwnedValue::Null");
-        }
+    fn test_json_patch_object_replaces_scalar() {
+        let target = OwnedValue::build_text(Rc::new("1".to_string()));
+        let patch = OwnedValue::build_text(Rc::new(r#"{"a":{"b":null},"c":null}"#.to_string()));
+
+        let OwnedValue::Text(result) = json_patch(&target, &patch).unwrap() else {
+            panic!("Expected OwnedValue::Text");
+        };
+        assert_eq!(result.value.as_str(), r#"{"a":{}}"#);
     }
 
     #[test]
-    fn test_json_array_simple() {
-        let text = OwnedValue::build_text(Rc::new("value1".to_string()));
-        let json = OwnedValue::Text(LimboText::json(Rc::new("\"value2\"".to_string())));
-        let input = vec![text, json, OwnedValue::Integer(1), OwnedValue::Float(1.1)];
+    fn test_json_patch_array_replaces_wholesale() {
+        let target = OwnedValue::build_text(Rc::new(r#"{"a":[1,2,3]}"#.to_string()));
+        let patch = OwnedValue::build_text(Rc::new(r#"{"a":[4]}"#.to_string()));
 
-        let result = json_array(&input).unwrap();
-        if let OwnedValue::Text(res) = result {
-            assert_eq!(res.value.as_str(), "[\"value1\",\"value2\",1,1.1]");
-            assert_eq!(res.subtype, TextSubtype::Json);
-        } else {
+        let OwnedValue::Text(result) = json_patch(&target, &patch).unwrap() else {
             panic!("Expected OwnedValue::Text");
-        }
+        };
+        assert_eq!(result.value.as_str(), r#"{"a":[4]}"#);
+
+        let target = OwnedValue::build_text(Rc::new("[1,2,3]".to_string()));
+        let patch = OwnedValue::build_text(Rc::new("[4]".to_string()));
+
+        let OwnedValue::Text(result) = json_patch(&target, &patch).unwrap() else {
+            panic!("Expected OwnedValue::Text");
+        };
+        assert_eq!(result.value.as_str(), "[4]");
     }
 
     #[test]
-    fn test_json_array_empty() {
-        let input = vec![];
+    fn test_json_patch_null_argument() {
+        let json = OwnedValue::build_text(Rc::new("{}".to_string()));
 
-        let result = json_array(&input).unwrap();
-        if let OwnedValue::Text(res) = result {
-            assert_eq!(res.value.as_str(), "[]");
-            assert_eq!(res.subtype, TextSubtype::Json);
-        } else {
-            panic!("Expected OwnedValue::Text");
+        assert_eq!(
+            json_patch(&OwnedValue::Null, &json).unwrap(),
+            OwnedValue::Null
+        );
+        assert_eq!(
+            json_patch(&json, &OwnedValue::Null).unwrap(),
+            OwnedValue::Null
+        );
+    }
+
+    fn patch_ops(target: &str, ops: &str) -> crate::Result<OwnedValue> {
+        json_patch_ops(
+            &OwnedValue::build_text(Rc::new(target.to_string())),
+            &OwnedValue::build_text(Rc::new(ops.to_string())),
+            None,
+        )
+    }
+
+    fn patch_ops_text(target: &str, ops: &str) -> String {
+        match patch_ops(target, ops).unwrap() {
+            OwnedValue::Text(result) => {
+                assert_eq!(result.subtype, TextSubtype::Json);
+                result.value.to_string()
+            }
+            result => panic!("Expected OwnedValue::Text, got {:?}", result),
         }
     }
 
     #[test]
-    fn test_json_array_blob_invalid() {
-        let blob = OwnedValue::Blob(Rc::new("1".as_bytes().to_vec()));
+    fn test_json_patch_ops_add() {
+        let target = r#"{"a":[1,2,3],"b":{"c":1}}"#;
 
-        let input = vec![blob];
+        assert_eq!(
+            patch_ops_text(target, r#"[{"op":"add","path":"/a/1","value":9}]"#),
+            r#"{"a":[1,9,2,3],"b":{"c":1}}"#
+        );
+        assert_eq!(
+            patch_ops_text(target, r#"[{"op":"add","path":"/a/-","value":[4]}]"#),
+            r#"{"a":[1,2,3,[4]],"b":{"c":1}}"#
+        );
+        assert_eq!(
+            patch_ops_text(target, r#"[{"op":"add","path":"/a/3","value":4}]"#),
+            r#"{"a":[1,2,3,4],"b":{"c":1}}"#
+        );
+        assert_eq!(
+            patch_ops_text(target, r#"[{"op":"add","path":"/b/c","value":2}]"#),
+            r#"{"a":[1,2,3],"b":{"c":2}}"#
+        );
+        assert_eq!(
+            patch_ops_text(target, r#"[{"op":"add","path":"/b/0","value":2}]"#),
+            r#"{"a":[1,2,3],"b":{"c":1,"0":2}}"#
+        );
+        assert_eq!(
+            patch_ops_text(target, r#"[{"op":"add","path":"","value":null}]"#),
+            "null"
+        );
+    }
 
-        let result = json_array(&input);
+    #[test]
+    fn test_json_patch_ops_remove_and_replace() {
+        let target = r#"{"a":[1,2,3],"b~c":{"d/e":1}}"#;
 
-        match result {
-            Ok(_) => panic!("Expected error for blob input"),
-            Err(e) => assert!(e.to_string().contains("JSON cannot hold BLOB values")),
-        }
+        assert_eq!(
+            patch_ops_text(target, r#"[{"op":"remove","path":"/a/0"}]"#),
+            r#"{"a":[2,3],"b~c":{"d/e":1}}"#
+        );
+        assert_eq!(
+            patch_ops_text(target, r#"[{"op":"remove","path":"/b~0c/d~1e"}]"#),
+            r#"{"a":[1,2,3],"b~c":{}}"#
+        );
+        assert_eq!(
+            patch_ops_text(target, r#"[{"op":"replace","path":"/a/2","value":"x"}]"#),
+            r#"{"a":[1,2,"x"],"b~c":{"d/e":1}}"#
+        );
     }
 
     #[test]
-    fn test_json_array_length() {
-        let input = OwnedValue::build_text(Rc::new("[1,2,3,4]".to_string()));
-        let result = json_array_length(&input, None).unwrap();
-        if let OwnedValue::Integer(res) = result {
-            assert_eq!(res, 4);
-        } else {
-            panic!("Expected OwnedValue::Integer");
-        }
+    fn test_json_patch_ops_move_and_copy() {
+        let target = r#"{"a":[1,2,3],"b":{"c":1}}"#;
+
+        assert_eq!(
+            patch_ops_text(target, r#"[{"op":"move","from":"/a/0","path":"/a/-"}]"#),
+            r#"{"a":[2,3,1],"b":{"c":1}}"#
+        );
+        assert_eq!(
+            patch_ops_text(target, r#"[{"op":"move","from":"/b/c","path":"/a/0"}]"#),
+            r#"{"a":[1,1,2,3],"b":{}}"#
+        );
+        assert_eq!(
+            patch_ops_text(target, r#"[{"op":"copy","from":"/b","path":"/d"}]"#),
+            r#"{"a":[1,2,3],"b":{"c":1},"d":{"c":1}}"#
+        );
+        assert_eq!(
+            patch_ops_text(target, r#"[{"op":"move","from":"/b","path":"/b"}]"#),
+            target
+        );
+        assert!(patch_ops(target, r#"[{"op":"move","from":"/b","path":"/b/d"}]"#).is_err());
     }
 
     #[test]
-    fn test_json_array_length_empty() {
-        let input = OwnedValue::build_text(Rc::new("[]".to_string()));
-        let result = json_array_length(&input, None).unwrap();
-        if let OwnedValue::Integer(res) = result {
-            assert_eq!(res, 0);
-        } else {
-            panic!("Expected OwnedValue::Integer");
-        }
+    fn test_json_patch_ops_applied_in_order() {
+        assert_eq!(
+            patch_ops_text(
+                "[]",
+                r#"[{"op":"add","path":"/-","value":1},{"op":"add","path":"/0","value":0},
+                    {"op":"replace","path":"/1","value":{"x":[]}},
+                    {"op":"add","path":"/1/x/0","value":true}]"#
+            ),
+            r#"[0,{"x":[true]}]"#
+        );
+        assert_eq!(
+            patch_ops_text(
+                r#"{"a":[1,2,3]}"#,
+                r#"[{"op":"add","path":"/a/1","value":9},{"op":"remove","path":"/a/0"},
+                    {"op":"move","from":"/a/0","path":"/a/-"}]"#
+            ),
+            r#"{"a":[2,3,9]}"#
+        );
     }
 
     #[test]
-    fn test_json_array_length_root() {
-        let input = OwnedValue::build_text(Rc::new("[1,2,3,4]".to_string()));
-        let result = json_array_length(
-            &input,
-            Some(&OwnedValue::build_text(Rc::new("$".to_string()))),
-        )
-        .unwrap();
-        if let OwnedValue::Integer(res) = result {
-            assert_eq!(res, 4);
-        } else {
-            panic!("Expected OwnedValue::Integer");
+    fn test_json_patch_ops_test() {
+        let target = r#"{"a":[1,2.0,"x"],"b":{"c":1,"d":null}}"#;
+
+        assert_eq!(
+            patch_ops_text(
+                target,
+                r#"[{"op":"test","path":"/a","value":[1.0,2,"x"]},
+                    {"op":"test","path":"/b","value":{"d":null,"c":1}},
+                    {"op":"remove","path":"/b"}]"#
+            ),
+            r#"{"a":[1,2.0,"x"]}"#
+        );
+        assert_eq!(
+            patch_ops(
+                r#"{"a":1}"#,
+                r#"[{"op":"test","path":"/a","value":2},{"op":"remove","path":"/a"}]"#
+            )
+            .unwrap(),
+            OwnedValue::Null
+        );
+        for ops in [
+            r#"[{"op":"test","path":"/a/2","value":"y"}]"#,
+            r#"[{"op":"test","path":"/a/5","value":null}]"#,
+            r#"[{"op":"test","path":"/b/d","value":false}]"#,
+            r#"[{"op":"remove","path":"/a"},{"op":"test","path":"/a","value":[1,2,"x"]}]"#,
+        ] {
+            assert_eq!(patch_ops(target, ops).unwrap(), OwnedValue::Null, "{}", ops);
+        }
+
+        let failed = json_patch_ops(
+            &OwnedValue::build_text(Rc::new(target.to_string())),
+            &OwnedValue::build_text(Rc::new(
+                r#"[{"op":"test","path":"/a/2","value":"y"}]"#.to_string(),
+            )),
+            Some(&OwnedValue::Integer(1)),
+        );
+        match failed {
+            Err(crate::error::LimboError::Constraint(msg)) => {
+                assert!(msg.contains("JSON patch test failed"), "{}", msg)
+            }
+            result => panic!("Expected a constraint error, got {:?}", result),
         }
     }
 
     #[test]
-    fn test_json_array_length_not_array() {
-        let input = OwnedValue::build_text(Rc::new("{one: [1,2,3,4]}".to_string()));
-        let result = json_array_length(&input, None).unwrap();
-        if let OwnedValue::Integer(res) = result {
-            assert_eq!(res, 0);
-        } else {
-            panic!("Expected OwnedValue::Integer");
+    fn test_json_patch_ops_invalid() {
+        let target = r#"{"a":[1,2,3]}"#;
+
+        for ops in [
+            r#"{"op":"add","path":"/b","value":1}"#,
+            r#"[1]"#,
+            r#"[{"path":"/b","value":1}]"#,
+            r#"[{"op":"frobnicate","path":"/b"}]"#,
+            r#"[{"op":"add","path":"/b"}]"#,
+            r#"[{"op":"add","path":"b","value":1}]"#,
+            r#"[{"op":"add","path":"/c/d","value":1}]"#,
+            r#"[{"op":"add","path":"/a/4","value":1}]"#,
+            r#"[{"op":"add","path":"/a/01","value":1}]"#,
+            r#"[{"op":"remove","path":"/b"}]"#,
+            r#"[{"op":"remove","path":"/a/-"}]"#,
+            r#"[{"op":"replace","path":"/a/3","value":1}]"#,
+            r#"[{"op":"copy","from":"/b","path":"/c"}]"#,
+        ] {
+            assert!(patch_ops(target, ops).is_err(), "{}", ops);
         }
     }
 
     #[test]
-    fn test_json_array_length_via_prop() {
-        let input = OwnedValue::build_text(Rc::new("{one: [1,2,3,4]}".to_string()));
-        let result = json_array_length(
-            &input,
-            Some(&OwnedValue::build_text(Rc::new("$.one".to_string()))),
-        )
-        .unwrap();
-        if let OwnedValue::Integer(res) = result {
-            assert_eq!(res, 4);
-        } else {
-            panic!("Expected OwnedValue::Integer");
-        }
+    fn test_json_patch_ops_null_argument() {
+        let json = OwnedValue::build_text(Rc::new("[]".to_string()));
+
+        assert_eq!(
+            json_patch_ops(&OwnedValue::Null, &json, None).unwrap(),
+            OwnedValue::Null
+        );
+        assert_eq!(
+            json_patch_ops(&json, &OwnedValue::Null, None).unwrap(),
+            OwnedValue::Null
+        );
     }
 
     #[test]
-    fn test_json_array_length_via_index() {
-        let input = OwnedValue::build_text(Rc::new("[[1,2,3,4]]".to_string()));
-        let result = json_array_length(
-            &input,
-            Some(&OwnedValue::build_text(Rc::new("$[0]".to_string()))),
-        )
-        .unwrap();
-        if let OwnedValue::Integer(res) = result {
-            assert_eq!(res, 4);
-        } else {
-            panic!("Expected OwnedValue::Integer");
-        }
+    fn test_json_patch_ops_keeps_jsonb() {
+        let target = get_jsonb(&OwnedValue::build_text(Rc::new("[1,2]".to_string()))).unwrap();
+        let ops = OwnedValue::build_text(Rc::new(
+            r#"[{"op":"add","path":"/0","value":0}]"#.to_string(),
+        ));
+
+        let result = json_patch_ops(&target, &ops, None).unwrap();
+        assert!(matches!(result, OwnedValue::Blob(_)));
+        let OwnedValue::Text(text) = get_json(&result).unwrap() else {
+            panic!("Expected OwnedValue::Text");
+        };
+        assert_eq!(text.value.as_str(), "[0,1,2]");
     }
 
     #[test]
-    fn test_json_array_length_via_index_not_array() {
-        let input = OwnedValue::build_text(Rc::new("[1,2,3,4]".to_string()));
-        let result = json_array_length(
-            &input,
-            Some(&OwnedValue::build_text(Rc::new("$[2]".to_string()))),
-        )
-        .unwrap();
-        if let OwnedValue::Integer(res) = result {
-            assert_eq!(res, 0);
-        } else {
-            panic!("Expected OwnedValue::Integer");
-        }
+    fn test_json_group_array() {
+        let mut group = JsonGroupArray::new();
+        group.step(&OwnedValue::Integer(1)).unwrap();
+        group
+            .step(&OwnedValue::build_text(Rc::new("[2]".to_string())))
+            .unwrap();
+        group
+            .step(&OwnedValue::Text(LimboText::json(Rc::new(
+                "[3]".to_string(),
+            ))))
+            .unwrap();
+        group.step(&OwnedValue::Null).unwrap();
+
+        let OwnedValue::Text(result) = group.finalize().unwrap() else {
+            panic!("Expected OwnedValue::Text");
+        };
+        assert_eq!(result.value.as_str(), r#"[1,"[2]",[3],null]"#);
+        assert_eq!(result.subtype, TextSubtype::Json);
     }
 
     #[test]
-    fn test_json_array_length_via_index_bad_prop() {
-        let input = OwnedValue::build_text(Rc::new("{one: [1,2,3,4]}".to_string()));
-        let result = json_array_length(
-            &input,
-            Some(&OwnedValue::build_text(Rc::new("$.two".to_string()))),
-        )
-        .unwrap();
-        assert_eq!(OwnedValue::Null, result);
+    fn test_json_group_array_empty() {
+        let OwnedValue::Text(result) = JsonGroupArray::new().finalize().unwrap() else {
+            panic!("Expected OwnedValue::Text");
+        };
+        assert_eq!(result.value.as_str(), "[]");
     }
 
     #[test]
-    fn test_json_array_length_simple_json_subtype() {
-        let input = OwnedValue::build_text(Rc::new("[1,2,3]".to_string()));
-        let wrapped = get_json(&input).unwrap();
-        let result = json_array_length(&wrapped, None).unwrap();
+    fn test_json_group_array_blob_invalid() {
+        let mut group = JsonGroupArray::new();
 
-        if let OwnedValue::Integer(res) = result {
-            assert_eq!(res, 3);
-        } else {
-            panic!("Expected OwnedValue::Integer");
+        match group.step(&OwnedValue::Blob(Rc::new(vec![1]))) {
+            Ok(_) => panic!("Expected error for blob value"),
+            Err(e) => assert!(e.to_string().contains("JSON cannot hold BLOB values")),
         }
     }
 
     #[test]
-    fn test_json_extract_missing_path() {
-        let result = json_extract(
-            &OwnedValue::build_text(Rc::new("{\"a\":2}".to_string())),
-            &[OwnedValue::build_text(Rc::new("$.x".to_string()))],
-        );
+    fn test_json_group_object() {
+        let mut group = JsonGroupObject::new();
+        group
+            .step(
+                &OwnedValue::build_text(Rc::new("b".to_string())),
+                &OwnedValue::Integer(1),
+            )
+            .unwrap();
+        group
+            .step(
+                &OwnedValue::build_text(Rc::new("a".to_string())),
+                &OwnedValue::Text(LimboText::json(Rc::new("[2]".to_string()))),
+            )
+            .unwrap();
+        group
+            .step(
+                &OwnedValue::build_text(Rc::new("b".to_string())),
+                &OwnedValue::build_text(Rc::new("x".to_string())),
+            )
+            .unwrap();
 
-        match result {
-            Ok(OwnedValue::Null) => (),
-            _ => panic!("Expected null result, got: {:?}", result),
-        }
+        let OwnedValue::Text(result) = group.finalize().unwrap() else {
+            panic!("Expected OwnedValue::Text");
+        };
+        assert_eq!(result.value.as_str(), r#"{"b":"x","a":[2]}"#);
+        assert_eq!(result.subtype, TextSubtype::Json);
     }
-    #[test]
-    fn test_json_extract_null_path() {
-        let result = json_extract(
-            &OwnedValue::build_text(Rc::new("{\"a\":2}".to_string())),
-            &[OwnedValue::Null],
-        );
 
-        match result {
-            Ok(OwnedValue::Null) => (),
-            _ => panic!("Expected null result, got: {:?}", result),
-        }
+    #[test]
+    fn test_json_group_object_empty() {
+        let OwnedValue::Text(result) = JsonGroupObject::new().finalize().unwrap() else {
+            panic!("Expected OwnedValue::Text");
+        };
+        assert_eq!(result.value.as_str(), "{}");
     }
 
     #[test]
-    fn test_json_path_invalid() {
-        let result = json_extract(
-            &OwnedValue::build_text(Rc::new("{\"a\":2}".to_string())),
-            &[OwnedValue::Float(1.1)],
-        );
+    fn test_json_group_object_non_text_key() {
+        let mut group = JsonGroupObject::new();
 
-        match result {
-            Ok(_) => panic!("expected error"),
-            Err(e) => assert!(e.to_string().contains("JSON path error")),
+        match group.step(&OwnedValue::Integer(1), &OwnedValue::Integer(2)) {
+            Ok(_) => panic!("Expected error for non-text key"),
+            Err(e) => assert!(e.to_string().contains("labels must be TEXT")),
         }
     }
 
@@ -805,6 +5794,146 @@ mod tests {
         assert_eq!(result, OwnedValue::Integer(16));
     }
 
+    #[test]
+    fn test_json_error_position_counts_from_start() {
+        let input = OwnedValue::build_text(Rc::new("[1,\n2,x]".to_string()));
+        let result = json_error_position(&input).unwrap();
+        assert_eq!(result, OwnedValue::Integer(7));
+    }
+
+    #[test]
+    fn test_json_error_position_counts_characters() {
+        let input = OwnedValue::build_text(Rc::new(r#"{"é":x}"#.to_string()));
+        let result = json_error_position(&input).unwrap();
+        assert_eq!(result, OwnedValue::Integer(6));
+    }
+
+    #[test]
+    fn test_json_error_position_end_of_input() {
+        // Text that's cut short fails right past its end, like in SQLite.
+        for (json, expected) in [("[1,2", 5), (r#"{"a":1"#, 7), (r#""abc"#, 5)] {
+            let input = OwnedValue::build_text(Rc::new(json.to_string()));
+            let result = json_error_position(&input).unwrap();
+            assert_eq!(result, OwnedValue::Integer(expected), "json: {}", json);
+        }
+    }
+
+    #[test]
+    fn test_parse_error_details() {
+        let Err(err) = from_str::<Val>("[1,\n 2, xyz]") else {
+            panic!("Expected a parse error");
+        };
+        let JsonError::UnexpectedToken {
+            ref location,
+            ref token,
+        } = err
+        else {
+            panic!("Expected an unexpected token error, got {:?}", err);
+        };
+        assert_eq!((location.line, location.column, location.offset), (2, 5, 9));
+        assert_eq!(token, "xyz");
+
+        // The location is only part of the alternate form of the message
+        assert_eq!(err.to_string(), "unexpected \"xyz\"");
+        assert_eq!(
+            format!("{:#}", err),
+            "unexpected \"xyz\" at line 2, column 5"
+        );
+    }
+
+    #[test]
+    fn test_parse_error_kinds() {
+        let parse_error = |input: &str| from_str::<Val>(input).unwrap_err();
+
+        assert!(matches!(
+            parse_error("[1,x]"),
+            JsonError::UnexpectedToken { ref token, .. } if token == "x"
+        ));
+        for truncated in ["[1,", "[1,2", r#"{"a":1"#, r#"{"a""#, r#""abc"#, "[[1]"] {
+            assert!(
+                matches!(parse_error(truncated), JsonError::UnexpectedEof { .. }),
+                "{}",
+                truncated
+            );
+        }
+        assert!(matches!(
+            parse_error(r#""a\1""#),
+            JsonError::InvalidEscape { .. }
+        ));
+        assert!(matches!(
+            parse_error(r#""\uD800""#),
+            JsonError::InvalidEscape { .. }
+        ));
+        assert!(matches!(
+            parse_error("[1e999]"),
+            JsonError::InvalidNumber { .. }
+        ));
+        let nested = "[".repeat(JSON_MAX_DEPTH + 1);
+        assert!(matches!(
+            parse_error(&nested),
+            JsonError::DepthExceeded { location: Some(_) }
+        ));
+
+        // Each kind of failure has its own message
+        for (input, expected) in [
+            ("[1,x]", r#"unexpected "x" at line 1, column 4"#),
+            ("[1,\n", "unexpected end of input at line 2, column 1"),
+            ("{\"a\" 1}", r#"unexpected "\"a\"" at line 1, column 2"#),
+            (
+                r#"["a\1"]"#,
+                r#"invalid escape sequence at line 1, column 5 near "\\1""#,
+            ),
+            (
+                r#"["\u12"]"#,
+                r#"invalid escape sequence at line 1, column 5 near "\\u12""#,
+            ),
+            (
+                r#"["\uDC00"]"#,
+                r#"lone surrogate in unicode escape at line 1, column 2 near "\"\\uDC00\"""#,
+            ),
+            (
+                "[1e999]",
+                r#"error parsing number: too large at line 1, column 2 near "1e999""#,
+            ),
+            (&nested, "excessive nesting at line 1, column 1001"),
+        ] {
+            assert_eq!(format!("{:#}", parse_error(input)), expected, "{:?}", input);
+        }
+
+        // An escaped backslash doesn't start an escape
+        assert!(matches!(
+            parse_error(r#"["\\", x]"#),
+            JsonError::UnexpectedToken { ref token, .. } if token == "x"
+        ));
+    }
+
+    #[test]
+    fn test_parse_error_into_limbo_error() {
+        let err: crate::LimboError = from_str::<Val>("[1,x]").unwrap_err().into();
+        assert_eq!(
+            err.to_string(),
+            r#"Parse error: unexpected "x" at line 1, column 4"#
+        );
+
+        let err = get_json(&OwnedValue::build_text(Rc::new("[1,x]".to_string()))).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            r#"Parse error: malformed JSON: unexpected "x" at line 1, column 4"#
+        );
+    }
+
+    #[test]
+    fn test_number_error_details() {
+        let Err(err) = from_str::<Val>("[1e999]") else {
+            panic!("Expected a parse error");
+        };
+        assert_eq!(err.to_string(), "error parsing number: too large");
+        assert_eq!(
+            format!("{:#}", err),
+            "error parsing number: too large at line 1, column 2 near \"1e999\""
+        );
+    }
+
     #[test]
     fn test_json_error_position_null() {
         let input = OwnedValue::Null;
@@ -833,6 +5962,222 @@ mod tests {
         assert_eq!(result, OwnedValue::Integer(16));
     }
 
+    #[test]
+    fn test_json_valid_strict() {
+        let valid = [
+            r#"{"a":[1,2.5,-3e10,true,false,null,"x\n\u00e9"]}"#,
+            " [ ] ",
+            "0",
+            r#""just a string""#,
+        ];
+        for input in valid {
+            let input = OwnedValue::build_text(Rc::new(input.to_string()));
+            assert_eq!(json_valid(&input, None).unwrap(), OwnedValue::Integer(1));
+        }
+
+        let invalid = [
+            "{key:1}",
+            "[1,2,]",
+            "01",
+            "1.",
+            ".5",
+            "0x1F",
+            "'single'",
+            "[1] 2",
+            "[1 /* comment */]",
+            "{\"a\":1",
+            "",
+        ];
+        for input in invalid {
+            let input = OwnedValue::build_text(Rc::new(input.to_string()));
+            assert_eq!(json_valid(&input, None).unwrap(), OwnedValue::Integer(0));
+        }
+    }
+
+    #[test]
+    fn test_json_valid_json5_flag() {
+        let input = OwnedValue::build_text(Rc::new("{key:1}".to_string()));
+        assert_eq!(
+            json_valid(&input, Some(&OwnedValue::Integer(1))).unwrap(),
+            OwnedValue::Integer(0)
+        );
+        assert_eq!(
+            json_valid(&input, Some(&OwnedValue::Integer(2))).unwrap(),
+            OwnedValue::Integer(1)
+        );
+        assert_eq!(
+            json_valid(&input, Some(&OwnedValue::Integer(3))).unwrap(),
+            OwnedValue::Integer(1)
+        );
+    }
+
+    #[test]
+    fn test_json_valid_jsonb_flag() {
+        let valid = [
+            get_jsonb(&OwnedValue::build_text(Rc::new(
+                r#"{"k1":"v"}"#.to_string(),
+            )))
+            .unwrap(),
+            get_jsonb(&OwnedValue::build_text(Rc::new("[1,2]".to_string()))).unwrap(),
+        ];
+        for input in &valid {
+            for flags in [4, 8, 9] {
+                assert_eq!(
+                    json_valid(input, Some(&OwnedValue::Integer(flags))).unwrap(),
+                    OwnedValue::Integer(1)
+                );
+            }
+            assert_eq!(
+                json_valid(input, Some(&OwnedValue::Integer(1))).unwrap(),
+                OwnedValue::Integer(0)
+            );
+        }
+
+        // Same truncated JSONB as in test_get_json_blob_invalid_jsonb.
+        let truncated = OwnedValue::Blob(Rc::new(vec![0xA2, 0x62, 0x6B, 0x31, 0x62, 0x76]));
+        assert_eq!(
+            json_valid(&truncated, Some(&OwnedValue::Integer(8))).unwrap(),
+            OwnedValue::Integer(0)
+        );
+    }
+
+    #[test]
+    fn test_json_valid_jsonb_flag_text_blob() {
+        let input = OwnedValue::Blob(Rc::new(b"[1]".to_vec()));
+        for (flags, expected) in [(1, 1), (8, 0), (9, 1)] {
+            assert_eq!(
+                json_valid(&input, Some(&OwnedValue::Integer(flags))).unwrap(),
+                OwnedValue::Integer(expected),
+                "flags: {}",
+                flags
+            );
+        }
+        let text = OwnedValue::build_text(Rc::new("[1]".to_string()));
+        assert_eq!(
+            json_valid(&text, Some(&OwnedValue::Integer(8))).unwrap(),
+            OwnedValue::Integer(0)
+        );
+    }
+
+    #[test]
+    fn test_validate_matches_from_str() {
+        let inputs = [
+            "{key:1, 'b': [1.5, .5, 0xFF, Infinity, -NaN,],}",
+            "/* comment */ [null, true, \"\\u0041\"]",
+            "[1e999]",
+            "[99999999999999999999]",
+            "[+NaN]",
+            "[\"\\uD83D\\uDE00\"]",
+            "[\"\\uD83D\"]",
+            "{\"a\":}",
+            "[1,2",
+            "",
+            "''it's''",
+            "''abc",
+            "[\"a\\\n b\", '\\x41\\0']",
+            "[\"\\1\"]",
+            "[\"\u{2028}\"]",
+            "{a\\u0062\u{301}: 1, $_: 2}",
+            "{1a: 1}",
+            "[.5e-3, 5., +.5, -0x1F]",
+            "[0x, 1]",
+            "[1,,2]",
+            "/* unterminated",
+            "\u{a0}[1]\u{3000}// trailing",
+        ];
+        for input in inputs {
+            assert_eq!(
+                validate(input).is_ok(),
+                from_str::<Val>(input).is_ok(),
+                "input: {}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_json_valid_null_and_numbers() {
+        assert_eq!(
+            json_valid(&OwnedValue::Null, None).unwrap(),
+            OwnedValue::Null
+        );
+        assert_eq!(
+            json_valid(&OwnedValue::Integer(5), None).unwrap(),
+            OwnedValue::Integer(1)
+        );
+        assert_eq!(
+            json_valid(&OwnedValue::Float(-1.5), None).unwrap(),
+            OwnedValue::Integer(1)
+        );
+    }
+
+    #[test]
+    fn test_json_valid_flags_out_of_range() {
+        let input = OwnedValue::build_text(Rc::new("[]".to_string()));
+        for flags in [0, 16] {
+            match json_valid(&input, Some(&OwnedValue::Integer(flags))) {
+                Ok(_) => panic!("Expected error for flags {}", flags),
+                Err(e) => assert!(e.to_string().contains("must be between 1 and 15")),
+            }
+        }
+    }
+
+    #[test]
+    fn test_json_valid_document() {
+        let valid_document = |value: &OwnedValue, flags: Option<i64>| {
+            json_valid_document(value, flags.map(OwnedValue::Integer).as_ref()).unwrap()
+        };
+        let text = |s: &str| OwnedValue::build_text(Rc::new(s.to_string()));
+
+        for (input, expected) in [
+            ("5", 0),
+            (r#""x""#, 0),
+            ("null", 0),
+            ("true", 0),
+            ("[]", 1),
+            (" {\"a\":[1]} ", 1),
+            ("[1,]", 0),
+            ("{a:1,}", 0),
+            ("{", 0),
+        ] {
+            assert_eq!(
+                valid_document(&text(input), None),
+                OwnedValue::Integer(expected),
+                "{}",
+                input
+            );
+        }
+        for input in ["[1,]", "{a:1,}"] {
+            assert_eq!(
+                valid_document(&text(input), Some(2)),
+                OwnedValue::Integer(1),
+                "{}",
+                input
+            );
+        }
+        assert_eq!(
+            valid_document(&text("'x'"), Some(2)),
+            OwnedValue::Integer(0)
+        );
+        assert_eq!(
+            valid_document(&OwnedValue::Integer(5), None),
+            OwnedValue::Integer(0)
+        );
+        assert_eq!(valid_document(&OwnedValue::Null, None), OwnedValue::Null);
+
+        let array = get_jsonb(&text("[1]")).unwrap();
+        let scalar = get_jsonb(&text("1")).unwrap();
+        assert_eq!(valid_document(&array, Some(8)), OwnedValue::Integer(1));
+        assert_eq!(valid_document(&scalar, Some(8)), OwnedValue::Integer(0));
+
+        match json_valid_document(&text("[]"), Some(&OwnedValue::Integer(16))) {
+            Ok(result) => panic!("Expected error for flags 16, got {:?}", result),
+            Err(e) => assert!(e
+                .to_string()
+                .contains("FLAGS parameter to json_valid_document() must be between 1 and 15")),
+        }
+    }
+
     #[test]
     fn test_json_object_simple() {
         let key = OwnedValue::build_text(Rc::new("key".to_string()));
@@ -884,6 +6229,35 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_json_object_preserves_insertion_order() {
+        let input = vec![
+            OwnedValue::build_text(Rc::new("b".to_string())),
+            OwnedValue::Integer(1),
+            OwnedValue::build_text(Rc::new("a".to_string())),
+            OwnedValue::Integer(2),
+        ];
+
+        let result = json_object(&input).unwrap();
+        let OwnedValue::Text(json_text) = result else {
+            panic!("Expected OwnedValue::Text");
+        };
+        assert_eq!(json_text.value.as_str(), r#"{"b":1,"a":2}"#);
+        assert_eq!(json_text.subtype, TextSubtype::Json);
+    }
+
+    #[test]
+    fn test_json_object_blob_value_invalid() {
+        let key = OwnedValue::build_text(Rc::new("key".to_string()));
+        let value = OwnedValue::Blob(Rc::new("1".as_bytes().to_vec()));
+        let input = vec![key, value];
+
+        match json_object(&input) {
+            Ok(_) => panic!("Expected error for blob value"),
+            Err(e) => assert!(e.to_string().contains("JSON cannot hold BLOB values")),
+        }
+    }
+
     #[test]
     fn test_json_object_json_value_is_rendered_as_json() {
         let key = OwnedValue::build_text(Rc::new("key".to_string()));
@@ -947,6 +6321,30 @@ mod tests {
         assert_eq!(json_text.value.as_str(), r#"{"key":"value"}"#);
     }
 
+    #[test]
+    fn test_json_object_duplicated_keys_last_wins() {
+        let text = |s: &str| OwnedValue::build_text(Rc::new(s.to_string()));
+        let input = vec![
+            text("a"),
+            OwnedValue::Integer(1),
+            text("b"),
+            OwnedValue::Integer(3),
+            text("a"),
+            OwnedValue::Integer(2),
+        ];
+
+        let result = json_object(&input).unwrap();
+        let OwnedValue::Text(json_text) = result else {
+            panic!("Expected OwnedValue::Text");
+        };
+        assert_eq!(json_text.value.as_str(), r#"{"b":3,"a":2}"#);
+        let parsed = get_json(&OwnedValue::build_text(Rc::new(
+            r#"{"a":1,"b":3,"a":2}"#.to_string(),
+        )))
+        .unwrap();
+        assert_eq!(parsed.to_string(), json_text.value.as_str());
+    }
+
     #[test]
     fn test_json_object_empty() {
         let input = vec![];
@@ -983,4 +6381,271 @@ mod tests {
                 .contains("json_object requires an even number of values")),
         }
     }
+
+    fn nested_arrays(depth: usize) -> OwnedValue {
+        OwnedValue::build_text(Rc::new("[".repeat(depth) + &"]".repeat(depth)))
+    }
+
+    #[test]
+    fn test_json_max_depth() {
+        // Unoptimized builds need more stack per level than the 2 MiB test threads get by default,
+        // so use the main thread's usual 8 MiB.
+        let handle = std::thread::Builder::new()
+            .stack_size(8 << 20)
+            .spawn(|| get_json(&nested_arrays(JSON_MAX_DEPTH)).map(|v| v.to_string()))
+            .unwrap();
+        let result = handle.join().unwrap().unwrap();
+        assert_eq!(result.len(), 2 * JSON_MAX_DEPTH);
+    }
+
+    #[test]
+    fn test_json_excessive_nesting() {
+        for depth in [JSON_MAX_DEPTH + 1, 100_000] {
+            match get_json(&nested_arrays(depth)) {
+                Ok(_) => panic!("expected an error for depth {}", depth),
+                Err(e) => assert!(e.to_string().contains("excessive nesting")),
+            }
+        }
+    }
+
+    #[test]
+    fn test_json_excessive_nesting_in_string_and_comments() {
+        let brackets = "[".repeat(JSON_MAX_DEPTH + 1);
+        let input = format!("[\"{0}\", '{0}' /* {0} */ // {0}\n]", brackets);
+        assert!(get_json(&OwnedValue::build_text(Rc::new(input))).is_ok());
+    }
+
+    #[test]
+    fn test_json_excessive_nesting_after_escaped_quotes() {
+        let brackets = "[".repeat(JSON_MAX_DEPTH + 1);
+        let input = format!(r#"["a long string with \" and \\", "{}"]"#, brackets);
+        assert!(get_json(&OwnedValue::build_text(Rc::new(input))).is_ok());
+        let input = format!(r#"["a long string with \" and \\", {}"#, brackets);
+        match get_json(&OwnedValue::build_text(Rc::new(input))) {
+            Ok(_) => panic!("expected an error"),
+            Err(e) => assert!(e.to_string().contains("excessive nesting")),
+        }
+    }
+
+    #[test]
+    fn test_json_valid_excessive_nesting() {
+        let result = json_valid(&nested_arrays(JSON_MAX_DEPTH + 1), None).unwrap();
+        assert_eq!(result, OwnedValue::Integer(0));
+        let result = json_valid(&nested_arrays(JSON_MAX_DEPTH), None).unwrap();
+        assert_eq!(result, OwnedValue::Integer(1));
+    }
+
+    #[test]
+    fn test_json_error_position_excessive_nesting() {
+        let result = json_error_position(&nested_arrays(JSON_MAX_DEPTH + 1)).unwrap();
+        assert_eq!(result, OwnedValue::Integer(JSON_MAX_DEPTH as i64 + 1));
+    }
+
+    #[test]
+    fn test_json_limits() {
+        assert_eq!(
+            JsonLimits::default(),
+            JsonLimits {
+                max_depth: 1000,
+                max_length: 1_000_000_000
+            }
+        );
+
+        let limits = JsonLimits {
+            max_depth: 3,
+            max_length: 9,
+        };
+        // At the limits
+        assert!(from_str_with_limits::<Val>("[[[1]]]", &limits).is_ok());
+        assert!(from_str_with_limits::<Val>("[1,2,3,4]", &limits).is_ok());
+        // Over the limits
+        assert_eq!(
+            from_str_with_limits::<Val>("[[[[]]]]", &limits),
+            Err(JsonError::DepthExceeded {
+                location: Some(error::Location {
+                    line: 1,
+                    column: 4,
+                    offset: 4
+                })
+            })
+        );
+        assert_eq!(
+            from_str_with_limits::<Val>("[1,2,3,45]", &limits),
+            Err(JsonError::TooLong {
+                length: 10,
+                max_length: 9
+            })
+        );
+        // The length is checked first, without scanning for nesting.
+        assert!(matches!(
+            from_str_with_limits::<Val>("[[[[[[[[[[]]]]]]]]]]", &limits),
+            Err(JsonError::TooLong { .. })
+        ));
+
+        // Going over a limit isn't reported as malformed JSON.
+        let err = Val::parse("[1,2,3,45]", &limits).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Parse error: input too long: 10 bytes, the limit is 9"
+        );
+        let err = Val::parse("[[[[]]]]", &limits).unwrap_err();
+        assert!(err.to_string().contains("excessive nesting"), "{}", err);
+        let err = Val::parse("[1,", &limits).unwrap_err();
+        assert!(err.to_string().contains("malformed JSON"), "{}", err);
+        assert_eq!(
+            Val::parse("[1,2]", &limits).unwrap(),
+            Val::array().push(1).push(2)
+        );
+    }
+
+    #[test]
+    fn test_serialize_excessive_nesting() {
+        let mut value = Val::Null;
+        for _ in 0..=JSON_MAX_DEPTH {
+            value = Val::Array(vec![value]);
+        }
+        let err = to_string(&value).unwrap_err();
+        assert!(err.is_excessive_nesting());
+        let err = val_to_string(&value).unwrap_err();
+        assert!(err.is_excessive_nesting());
+    }
+
+    #[test]
+    fn test_val_to_string_max_depth() {
+        // Runs on the test thread's default stack, which the recursive serializer can overflow in
+        // unoptimized builds.
+        let mut value = Val::Array(vec![]);
+        for _ in 1..JSON_MAX_DEPTH {
+            value = Val::Array(vec![Val::Integer(1), value]);
+        }
+        let json = val_to_string(&value).unwrap();
+        let wrappers = JSON_MAX_DEPTH - 1;
+        assert_eq!(json, "[1,".repeat(wrappers) + "[]" + &"]".repeat(wrappers));
+    }
+
+    #[test]
+    fn test_parse_borrows_unescaped_strings() {
+        let input = r#"["plain", "esc\"aped", 'single', "\u0041", {"k": "v"}]"#;
+        let Val::Array(items) = from_str::<Val>(input).unwrap() else {
+            panic!("Expected Val::Array");
+        };
+        let strings: Vec<_> = items[..4]
+            .iter()
+            .map(|item| match item {
+                Val::String(s) => s,
+                _ => panic!("Expected Val::String"),
+            })
+            .collect();
+        assert!(matches!(strings[0], Cow::Borrowed("plain")));
+        assert!(matches!(strings[1], Cow::Owned(s) if s == "esc\"aped"));
+        assert!(matches!(strings[2], Cow::Borrowed("single")));
+        assert!(matches!(strings[3], Cow::Owned(s) if s == "A"));
+        let Val::Object(members) = &items[4] else {
+            panic!("Expected Val::Object");
+        };
+        assert!(matches!(&members["k"], Val::String(Cow::Borrowed("v"))));
+    }
+
+    #[test]
+    fn test_val_into_owned() {
+        let owned = {
+            let input = String::from(r#"{"a": ["x", "y\n"]}"#);
+            from_str::<Val>(&input).unwrap().into_owned()
+        };
+        assert_eq!(to_string(&owned).unwrap(), r#"{"a":["x","y\n"]}"#);
+        let Val::Object(members) = &owned else {
+            panic!("Expected Val::Object");
+        };
+        let Val::Array(items) = &members["a"] else {
+            panic!("Expected Val::Array");
+        };
+        assert!(matches!(&items[0], Val::String(Cow::Owned(s)) if s == "x"));
+    }
+
+    /// Strings drawn from characters that exercise escaping: quotes, backslashes, control
+    /// characters, DEL and multi-byte characters.
+    fn arb_string() -> impl proptest::strategy::Strategy<Value = String> {
+        "[a-zA-Z0-9 \"'\\\\/\n\t\u{1}\u{1f}\u{7f}é😀]{0,8}"
+    }
+
+    fn arb_val() -> impl proptest::strategy::Strategy<Value = Val<'static>> {
+        use proptest::prelude::*;
+
+        let leaf = prop_oneof![
+            Just(Val::Null),
+            any::<bool>().prop_map(Val::Bool),
+            any::<i64>().prop_map(Val::Integer),
+            // NaN is written as null, which doesn't read back as the same value, and infinities
+            // read back as floats rather than as raw numbers.
+            any::<f64>()
+                .prop_filter("finite", |f| f.is_finite())
+                .prop_map(Val::Float),
+            "-?(0|[1-9][0-9]{0,5})\\.[0-9]{1,5}(e-?[1-9][0-9]?)?".prop_map(Val::RawNumber),
+            arb_string().prop_map(|s| Val::String(s.into())),
+        ];
+        leaf.prop_recursive(4, 64, 6, |inner| {
+            prop_oneof![
+                prop::collection::vec(inner.clone(), 0..6).prop_map(Val::Array),
+                prop::collection::vec((arb_string(), inner), 0..6)
+                    .prop_map(|members| Val::Object(members.into_iter().collect())),
+            ]
+        })
+    }
+
+    /// What `value` is expected to read back as once written out: floats are written with 15
+    /// significant digits and read back as raw numbers holding that text.
+    fn round_tripped(value: &Val) -> Val<'static> {
+        match value {
+            Val::Float(_) => Val::RawNumber(to_string(value).unwrap()),
+            Val::Array(items) => Val::Array(items.iter().map(round_tripped).collect()),
+            Val::Object(members) => Val::Object(
+                members
+                    .iter()
+                    .map(|(k, v)| (k.clone(), round_tripped(v)))
+                    .collect(),
+            ),
+            _ => value.clone().into_owned(),
+        }
+    }
+
+    proptest::proptest! {
+        #[test]
+        fn test_val_round_trip(value in arb_val()) {
+            let json = to_string(&value).unwrap();
+            let parsed: Val = from_str(&json).unwrap();
+            proptest::prop_assert_eq!(&parsed, &round_tripped(&value));
+            proptest::prop_assert_eq!(to_string(&parsed).unwrap(), json);
+        }
+
+        #[test]
+        fn test_val_to_string_matches_serde(value in arb_val()) {
+            proptest::prop_assert_eq!(val_to_string(&value).unwrap(), to_string(&value).unwrap());
+
+            let mut buf = "[1,".to_string();
+            let mut expected = buf.clone();
+            write_val(&mut buf, &value).unwrap();
+            serialize_into(&mut expected, &value).unwrap();
+            proptest::prop_assert_eq!(buf, expected);
+
+            if let Val::Object(members) = &value {
+                let mut json = String::new();
+                write_object(&mut json, members).unwrap();
+                proptest::prop_assert_eq!(json, to_string(members).unwrap());
+            }
+        }
+
+        #[test]
+        fn test_validate_matches_from_str_on_json5(
+            json in "(\\[|]|\\{|}|,|:| |\n|\u{a0}|\u{2028}|\"|'|\\\\|u|x|[0-9a-f]|D8|DC|\\.|[eE]|\\+|-|null|true|Infinity|NaN|k|é|_|/\\*|\\*/|//){0,16}"
+        ) {
+            proptest::prop_assert_eq!(validate(&json).is_ok(), from_str::<Val>(&json).is_ok());
+        }
+
+        #[test]
+        fn test_top_level_type_matches_parse(value in arb_val()) {
+            let json = to_string(&value).unwrap();
+            let parsed: Val = from_str(&json).unwrap();
+            proptest::prop_assert_eq!(top_level_type(&json), Some(parsed.type_name()));
+        }
+    }
 }
@@ -0,0 +1,166 @@
+//! Finds where a string body ends or needs a closer look. Most strings are long runs of plain
+//! characters, so with the `simd` feature those are skipped 16 bytes at a time.
+
+/// Returns the index of the first byte of `bytes` that is `quote`, a backslash or a control
+/// character (below 0x20), or `bytes.len()` if there is none.
+#[inline]
+pub fn find_string_special(bytes: &[u8], quote: u8) -> usize {
+    #[cfg(all(feature = "simd", target_arch = "x86_64"))]
+    {
+        find_string_special_sse2(bytes, quote)
+    }
+    #[cfg(not(all(feature = "simd", target_arch = "x86_64")))]
+    {
+        find_string_special_scalar(bytes, quote)
+    }
+}
+
+fn is_string_special(b: u8, quote: u8) -> bool {
+    b == quote || b == b'\\' || b < 0x20
+}
+
+pub fn find_string_special_scalar(bytes: &[u8], quote: u8) -> usize {
+    bytes
+        .iter()
+        .position(|&b| is_string_special(b, quote))
+        .unwrap_or(bytes.len())
+}
+
+/// Returns whether the JSON text `json` has no whitespace outside of its strings, like the text
+/// the serializer writes. Strings must be double-quoted, as they are in strict JSON.
+pub fn is_compact(json: &str) -> bool {
+    let bytes = json.as_bytes();
+    let mut i = 0;
+    while let Some(&b) = bytes.get(i) {
+        match b {
+            b' ' | b'\t' | b'\n' | b'\r' => return false,
+            b'"' => {
+                i += 1;
+                loop {
+                    i += find_string_special(bytes.get(i..).unwrap_or_default(), b'"');
+                    match bytes.get(i) {
+                        Some(b'"') => break,
+                        // Skips the escaped character, which may be a quote.
+                        Some(b'\\') => i += 2,
+                        Some(_) => i += 1,
+                        None => return true,
+                    }
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    true
+}
+
+#[cfg(all(feature = "simd", target_arch = "x86_64"))]
+fn find_string_special_sse2(bytes: &[u8], quote: u8) -> usize {
+    use std::arch::x86_64::{
+        __m128i, _mm_cmpeq_epi8, _mm_loadu_si128, _mm_min_epu8, _mm_movemask_epi8, _mm_or_si128,
+        _mm_set1_epi8,
+    };
+
+    const LANES: usize = 16;
+    let mut i = 0;
+    // SAFETY: SSE2 is part of the x86_64 baseline, and every load reads 16 bytes that are
+    // within `bytes`; `_mm_loadu_si128` has no alignment requirement.
+    unsafe {
+        let quotes = _mm_set1_epi8(quote as i8);
+        let backslashes = _mm_set1_epi8(b'\\' as i8);
+        let max_control = _mm_set1_epi8(0x1f);
+        while i + LANES <= bytes.len() {
+            let chunk = _mm_loadu_si128(bytes.as_ptr().add(i) as *const __m128i);
+            // A byte is a control character when the unsigned minimum with 0x1f leaves it as is.
+            let control = _mm_cmpeq_epi8(_mm_min_epu8(chunk, max_control), chunk);
+            let special = _mm_or_si128(
+                _mm_or_si128(
+                    _mm_cmpeq_epi8(chunk, quotes),
+                    _mm_cmpeq_epi8(chunk, backslashes),
+                ),
+                control,
+            );
+            let mask = _mm_movemask_epi8(special);
+            if mask != 0 {
+                return i + mask.trailing_zeros() as usize;
+            }
+            i += LANES;
+        }
+    }
+    i + find_string_special_scalar(&bytes[i..], quote)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn test_find_string_special() {
+        assert_eq!(find_string_special(b"", b'"'), 0);
+        assert_eq!(find_string_special(b"abc", b'"'), 3);
+        assert_eq!(find_string_special(b"ab\"c", b'"'), 2);
+        assert_eq!(find_string_special(b"ab'c\"", b'\''), 2);
+        assert_eq!(find_string_special(b"ab'c\"", b'"'), 4);
+        assert_eq!(find_string_special(b"a\\\"", b'"'), 1);
+        assert_eq!(find_string_special(b"a\tb", b'"'), 1);
+        assert_eq!(find_string_special("é😀\"".as_bytes(), b'"'), 6);
+
+        let long = "x".repeat(40) + "\"";
+        assert_eq!(find_string_special(long.as_bytes(), b'"'), 40);
+        assert_eq!(find_string_special(&long.as_bytes()[..40], b'"'), 40);
+        let late_control = "y".repeat(33) + "\u{1f}";
+        assert_eq!(find_string_special(late_control.as_bytes(), b'"'), 33);
+        assert_eq!(find_string_special("\u{7f}\u{80}".as_bytes(), b'"'), 3);
+    }
+
+    #[test]
+    fn test_is_compact() {
+        for json in [
+            "",
+            "1",
+            r#"{"a":[1,2,{"b":null}]}"#,
+            r#"["a b","\t\n"," "]"#,
+            r#"["\" ","\\"," x"]"#,
+            r#""unterminated \"#,
+        ] {
+            assert!(is_compact(json), "{}", json);
+        }
+        for json in [
+            " 1",
+            "1\n",
+            r#"{"a": 1}"#,
+            "[1,\t2]",
+            r#"["\" " ]"#,
+            "[\r\n]",
+        ] {
+            assert!(!is_compact(json), "{}", json);
+        }
+    }
+
+    #[test]
+    fn test_find_string_special_matches_scalar() {
+        const BYTES: &[u8] = b"ab\"'\\\x00\x1f\x20\x7f\x80\xc3\xff";
+        let mut rng = rand::rngs::StdRng::seed_from_u64(0x5eed);
+        for _ in 0..2000 {
+            let len = rng.gen_range(0..80);
+            let bytes: Vec<u8> = (0..len)
+                .map(|_| {
+                    if rng.gen_bool(0.95) {
+                        b'a'
+                    } else {
+                        BYTES[rng.gen_range(0..BYTES.len())]
+                    }
+                })
+                .collect();
+            for quote in [b'"', b'\''] {
+                assert_eq!(
+                    find_string_special(&bytes, quote),
+                    find_string_special_scalar(&bytes, quote),
+                    "{:?}",
+                    bytes
+                );
+            }
+        }
+    }
+}
@@ -1,36 +1,272 @@
+use indexmap::IndexMap;
 use serde::ser::{self, Serialize};
 use std::{f32, f64, num::FpCategory};
 
 use crate::json::error::{Error, Result};
+use crate::json::{Val, JSON_MAX_DEPTH, RAW_JSON_TOKEN, RAW_NUMBER_TOKEN};
 
 /// Attempts to serialize the input as a JSON5 string (actually a JSON string).
 pub fn to_string<T>(value: &T) -> Result<String>
 where
     T: Serialize,
+{
+    let mut output = String::new();
+    serialize_into(&mut output, value)?;
+    Ok(output)
+}
+
+/// Serializes the input as a JSON string appended to `buf`, so that callers building composite
+/// JSON can reuse a single buffer. Fails for arrays and objects nested deeper than
+/// `JSON_MAX_DEPTH`. On error `buf` may be left holding partial output.
+pub fn serialize_into<T>(buf: &mut String, value: &T) -> Result<()>
+where
+    T: ?Sized + Serialize,
 {
     let mut serializer = Serializer {
-        output: String::new(),
+        output: buf,
+        depth: 0,
+        max_depth: JSON_MAX_DEPTH,
+        raw: false,
     };
-    value.serialize(&mut serializer)?;
-    Ok(serializer.output)
+    value.serialize(&mut serializer)
+}
+
+/// Writes `value` as JSON text, producing the same output as `to_string`. Unlike serde's
+/// recursive walk, arrays and objects are walked with an explicit stack, so deep values don't use
+/// any more of the call stack than shallow ones.
+pub fn val_to_string(value: &Val) -> Result<String> {
+    let mut output = String::new();
+    write_val(&mut output, value)?;
+    Ok(output)
+}
+
+/// Same as `val_to_string`, but appends to `buf` like `serialize_into`.
+pub fn write_val(buf: &mut String, value: &Val) -> Result<()> {
+    ValWriter::new(buf).write(Some(value))
+}
+
+/// Writes `members` as a JSON object appended to `buf`, without building a `Val::Object` first.
+pub fn write_object(buf: &mut String, members: &IndexMap<String, Val>) -> Result<()> {
+    let mut writer = ValWriter::new(buf);
+    writer.open('{')?;
+    writer.stack.push(Frame::Object(members.iter()));
+    writer.write(None)
+}
+
+/// Writes `value` as canonical JSON in the manner of RFC 8785: object members sorted by key,
+/// numbers in their shortest round-trip form and no whitespace, so that equal values always
+/// produce the same bytes. Unlike `val_to_string`, insertion order is not kept, and NaN and
+/// infinities are rejected since canonical JSON has no way to write them.
+pub fn val_to_canonical_string(value: &Val) -> Result<String> {
+    let mut output = String::new();
+    write_canonical_val(&mut output, value)?;
+    Ok(output)
+}
+
+/// Same as `val_to_canonical_string`, but appends to `buf` like `serialize_into`.
+pub fn write_canonical_val(buf: &mut String, value: &Val) -> Result<()> {
+    let mut writer = ValWriter::new(buf);
+    writer.canonical = true;
+    writer.write(Some(value))
 }
 
-struct Serializer {
-    output: String,
+/// An array or object being written, with the elements or members left to write.
+enum Frame<'a> {
+    Array(std::slice::Iter<'a, Val<'a>>),
+    Object(indexmap::map::Iter<'a, String, Val<'a>>),
+    /// An object written in canonical form, its members already sorted by key.
+    SortedObject(std::vec::IntoIter<(&'a String, &'a Val<'a>)>),
+}
+
+struct ValWriter<'a, 'v> {
+    output: &'a mut String,
+    /// Arrays and objects currently open, innermost last.
+    stack: Vec<Frame<'v>>,
+    max_depth: usize,
+    /// Whether to write canonical JSON, see `val_to_canonical_string`.
+    canonical: bool,
+}
+
+impl<'a, 'v> ValWriter<'a, 'v> {
+    fn new(output: &'a mut String) -> Self {
+        Self {
+            output,
+            stack: Vec::new(),
+            max_depth: JSON_MAX_DEPTH,
+            canonical: false,
+        }
+    }
+
+    /// Checks that one more array or object can be opened, then writes its opening bracket.
+    fn open(&mut self, bracket: char) -> Result<()> {
+        if self.stack.len() >= self.max_depth {
+            return Err(Error::DepthExceeded { location: None });
+        }
+        self.output.push(bracket);
+        Ok(())
+    }
+
+    /// Writes `next`, if any, then the rest of every open array and object.
+    fn write(&mut self, mut next: Option<&'v Val<'v>>) -> Result<()> {
+        loop {
+            if let Some(value) = next.take() {
+                match value {
+                    Val::Array(items) => {
+                        self.open('[')?;
+                        self.stack.push(Frame::Array(items.iter()));
+                    }
+                    Val::Object(members) if self.canonical => {
+                        self.open('{')?;
+                        let mut members = members.iter().collect::<Vec<_>>();
+                        // RFC 8785 orders keys by their UTF-16 code units, which differs from
+                        // byte order for characters outside the Basic Multilingual Plane.
+                        members.sort_by(|(a, _), (b, _)| a.encode_utf16().cmp(b.encode_utf16()));
+                        self.stack.push(Frame::SortedObject(members.into_iter()));
+                    }
+                    Val::Object(members) => {
+                        self.open('{')?;
+                        self.stack.push(Frame::Object(members.iter()));
+                    }
+                    _ if self.canonical => self.write_canonical_scalar(value)?,
+                    _ => self.write_scalar(value),
+                }
+            }
+
+            let Some(frame) = self.stack.last_mut() else {
+                return Ok(());
+            };
+            match frame {
+                Frame::Array(items) => match items.next() {
+                    Some(item) => {
+                        if !self.output.ends_with('[') {
+                            self.output.push(',');
+                        }
+                        next = Some(item);
+                    }
+                    None => {
+                        self.output.push(']');
+                        self.stack.pop();
+                    }
+                },
+                Frame::Object(members) => match members.next() {
+                    Some((key, value)) => {
+                        self.write_key(key);
+                        next = Some(value);
+                    }
+                    None => {
+                        self.output.push('}');
+                        self.stack.pop();
+                    }
+                },
+                Frame::SortedObject(members) => match members.next() {
+                    Some((key, value)) => {
+                        self.write_key(key);
+                        next = Some(value);
+                    }
+                    None => {
+                        self.output.push('}');
+                        self.stack.pop();
+                    }
+                },
+            }
+        }
+    }
+
+    /// Writes the key of the next object member, preceded by a comma unless it's the first.
+    fn write_key(&mut self, key: &str) {
+        if !self.output.ends_with('{') {
+            self.output.push(',');
+        }
+        self.output.push('"');
+        escape_into(self.output, key);
+        self.output.push_str("\":");
+    }
+
+    fn write_scalar(&mut self, value: &Val) {
+        match value {
+            Val::Null => self.output.push_str("null"),
+            Val::Bool(b) => self.output.push_str(if *b { "true" } else { "false" }),
+            Val::Integer(i) => self.output.push_str(&i.to_string()),
+            Val::Float(f) => write_f64(self.output, *f),
+            Val::RawNumber(s) => {
+                // Mirrors the serde path, which writes the text as a string and strips the quotes.
+                let mut quoted = String::from('"');
+                escape_into(&mut quoted, s);
+                quoted.push('"');
+                self.output.push_str(quoted.trim_matches('"'));
+            }
+            Val::RawJson(s) => self.output.push_str(s),
+            Val::String(s) => {
+                self.output.push('"');
+                escape_into(self.output, s);
+                self.output.push('"');
+            }
+            Val::Array(_) | Val::Object(_) => unreachable!("containers are handled by write"),
+        }
+    }
+
+    fn write_canonical_scalar(&mut self, value: &Val) -> Result<()> {
+        match value {
+            Val::Integer(i) => self.output.push_str(&format_canonical_number(*i as f64)?),
+            Val::Float(f) => self.output.push_str(&format_canonical_number(*f)?),
+            Val::RawNumber(s) => {
+                let number = s.parse::<f64>().map_err(|_| {
+                    Error::invalid_number(&format!("cannot write {} as canonical JSON", s))
+                })?;
+                self.output.push_str(&format_canonical_number(number)?);
+            }
+            Val::RawJson(s) => {
+                // Embedded JSON text has to be parsed, so that its members get sorted too.
+                let parsed = crate::json::from_str::<Val>(s)?;
+                let mut writer = ValWriter::new(self.output);
+                writer.canonical = true;
+                writer.max_depth = self.max_depth - self.stack.len();
+                writer.write(Some(&parsed))?;
+            }
+            _ => self.write_scalar(value),
+        }
+        Ok(())
+    }
+}
+
+struct Serializer<'a> {
+    output: &'a mut String,
+    /// Number of arrays and objects currently open.
+    depth: usize,
+    max_depth: usize,
+    /// Whether the next string is JSON text to write as it is, for `Val::RawJson`.
+    raw: bool,
     // TODO settings for formatting (single vs double quotes, whitespace etc)
 }
 
-impl Serializer {
+impl Serializer<'_> {
     fn call_to_string<T>(&mut self, v: &T) -> Result<()>
     where
         T: ToString,
     {
-        self.output += &v.to_string();
+        *self.output += &v.to_string();
+        Ok(())
+    }
+
+    /// Opens an array or object, failing when that nests them too deeply.
+    fn open(&mut self, s: &str) -> Result<()> {
+        self.depth += 1;
+        if self.depth > self.max_depth {
+            return Err(Error::DepthExceeded { location: None });
+        }
+        *self.output += s;
+        Ok(())
+    }
+
+    /// Closes the innermost array or object.
+    fn close(&mut self, s: &str) -> Result<()> {
+        self.depth -= 1;
+        *self.output += s;
         Ok(())
     }
 }
 
-impl ser::Serializer for &mut Serializer {
+impl ser::Serializer for &mut Serializer<'_> {
     type Ok = ();
     type Error = Error;
 
@@ -80,36 +316,22 @@ impl ser::Serializer for &mut Serializer {
 
     fn serialize_f32(self, v: f32) -> Result<()> {
         match v.classify() {
-            FpCategory::Nan => self.output += "null",
+            FpCategory::Nan => *self.output += "null",
             FpCategory::Infinite => {
                 let infinity = if v.is_sign_negative() {
                     "-9e999"
                 } else {
                     "9e999"
                 };
-                self.output += infinity
+                *self.output += infinity
             }
-            _ => self.output += &v.to_string(),
+            _ => *self.output += &format_float(v.into()),
         }
         Ok(())
     }
 
     fn serialize_f64(self, v: f64) -> Result<()> {
-        match v.classify() {
-            FpCategory::Nan => self.output += "null",
-            FpCategory::Infinite => {
-                let infinity = if v.is_sign_negative() {
-                    "-9e999"
-                } else {
-                    "9e999"
-                };
-                self.output += infinity
-            }
-            _ => {
-                let str = &format!("{:.1}", v);
-                self.output += str
-            }
-        }
+        write_f64(self.output, v);
         Ok(())
     }
 
@@ -120,9 +342,13 @@ impl ser::Serializer for &mut Serializer {
     }
 
     fn serialize_str(self, v: &str) -> Result<()> {
-        self.output += "\"";
-        self.output += &escape(v);
-        self.output += "\"";
+        if std::mem::take(&mut self.raw) {
+            *self.output += v;
+            return Ok(());
+        }
+        self.output.push('"');
+        escape_into(self.output, v);
+        self.output.push('"');
         Ok(())
     }
 
@@ -142,7 +368,7 @@ impl ser::Serializer for &mut Serializer {
     }
 
     fn serialize_unit(self) -> Result<()> {
-        self.output += "null";
+        *self.output += "null";
         Ok(())
     }
 
@@ -159,10 +385,22 @@ impl ser::Serializer for &mut Serializer {
         self.serialize_str(variant)
     }
 
-    fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<()>
+    fn serialize_newtype_struct<T>(self, name: &'static str, value: &T) -> Result<()>
     where
         T: ?Sized + Serialize,
     {
+        if name == RAW_NUMBER_TOKEN {
+            // The payload is the number's source text, which is written out without quotes.
+            let start = self.output.len();
+            value.serialize(&mut *self)?;
+            let quoted = self.output.split_off(start);
+            *self.output += quoted.trim_matches('"');
+            return Ok(());
+        }
+        if name == RAW_JSON_TOKEN {
+            // The payload is JSON text with the JSON subtype, which is trusted to be valid.
+            self.raw = true;
+        }
         value.serialize(self)
     }
 
@@ -176,16 +414,16 @@ impl ser::Serializer for &mut Serializer {
     where
         T: ?Sized + Serialize,
     {
-        self.output += "{";
+        *self.output += "{";
         variant.serialize(&mut *self)?; // TODO drop the quotes where possible
-        self.output += ":";
+        *self.output += ":";
         value.serialize(&mut *self)?;
-        self.output += "}";
+        *self.output += "}";
         Ok(())
     }
 
     fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq> {
-        self.output += "[";
+        self.open("[")?;
         Ok(self)
     }
 
@@ -208,14 +446,14 @@ impl ser::Serializer for &mut Serializer {
         variant: &'static str,
         _len: usize,
     ) -> Result<Self::SerializeTupleVariant> {
-        self.output += "{";
+        self.open("{")?;
         variant.serialize(&mut *self)?;
-        self.output += ":[";
+        *self.output += ":[";
         Ok(self)
     }
 
     fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
-        self.output += "{";
+        self.open("{")?;
         Ok(self)
     }
 
@@ -230,14 +468,14 @@ impl ser::Serializer for &mut Serializer {
         variant: &'static str,
         _len: usize,
     ) -> Result<Self::SerializeStructVariant> {
-        self.output += "{";
+        self.open("{")?;
         variant.serialize(&mut *self)?;
-        self.output += ":{";
+        *self.output += ":{";
         Ok(self)
     }
 }
 
-impl ser::SerializeSeq for &mut Serializer {
+impl ser::SerializeSeq for &mut Serializer<'_> {
     type Ok = ();
     type Error = Error;
 
@@ -246,18 +484,17 @@ impl ser::SerializeSeq for &mut Serializer {
         T: ?Sized + Serialize,
     {
         if !self.output.ends_with('[') {
-            self.output += ",";
+            *self.output += ",";
         }
         value.serialize(&mut **self)
     }
 
     fn end(self) -> Result<()> {
-        self.output += "]";
-        Ok(())
+        self.close("]")
     }
 }
 
-impl ser::SerializeTuple for &mut Serializer {
+impl ser::SerializeTuple for &mut Serializer<'_> {
     type Ok = ();
     type Error = Error;
 
@@ -273,7 +510,7 @@ impl ser::SerializeTuple for &mut Serializer {
     }
 }
 
-impl ser::SerializeTupleStruct for &mut Serializer {
+impl ser::SerializeTupleStruct for &mut Serializer<'_> {
     type Ok = ();
     type Error = Error;
 
@@ -289,7 +526,7 @@ impl ser::SerializeTupleStruct for &mut Serializer {
     }
 }
 
-impl ser::SerializeTupleVariant for &mut Serializer {
+impl ser::SerializeTupleVariant for &mut Serializer<'_> {
     type Ok = ();
     type Error = Error;
 
@@ -301,12 +538,11 @@ impl ser::SerializeTupleVariant for &mut Serializer {
     }
 
     fn end(self) -> Result<()> {
-        self.output += "]}";
-        Ok(())
+        self.close("]}")
     }
 }
 
-impl ser::SerializeMap for &mut Serializer {
+impl ser::SerializeMap for &mut Serializer<'_> {
     type Ok = ();
     type Error = Error;
 
@@ -315,7 +551,7 @@ impl ser::SerializeMap for &mut Serializer {
         T: ?Sized + Serialize,
     {
         if !self.output.ends_with('{') {
-            self.output += ",";
+            *self.output += ",";
         }
         key.serialize(&mut **self)
     }
@@ -324,17 +560,16 @@ impl ser::SerializeMap for &mut Serializer {
     where
         T: ?Sized + Serialize,
     {
-        self.output += ":";
+        *self.output += ":";
         value.serialize(&mut **self)
     }
 
     fn end(self) -> Result<()> {
-        self.output += "}";
-        Ok(())
+        self.close("}")
     }
 }
 
-impl ser::SerializeStruct for &mut Serializer {
+impl ser::SerializeStruct for &mut Serializer<'_> {
     type Ok = ();
     type Error = Error;
 
@@ -351,7 +586,7 @@ impl ser::SerializeStruct for &mut Serializer {
     }
 }
 
-impl ser::SerializeStructVariant for &mut Serializer {
+impl ser::SerializeStructVariant for &mut Serializer<'_> {
     type Ok = ();
     type Error = Error;
 
@@ -363,22 +598,144 @@ impl ser::SerializeStructVariant for &mut Serializer {
     }
 
     fn end(self) -> Result<()> {
-        self.output += "}}";
-        Ok(())
+        self.close("}}")
     }
 }
 
-fn escape(v: &str) -> String {
-    v.chars()
-        .flat_map(|c| match c {
-            '"' => vec!['\\', c],
-            '\n' => vec!['\\', 'n'],
-            '\r' => vec!['\\', 'r'],
-            '\t' => vec!['\\', 't'],
-            '\\' => vec!['\\', '\\'],
-            '\u{0008}' => vec!['\\', 'b'],
-            '\u{000c}' => vec!['\\', 'f'],
-            c => vec![c],
-        })
-        .collect()
+/// Writes `v` as a JSON number: NaN as `null` and infinities as `9e999` / `-9e999`.
+fn write_f64(output: &mut String, v: f64) {
+    match v.classify() {
+        FpCategory::Nan => *output += "null",
+        FpCategory::Infinite => {
+            let infinity = if v.is_sign_negative() {
+                "-9e999"
+            } else {
+                "9e999"
+            };
+            *output += infinity
+        }
+        _ => *output += &format_float(v),
+    }
+}
+
+/// Formats a finite float like SQLite's `%!.15g`: at most 15 significant digits, in exponential
+/// notation when the exponent is below -4 or at least 15, and always with a fractional part,
+/// eg. `0.1`, `100.0`, `1.0e+20` or `1.5e-10`. The exponent is lowercase, always signed and at
+/// least two digits long. Negative zero is written as `0.0`, since SQLite doesn't keep the sign of
+/// a zero either; `-0.0` read from JSON text is a raw number and keeps its sign.
+fn format_float(v: f64) -> String {
+    if v == 0.0 {
+        return "0.0".to_string();
+    }
+
+    // Rounds to 15 significant digits, eg. "1.23450000000000e-3"
+    let formatted = format!("{:.14e}", v);
+    let (mantissa, exponent) = formatted.split_once('e').unwrap();
+    let exponent: i32 = exponent.parse().unwrap();
+    let (sign, mantissa) = match mantissa.strip_prefix('-') {
+        Some(mantissa) => ("-", mantissa),
+        None => ("", mantissa),
+    };
+    let digits = mantissa.replace('.', "");
+    let digits = digits.trim_end_matches('0');
+
+    if !(-4..15).contains(&exponent) {
+        let (first, rest) = digits.split_at(1);
+        let rest = if rest.is_empty() { "0" } else { rest };
+        let exponent_sign = if exponent < 0 { '-' } else { '+' };
+        return format!(
+            "{sign}{first}.{rest}e{exponent_sign}{:02}",
+            exponent.unsigned_abs()
+        );
+    }
+
+    let (int_part, frac_part) = if exponent < 0 {
+        let zeros = "0".repeat(exponent.unsigned_abs() as usize - 1);
+        ("0".to_string(), format!("{zeros}{digits}"))
+    } else {
+        let int_len = exponent as usize + 1;
+        if digits.len() > int_len {
+            let (int_part, frac_part) = digits.split_at(int_len);
+            (int_part.to_string(), frac_part.to_string())
+        } else {
+            let zeros = "0".repeat(int_len - digits.len());
+            (format!("{digits}{zeros}"), String::new())
+        }
+    };
+    let frac_part = if frac_part.is_empty() {
+        "0"
+    } else {
+        &frac_part
+    };
+    format!("{sign}{int_part}.{frac_part}")
+}
+
+/// Formats a number the way RFC 8785 does, which is ECMAScript's `Number.prototype.toString`:
+/// the shortest digits that round-trip, in plain notation for exponents from -7 to 20 and in
+/// exponential notation otherwise, eg. `1`, `0.000001`, `1e+21` or `1.5e-7`. Integers are written
+/// through their nearest double, so those beyond 2^53 may lose precision.
+fn format_canonical_number(v: f64) -> Result<String> {
+    if !v.is_finite() {
+        return Err(Error::invalid_number(&format!(
+            "cannot write {} as canonical JSON",
+            v
+        )));
+    }
+    if v == 0.0 {
+        // Negative zero is written as 0 as well.
+        return Ok("0".to_string());
+    }
+
+    // The shortest round-trip digits, eg. "1.2345e-3"
+    let formatted = format!("{:e}", v.abs());
+    let (mantissa, exponent) = formatted.split_once('e').unwrap();
+    let digits = mantissa.replace('.', "");
+    let k = digits.len() as i32;
+    // The position of the decimal point relative to the first digit.
+    let n = exponent.parse::<i32>().unwrap() + 1;
+    let sign = if v.is_sign_negative() { "-" } else { "" };
+
+    let number = if k <= n && n <= 21 {
+        format!("{sign}{digits}{}", "0".repeat((n - k) as usize))
+    } else if 0 < n && n <= 21 {
+        let (int_part, frac_part) = digits.split_at(n as usize);
+        format!("{sign}{int_part}.{frac_part}")
+    } else if -6 < n && n <= 0 {
+        format!("{sign}0.{}{digits}", "0".repeat(n.unsigned_abs() as usize))
+    } else {
+        let (first, rest) = digits.split_at(1);
+        let exponent_sign = if n > 0 { '+' } else { '-' };
+        let exponent = (n - 1).unsigned_abs();
+        if rest.is_empty() {
+            format!("{sign}{first}e{exponent_sign}{exponent}")
+        } else {
+            format!("{sign}{first}.{rest}e{exponent_sign}{exponent}")
+        }
+    };
+    Ok(number)
+}
+
+/// Returns `v` escaped for a JSON string literal, without the quotes.
+pub fn escape(v: &str) -> String {
+    let mut output = String::with_capacity(v.len());
+    escape_into(&mut output, v);
+    output
+}
+
+/// Appends `v` to `output`, escaped for a JSON string literal. Control characters without a short
+/// escape are written as `\u00XX`, like SQLite does. Anything else, DEL included, is kept as is.
+fn escape_into(output: &mut String, v: &str) {
+    for c in v.chars() {
+        match c {
+            '"' => output.push_str("\\\""),
+            '\\' => output.push_str("\\\\"),
+            '\n' => output.push_str("\\n"),
+            '\r' => output.push_str("\\r"),
+            '\t' => output.push_str("\\t"),
+            '\u{0008}' => output.push_str("\\b"),
+            '\u{000c}' => output.push_str("\\f"),
+            '\u{0000}'..='\u{001f}' => output.push_str(&format!("\\u{:04x}", c as u32)),
+            c => output.push(c),
+        }
+    }
 }
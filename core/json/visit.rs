@@ -0,0 +1,182 @@
+//! Read-only traversal of `Val` trees. A `JsonVisitor` is called for each value in document
+//! order, so that counts or aggregates can be computed from a tree in place, without copying
+//! subtrees out of it.
+
+use indexmap::IndexMap;
+
+use crate::json::Val;
+
+/// What the traversal does after a callback of a `JsonVisitor`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VisitControl {
+    /// Goes on with the next value, starting with the children of an object or array.
+    Continue,
+    /// Goes on with the next value, skipping the children of the object or array just entered.
+    SkipChildren,
+    /// Ends the traversal.
+    Stop,
+}
+
+/// Callbacks for `Val::walk`. Each of them defaults to continuing the traversal.
+pub trait JsonVisitor {
+    /// Called for every value that isn't an array or an object.
+    fn visit_scalar(&mut self, _value: &Val) -> VisitControl {
+        VisitControl::Continue
+    }
+
+    /// Called for an object, before its members.
+    fn enter_object(&mut self, _members: &IndexMap<String, Val>) -> VisitControl {
+        VisitControl::Continue
+    }
+
+    /// Called after the members of an object, or right after `enter_object` when it returned
+    /// `SkipChildren`.
+    fn leave_object(&mut self) {}
+
+    /// Called for an array, before its elements.
+    fn enter_array(&mut self, _items: &[Val]) -> VisitControl {
+        VisitControl::Continue
+    }
+
+    /// Called after the elements of an array, or right after `enter_array` when it returned
+    /// `SkipChildren`.
+    fn leave_array(&mut self) {}
+}
+
+/// An array or object entered by the traversal, with the children left to visit.
+enum Frame<'a, 'v> {
+    Array(std::slice::Iter<'a, Val<'v>>),
+    Object(indexmap::map::Values<'a, String, Val<'v>>),
+}
+
+impl Val<'_> {
+    /// Visits this value and everything in it, depth first, with the members of objects in their
+    /// order. Open containers are kept on a stack rather than recursed into.
+    pub fn walk(&self, visitor: &mut impl JsonVisitor) {
+        let mut stack = Vec::new();
+        let mut next = Some(self);
+        loop {
+            if let Some(value) = next.take() {
+                let control = match value {
+                    Val::Array(items) => {
+                        let control = visitor.enter_array(items);
+                        match control {
+                            VisitControl::Continue => stack.push(Frame::Array(items.iter())),
+                            VisitControl::SkipChildren => visitor.leave_array(),
+                            VisitControl::Stop => {}
+                        }
+                        control
+                    }
+                    Val::Object(members) => {
+                        let control = visitor.enter_object(members);
+                        match control {
+                            VisitControl::Continue => stack.push(Frame::Object(members.values())),
+                            VisitControl::SkipChildren => visitor.leave_object(),
+                            VisitControl::Stop => {}
+                        }
+                        control
+                    }
+                    scalar => visitor.visit_scalar(scalar),
+                };
+                if control == VisitControl::Stop {
+                    return;
+                }
+            }
+
+            let Some(frame) = stack.last_mut() else {
+                return;
+            };
+            next = match frame {
+                Frame::Array(items) => items.next(),
+                Frame::Object(members) => members.next(),
+            };
+            if next.is_none() {
+                match stack.pop() {
+                    Some(Frame::Array(_)) => visitor.leave_array(),
+                    Some(Frame::Object(_)) => visitor.leave_object(),
+                    None => unreachable!(),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json::from_str;
+
+    /// Writes down the callbacks it gets, and skips or stops at the given array lengths.
+    #[derive(Default)]
+    struct Recorder {
+        events: Vec<String>,
+        skip_len: Option<usize>,
+        stop_len: Option<usize>,
+    }
+
+    impl JsonVisitor for Recorder {
+        fn visit_scalar(&mut self, value: &Val) -> VisitControl {
+            self.events.push(value.type_name().to_string());
+            VisitControl::Continue
+        }
+
+        fn enter_object(&mut self, members: &IndexMap<String, Val>) -> VisitControl {
+            self.events.push(format!("{{{}", members.len()));
+            VisitControl::Continue
+        }
+
+        fn leave_object(&mut self) {
+            self.events.push("}".to_string());
+        }
+
+        fn enter_array(&mut self, items: &[Val]) -> VisitControl {
+            self.events.push(format!("[{}", items.len()));
+            if Some(items.len()) == self.skip_len {
+                VisitControl::SkipChildren
+            } else if Some(items.len()) == self.stop_len {
+                VisitControl::Stop
+            } else {
+                VisitControl::Continue
+            }
+        }
+
+        fn leave_array(&mut self) {
+            self.events.push("]".to_string());
+        }
+    }
+
+    fn walk(json: &str, recorder: Recorder) -> String {
+        let mut recorder = recorder;
+        from_str::<Val>(json).unwrap().walk(&mut recorder);
+        recorder.events.join(" ")
+    }
+
+    #[test]
+    fn test_walk() {
+        let json = r#"{"a":[1,2.5,[]],"b":{},"c":[null,"x",[true]]}"#;
+        assert_eq!(
+            walk(json, Recorder::default()),
+            "{3 [3 integer real [0 ] ] {0 } [3 null text [1 true ] ] }"
+        );
+        assert_eq!(walk("7", Recorder::default()), "integer");
+    }
+
+    #[test]
+    fn test_walk_skip_and_stop() {
+        let json = r#"[[1,2],{"a":[3,4,5]},6]"#;
+        let skip = Recorder {
+            skip_len: Some(2),
+            ..Default::default()
+        };
+        assert_eq!(
+            walk(json, skip),
+            "[3 [2 ] {1 [3 integer integer integer ] } integer ]"
+        );
+
+        let stop = Recorder {
+            stop_len: Some(2),
+            ..Default::default()
+        };
+        assert_eq!(walk(json, stop), "[3 [2");
+    }
+}
@@ -44,6 +44,11 @@ pub use types::Value;
 use util::parse_schema_rows;
 
 pub use error::LimboError;
+#[cfg(feature = "json")]
+pub use json::{
+    json_each, json_tree, ArrayCursor, JsonEach, JsonEachRow, JsonLimits, JsonTree, JsonVisitor,
+    Val, VisitControl,
+};
 use translate::select::prepare_select_plan;
 pub type Result<T, E = LimboError> = std::result::Result<T, E>;
 
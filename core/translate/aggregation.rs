@@ -233,6 +233,51 @@ pub fn translate_aggregation_step(
             });
             target_register
         }
+        #[cfg(feature = "json")]
+        AggFunc::JsonGroupArray => {
+            if agg.args.len() != 1 {
+                crate::bail_parse_error!("json_group_array bad number of arguments");
+            }
+            let expr = &agg.args[0];
+            let expr_reg = program.alloc_register();
+            let _ = translate_expr(program, Some(referenced_tables), expr, expr_reg, resolver)?;
+            program.emit_insn(Insn::AggStep {
+                acc_reg: target_register,
+                col: expr_reg,
+                delimiter: 0,
+                func: AggFunc::JsonGroupArray,
+            });
+            target_register
+        }
+        #[cfg(feature = "json")]
+        AggFunc::JsonGroupObject => {
+            if agg.args.len() != 2 {
+                crate::bail_parse_error!("json_group_object bad number of arguments");
+            }
+            let key_reg = program.alloc_register();
+            let value_reg = program.alloc_register();
+            translate_expr(
+                program,
+                Some(referenced_tables),
+                &agg.args[0],
+                key_reg,
+                resolver,
+            )?;
+            translate_expr(
+                program,
+                Some(referenced_tables),
+                &agg.args[1],
+                value_reg,
+                resolver,
+            )?;
+            program.emit_insn(Insn::AggStep {
+                acc_reg: target_register,
+                col: key_reg,
+                delimiter: value_reg,
+                func: AggFunc::JsonGroupObject,
+            });
+            target_register
+        }
         AggFunc::External(ref func) => {
             let expr_reg = program.alloc_register();
             let argc = func.agg_args().map_err(|_| {
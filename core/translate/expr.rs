@@ -128,6 +128,7 @@ macro_rules! expect_arguments_min {
     }};
 }
 
+#[cfg(feature = "json")]
 macro_rules! expect_arguments_even {
     (
         $args:expr,
@@ -146,6 +147,27 @@ macro_rules! expect_arguments_even {
     }};
 }
 
+#[cfg(feature = "json")]
+macro_rules! expect_arguments_odd {
+    (
+        $args:expr,
+        $func:ident
+    ) => {{
+        let args = if let Some(args) = $args {
+            if args.len() % 2 == 0 {
+                crate::bail_parse_error!(
+                    "{} function requires an odd number of arguments",
+                    $func.to_string()
+                );
+            }
+            args
+        } else {
+            crate::bail_parse_error!("{} function with no arguments", $func.to_string());
+        };
+        args
+    }};
+}
+
 pub fn translate_condition_expr(
     program: &mut ProgramBuilder,
     referenced_tables: &[TableReference],
@@ -834,7 +856,12 @@ pub fn translate_expr(
                 }
                 #[cfg(feature = "json")]
                 Func::Json(j) => match j {
-                    JsonFunc::Json => {
+                    JsonFunc::Json
+                    | JsonFunc::Jsonb
+                    | JsonFunc::JsonQuote
+                    | JsonFunc::JsonCanonicalize
+                    | JsonFunc::JsonEscape
+                    | JsonFunc::JsonUnescape => {
                         let args = expect_arguments_exact!(args, 1, j);
 
                         translate_function(
@@ -846,7 +873,37 @@ pub fn translate_expr(
                             func_ctx,
                         )
                     }
-                    JsonFunc::JsonArray | JsonFunc::JsonExtract => translate_function(
+                    JsonFunc::JsonExtract => {
+                        let args = args.as_deref().unwrap_or_default();
+                        let start_reg = program.alloc_registers(args.len());
+                        // When all the paths are string literals, they are compiled once for the
+                        // whole statement instead of for every row.
+                        let constant_mask = (args.len() > 1
+                            && args[1..].iter().all(|arg| {
+                                matches!(arg, ast::Expr::Literal(ast::Literal::String(_)))
+                            })) as i32;
+
+                        for (i, arg) in args.iter().enumerate() {
+                            translate_expr(
+                                program,
+                                referenced_tables,
+                                arg,
+                                start_reg + i,
+                                resolver,
+                            )?;
+                            if i > 0 && constant_mask > 0 {
+                                program.mark_last_insn_constant();
+                            }
+                        }
+                        program.emit_insn(Insn::Function {
+                            constant_mask,
+                            start_reg,
+                            dest: target_register,
+                            func: func_ctx,
+                        });
+                        Ok(target_register)
+                    }
+                    JsonFunc::JsonArray | JsonFunc::JsonbArray => translate_function(
                         program,
                         args.as_deref().unwrap_or_default(),
                         referenced_tables,
@@ -859,7 +916,12 @@ pub fn translate_expr(
                             "These two functions are only reachable via the -> and ->> operators"
                         )
                     }
-                    JsonFunc::JsonArrayLength | JsonFunc::JsonType => {
+                    JsonFunc::JsonArrayLength
+                    | JsonFunc::JsonKeys
+                    | JsonFunc::JsonValues
+                    | JsonFunc::JsonType
+                    | JsonFunc::JsonValid
+                    | JsonFunc::JsonValidDocument => {
                         let args = expect_arguments_max!(args, 2, j);
 
                         translate_function(
@@ -896,7 +958,7 @@ pub fn translate_expr(
                         });
                         Ok(target_register)
                     }
-                    JsonFunc::JsonObject => {
+                    JsonFunc::JsonObject | JsonFunc::JsonbObject => {
                         let args = expect_arguments_even!(args, j);
 
                         translate_function(
@@ -908,6 +970,75 @@ pub fn translate_expr(
                             func_ctx,
                         )
                     }
+                    JsonFunc::JsonPatch
+                    | JsonFunc::JsonConcat
+                    | JsonFunc::JsonContains
+                    | JsonFunc::JsonEqual => {
+                        let args = expect_arguments_exact!(args, 2, j);
+
+                        translate_function(
+                            program,
+                            args,
+                            referenced_tables,
+                            resolver,
+                            target_register,
+                            func_ctx,
+                        )
+                    }
+                    JsonFunc::JsonPatchOps => {
+                        let args = if let Some(args) = args {
+                            if !(args.len() == 2 || args.len() == 3) {
+                                crate::bail_parse_error!(
+                                    "{} function with wrong number of arguments",
+                                    j.to_string()
+                                )
+                            }
+                            args
+                        } else {
+                            crate::bail_parse_error!(
+                                "{} function with no arguments",
+                                j.to_string()
+                            );
+                        };
+
+                        translate_function(
+                            program,
+                            args,
+                            referenced_tables,
+                            resolver,
+                            target_register,
+                            func_ctx,
+                        )
+                    }
+                    JsonFunc::JsonInsert
+                    | JsonFunc::JsonbInsert
+                    | JsonFunc::JsonReplace
+                    | JsonFunc::JsonbReplace
+                    | JsonFunc::JsonSet
+                    | JsonFunc::JsonbSet => {
+                        let args = expect_arguments_odd!(args, j);
+
+                        translate_function(
+                            program,
+                            args,
+                            referenced_tables,
+                            resolver,
+                            target_register,
+                            func_ctx,
+                        )
+                    }
+                    JsonFunc::JsonRemove | JsonFunc::JsonbRemove | JsonFunc::JsonQuery => {
+                        let args = expect_arguments_min!(args, 1, j);
+
+                        translate_function(
+                            program,
+                            args,
+                            referenced_tables,
+                            resolver,
+                            target_register,
+                            func_ctx,
+                        )
+                    }
                 },
                 Func::Scalar(srf) => {
                     match srf {
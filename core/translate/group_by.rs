@@ -610,6 +610,42 @@ pub fn translate_aggregation_step_groupby(
             });
             target_register
         }
+        #[cfg(feature = "json")]
+        AggFunc::JsonGroupArray => {
+            if agg.args.len() != 1 {
+                crate::bail_parse_error!("json_group_array bad number of arguments");
+            }
+            let expr_reg = program.alloc_register();
+            emit_column(program, expr_reg);
+            program.emit_insn(Insn::AggStep {
+                acc_reg: target_register,
+                col: expr_reg,
+                delimiter: 0,
+                func: AggFunc::JsonGroupArray,
+            });
+            target_register
+        }
+        #[cfg(feature = "json")]
+        AggFunc::JsonGroupObject => {
+            if agg.args.len() != 2 {
+                crate::bail_parse_error!("json_group_object bad number of arguments");
+            }
+            let key_reg = program.alloc_register();
+            let value_reg = program.alloc_register();
+            emit_column(program, key_reg);
+            program.emit_insn(Insn::Column {
+                cursor_id: group_by_sorter_cursor_id,
+                column: cursor_index + 1,
+                dest: value_reg,
+            });
+            program.emit_insn(Insn::AggStep {
+                acc_reg: target_register,
+                col: key_reg,
+                delimiter: value_reg,
+                func: AggFunc::JsonGroupObject,
+            });
+            target_register
+        }
         AggFunc::External(_) => {
             todo!("External aggregate functions are not yet supported in GROUP BY");
         }
@@ -48,6 +48,9 @@ impl LimboText {
         }
     }
 
+    /// Text that is known to be JSON, as produced by the JSON functions. Those write compact
+    /// JSON, and text with whitespace outside of its strings is compacted when it's embedded into
+    /// another JSON value.
     pub fn json(value: Rc<String>) -> Self {
         Self {
             value,
@@ -105,6 +108,11 @@ impl Display for OwnedValue {
                 AggContext::Max(max) => write!(f, "{}", max.as_ref().unwrap_or(&Self::Null)),
                 AggContext::Min(min) => write!(f, "{}", min.as_ref().unwrap_or(&Self::Null)),
                 AggContext::GroupConcat(s) => write!(f, "{}", s),
+                // JSON aggregates are replaced by their result when finalized
+                #[cfg(feature = "json")]
+                AggContext::JsonGroupArray(_) | AggContext::JsonGroupObject(_) => {
+                    write!(f, "{}", Self::Null)
+                }
                 AggContext::External(v) => {
                     write!(f, "{}", v.finalized_value.as_ref().unwrap_or(&Self::Null))
                 }
@@ -172,6 +180,10 @@ pub enum AggContext {
     Max(Option<OwnedValue>),
     Min(Option<OwnedValue>),
     GroupConcat(OwnedValue),
+    #[cfg(feature = "json")]
+    JsonGroupArray(crate::json::JsonGroupArray),
+    #[cfg(feature = "json")]
+    JsonGroupObject(crate::json::JsonGroupObject),
     External(ExternalAggState),
 }
 
@@ -195,6 +207,8 @@ impl AggContext {
             Self::Max(max) => max.as_ref().unwrap_or(&NULL),
             Self::Min(min) => min.as_ref().unwrap_or(&NULL),
             Self::GroupConcat(s) => s,
+            #[cfg(feature = "json")]
+            Self::JsonGroupArray(_) | Self::JsonGroupObject(_) => &NULL,
             Self::External(ext_state) => ext_state.finalized_value.as_ref().unwrap_or(&NULL),
         }
     }
@@ -414,6 +428,8 @@ pub fn to_value(value: &OwnedValue) -> Value<'_> {
                 None => Value::Null,
             },
             AggContext::GroupConcat(s) => to_value(s),
+            #[cfg(feature = "json")]
+            AggContext::JsonGroupArray(_) | AggContext::JsonGroupObject(_) => Value::Null,
             AggContext::External(ext_state) => to_value(
                 ext_state
                     .finalized_value
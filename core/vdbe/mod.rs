@@ -39,9 +39,16 @@ use crate::vdbe::builder::CursorType;
 use crate::vdbe::insn::Insn;
 #[cfg(feature = "json")]
 use crate::{
-    function::JsonFunc, json::get_json, json::json_array, json::json_array_length,
-    json::json_arrow_extract, json::json_arrow_shift_extract, json::json_error_position,
-    json::json_extract, json::json_object, json::json_type,
+    function::JsonFunc, json::compile_json_paths, json::get_json_with_format, json::json_array,
+    json::json_array_length, json::json_arrow_extract, json::json_arrow_shift_extract,
+    json::json_canonicalize, json::json_concat, json::json_contains, json::json_equal,
+    json::json_error_position, json::json_escape, json::json_extract, json::json_extract_compiled,
+    json::json_insert, json::json_keys, json::json_object, json::json_patch, json::json_patch_ops,
+    json::json_query, json::json_quote, json::json_remove, json::json_replace, json::json_set,
+    json::json_type, json::json_unescape, json::json_valid, json::json_valid_document,
+    json::json_values, json::jsonb_array, json::jsonb_insert, json::jsonb_object,
+    json::jsonb_remove, json::jsonb_replace, json::jsonb_set, json::CompiledPath,
+    json::JsonGroupArray, json::JsonGroupObject, json::JsonRepr,
 };
 use crate::{resolve_ext_path, Connection, Result, Rows, TransactionState, DATABASE_VERSION};
 use datetime::{exec_date, exec_datetime_full, exec_julianday, exec_time, exec_unixepoch};
@@ -56,6 +63,8 @@ use regex::{Regex, RegexBuilder};
 use sorter::Sorter;
 use std::borrow::BorrowMut;
 use std::cell::{Cell, RefCell};
+#[cfg(feature = "json")]
+use std::collections::hash_map::Entry;
 use std::collections::{BTreeMap, HashMap};
 use std::num::NonZero;
 use std::rc::{Rc, Weak};
@@ -202,6 +211,9 @@ pub struct ProgramState {
     deferred_seek: Option<(CursorID, CursorID)>,
     ended_coroutine: HashMap<usize, bool>, // flag to indicate that a coroutine has ended (key is the yield register)
     regex_cache: RegexCache,
+    /// Compiled constant paths of json_extract, by the address of the instruction using them.
+    #[cfg(feature = "json")]
+    json_path_cache: HashMap<InsnReference, Vec<Option<CompiledPath>>>,
     interrupted: bool,
     parameters: HashMap<NonZero<usize>, OwnedValue>,
 }
@@ -225,6 +237,8 @@ impl ProgramState {
             deferred_seek: None,
             ended_coroutine: HashMap::new(),
             regex_cache: RegexCache::new(),
+            #[cfg(feature = "json")]
+            json_path_cache: HashMap::new(),
             interrupted: false,
             parameters: HashMap::new(),
         }
@@ -1312,6 +1326,14 @@ impl Program {
                                     OwnedValue::build_text(Rc::new("".to_string())),
                                 )))
                             }
+                            #[cfg(feature = "json")]
+                            AggFunc::JsonGroupArray => OwnedValue::Agg(Box::new(
+                                AggContext::JsonGroupArray(JsonGroupArray::new()),
+                            )),
+                            #[cfg(feature = "json")]
+                            AggFunc::JsonGroupObject => OwnedValue::Agg(Box::new(
+                                AggContext::JsonGroupObject(JsonGroupObject::new()),
+                            )),
                             AggFunc::External(func) => match func.as_ref() {
                                 ExtFunc::Aggregate {
                                     init,
@@ -1473,6 +1495,31 @@ impl Program {
                                 *acc += col;
                             }
                         }
+                        #[cfg(feature = "json")]
+                        AggFunc::JsonGroupArray => {
+                            let col = state.registers[*col].clone();
+                            let OwnedValue::Agg(agg) = state.registers[*acc_reg].borrow_mut()
+                            else {
+                                unreachable!();
+                            };
+                            let AggContext::JsonGroupArray(group) = agg.borrow_mut() else {
+                                unreachable!();
+                            };
+                            group.step(&col)?;
+                        }
+                        #[cfg(feature = "json")]
+                        AggFunc::JsonGroupObject => {
+                            let key = state.registers[*col].clone();
+                            let value = state.registers[*delimiter].clone();
+                            let OwnedValue::Agg(agg) = state.registers[*acc_reg].borrow_mut()
+                            else {
+                                unreachable!();
+                            };
+                            let AggContext::JsonGroupObject(group) = agg.borrow_mut() else {
+                                unreachable!();
+                            };
+                            group.step(&key, &value)?;
+                        }
                         AggFunc::External(_) => {
                             let (step_fn, state_ptr, argc) = {
                                 let OwnedValue::Agg(agg) = &state.registers[*acc_reg] else {
@@ -1512,6 +1559,20 @@ impl Program {
                             AggFunc::Max => {}
                             AggFunc::Min => {}
                             AggFunc::GroupConcat | AggFunc::StringAgg => {}
+                            #[cfg(feature = "json")]
+                            AggFunc::JsonGroupArray => {
+                                let AggContext::JsonGroupArray(group) = agg.borrow_mut() else {
+                                    unreachable!();
+                                };
+                                state.registers[*register] = group.finalize()?;
+                            }
+                            #[cfg(feature = "json")]
+                            AggFunc::JsonGroupObject => {
+                                let AggContext::JsonGroupObject(group) = agg.borrow_mut() else {
+                                    unreachable!();
+                                };
+                                state.registers[*register] = group.finalize()?;
+                            }
                             AggFunc::External(_) => {
                                 agg.compute_external();
                             }
@@ -1525,6 +1586,16 @@ impl Program {
                                 AggFunc::Count | AggFunc::Count0 => {
                                     state.registers[*register] = OwnedValue::Integer(0);
                                 }
+                                #[cfg(feature = "json")]
+                                AggFunc::JsonGroupArray => {
+                                    state.registers[*register] =
+                                        JsonGroupArray::new().finalize()?;
+                                }
+                                #[cfg(feature = "json")]
+                                AggFunc::JsonGroupObject => {
+                                    state.registers[*register] =
+                                        JsonGroupObject::new().finalize()?;
+                                }
                                 _ => {}
                             }
                         }
@@ -1615,21 +1686,48 @@ impl Program {
                     match &func.func {
                         #[cfg(feature = "json")]
                         crate::function::Func::Json(json_func) => match json_func {
-                            JsonFunc::Json => {
+                            JsonFunc::Json | JsonFunc::Jsonb => {
+                                let json_value = &state.registers[*start_reg];
+                                let format = match json_func {
+                                    JsonFunc::Json => JsonRepr::Text,
+                                    JsonFunc::Jsonb => JsonRepr::Jsonb,
+                                    _ => unreachable!(),
+                                };
+                                match get_json_with_format(json_value, format) {
+                                    Ok(json) => state.registers[*dest] = json,
+                                    Err(e) => return Err(e),
+                                }
+                            }
+                            JsonFunc::JsonQuote
+                            | JsonFunc::JsonCanonicalize
+                            | JsonFunc::JsonEscape
+                            | JsonFunc::JsonUnescape => {
                                 let json_value = &state.registers[*start_reg];
-                                let json_str = get_json(json_value);
+                                let json_func = match json_func {
+                                    JsonFunc::JsonQuote => json_quote,
+                                    JsonFunc::JsonCanonicalize => json_canonicalize,
+                                    JsonFunc::JsonEscape => json_escape,
+                                    JsonFunc::JsonUnescape => json_unescape,
+                                    _ => unreachable!(),
+                                };
+                                let json_str = json_func(json_value);
                                 match json_str {
                                     Ok(json) => state.registers[*dest] = json,
                                     Err(e) => return Err(e),
                                 }
                             }
-                            JsonFunc::JsonArray | JsonFunc::JsonObject => {
+                            JsonFunc::JsonArray
+                            | JsonFunc::JsonbArray
+                            | JsonFunc::JsonObject
+                            | JsonFunc::JsonbObject => {
                                 let reg_values =
                                     &state.registers[*start_reg..*start_reg + arg_count];
 
                                 let json_func = match json_func {
                                     JsonFunc::JsonArray => json_array,
+                                    JsonFunc::JsonbArray => jsonb_array,
                                     JsonFunc::JsonObject => json_object,
+                                    JsonFunc::JsonbObject => jsonb_object,
                                     _ => unreachable!(),
                                 };
                                 let json_result = json_func(reg_values);
@@ -1647,7 +1745,19 @@ impl Program {
                                         let reg_values = &state.registers
                                             [*start_reg + 1..*start_reg + arg_count];
 
-                                        json_extract(val, reg_values)
+                                        // Constant paths are only compiled for the first row
+                                        // that needs them.
+                                        if *constant_mask > 0 && *val != OwnedValue::Null {
+                                            let paths =
+                                                match state.json_path_cache.entry(state.pc) {
+                                                    Entry::Occupied(entry) => entry.into_mut(),
+                                                    Entry::Vacant(entry) => entry
+                                                        .insert(compile_json_paths(reg_values)?),
+                                                };
+                                            json_extract_compiled(val, paths)
+                                        } else {
+                                            json_extract(val, reg_values)
+                                        }
                                     }
                                 };
 
@@ -1656,6 +1766,79 @@ impl Program {
                                     Err(e) => return Err(e),
                                 }
                             }
+                            JsonFunc::JsonInsert
+                            | JsonFunc::JsonbInsert
+                            | JsonFunc::JsonReplace
+                            | JsonFunc::JsonbReplace
+                            | JsonFunc::JsonSet
+                            | JsonFunc::JsonbSet => {
+                                let json = &state.registers[*start_reg];
+                                let args = &state.registers[*start_reg + 1..*start_reg + arg_count];
+                                let json_value = match json_func {
+                                    JsonFunc::JsonInsert => json_insert(json, args),
+                                    JsonFunc::JsonbInsert => jsonb_insert(json, args),
+                                    JsonFunc::JsonReplace => json_replace(json, args),
+                                    JsonFunc::JsonbReplace => jsonb_replace(json, args),
+                                    JsonFunc::JsonSet => json_set(json, args),
+                                    JsonFunc::JsonbSet => jsonb_set(json, args),
+                                    _ => unreachable!(),
+                                };
+
+                                match json_value {
+                                    Ok(json) => state.registers[*dest] = json,
+                                    Err(e) => return Err(e),
+                                }
+                            }
+                            JsonFunc::JsonRemove | JsonFunc::JsonbRemove | JsonFunc::JsonQuery => {
+                                let json = &state.registers[*start_reg];
+                                let paths =
+                                    &state.registers[*start_reg + 1..*start_reg + arg_count];
+                                let json_func = match json_func {
+                                    JsonFunc::JsonRemove => json_remove,
+                                    JsonFunc::JsonbRemove => jsonb_remove,
+                                    JsonFunc::JsonQuery => json_query,
+                                    _ => unreachable!(),
+                                };
+
+                                match json_func(json, paths) {
+                                    Ok(json) => state.registers[*dest] = json,
+                                    Err(e) => return Err(e),
+                                }
+                            }
+                            JsonFunc::JsonPatch
+                            | JsonFunc::JsonConcat
+                            | JsonFunc::JsonContains
+                            | JsonFunc::JsonEqual => {
+                                assert_eq!(arg_count, 2);
+                                let target = &state.registers[*start_reg];
+                                let patch = &state.registers[*start_reg + 1];
+                                let json_func = match json_func {
+                                    JsonFunc::JsonPatch => json_patch,
+                                    JsonFunc::JsonConcat => json_concat,
+                                    JsonFunc::JsonContains => json_contains,
+                                    JsonFunc::JsonEqual => json_equal,
+                                    _ => unreachable!(),
+                                };
+
+                                match json_func(target, patch) {
+                                    Ok(json) => state.registers[*dest] = json,
+                                    Err(e) => return Err(e),
+                                }
+                            }
+                            JsonFunc::JsonPatchOps => {
+                                let target = &state.registers[*start_reg];
+                                let ops = &state.registers[*start_reg + 1];
+                                let strict = if arg_count > 2 {
+                                    Some(&state.registers[*start_reg + 2])
+                                } else {
+                                    None
+                                };
+
+                                match json_patch_ops(target, ops, strict) {
+                                    Ok(json) => state.registers[*dest] = json,
+                                    Err(e) => return Err(e),
+                                }
+                            }
                             JsonFunc::JsonArrowExtract | JsonFunc::JsonArrowShiftExtract => {
                                 assert_eq!(arg_count, 2);
                                 let json = &state.registers[*start_reg];
@@ -1671,7 +1854,12 @@ impl Program {
                                     Err(e) => return Err(e),
                                 }
                             }
-                            JsonFunc::JsonArrayLength | JsonFunc::JsonType => {
+                            JsonFunc::JsonArrayLength
+                            | JsonFunc::JsonKeys
+                            | JsonFunc::JsonValues
+                            | JsonFunc::JsonType
+                            | JsonFunc::JsonValid
+                            | JsonFunc::JsonValidDocument => {
                                 let json_value = &state.registers[*start_reg];
                                 let path_value = if arg_count > 1 {
                                     Some(&state.registers[*start_reg + 1])
@@ -1682,7 +1870,13 @@ impl Program {
                                     JsonFunc::JsonArrayLength => {
                                         json_array_length(json_value, path_value)
                                     }
+                                    JsonFunc::JsonKeys => json_keys(json_value, path_value),
+                                    JsonFunc::JsonValues => json_values(json_value, path_value),
                                     JsonFunc::JsonType => json_type(json_value, path_value),
+                                    JsonFunc::JsonValid => json_valid(json_value, path_value),
+                                    JsonFunc::JsonValidDocument => {
+                                        json_valid_document(json_value, path_value)
+                                    }
                                     _ => unreachable!(),
                                 };
 